@@ -0,0 +1,44 @@
+use std::{env, io::ErrorKind, path::Path};
+
+use rocket::tokio::fs;
+use sha1::{Digest, Sha1};
+
+// Length of the SHA-1 prefix used to name the range files
+const PREFIX_LENGTH: usize = 5;
+
+/// Whether a local breached password list is configured (`BREACHED_PASSWORDS_PATH`)
+pub fn is_configured() -> bool {
+    env::var("BREACHED_PASSWORDS_PATH").is_ok()
+}
+
+/// Looks the password up in the local list, which uses the k-anonymity range format:
+/// one `<PREFIX>.txt` file per 5 character SHA-1 prefix, each line `<SUFFIX>:<COUNT>`
+pub async fn is_breached(password: &str) -> Result<bool, String> {
+    let path = env::var("BREACHED_PASSWORDS_PATH")
+        .map_err(|_| "BREACHED_PASSWORDS_PATH is not set".to_string())?;
+
+    let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+    let range_file = Path::new(&path).join(format!("{}.txt", prefix));
+    let range = match fs::read_to_string(&range_file).await {
+        Ok(range) => range,
+        // No file means no breached password shares the prefix
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => {
+            return Err(format!(
+                "Failed to read {}: {}",
+                range_file.to_string_lossy(),
+                err
+            ))
+        }
+    };
+
+    Ok(range.lines().any(|line| match line.trim().split_once(':') {
+        // Padding entries have a count of 0
+        Some((line_suffix, count)) => {
+            line_suffix.eq_ignore_ascii_case(suffix) && count.trim().parse::<u64>().unwrap_or(1) > 0
+        }
+        None => line.trim().eq_ignore_ascii_case(suffix),
+    }))
+}
@@ -0,0 +1,23 @@
+use rocket::{http::Status, outcome::Outcome, request::FromRequest, Request};
+
+/// Information about the client a request was sent from, stored with new sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<ClientInfo, (Status, ()), Status> {
+        Outcome::Success(ClientInfo {
+            ip_address: request.client_ip().map(|ip| ip.to_string()),
+            user_agent: request
+                .headers()
+                .get_one("User-Agent")
+                .map(|user_agent| user_agent.to_string()),
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashMap, env, time::Duration};
+
+use mongodb::bson::Uuid;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+
+use crate::{
+    db::AuthRsDatabase,
+    errors::{ApiError, ApiResult},
+    mail::{self, Mail},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        user::User,
+    },
+    store,
+    utils::{hash_token::hash_token, url_token::generate_url_token},
+    SETTINGS,
+};
+
+use super::oidc::get_frontend_url;
+
+const EMAIL_VERIFICATIONS_NAMESPACE: &str = "email-verifications";
+
+/// A pending verification, stored under the hash of its token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct EmailVerification {
+    user_id: Uuid,
+    // Only this address gets verified, links sent to a previous address stop working
+    email: String,
+}
+
+/// How long a verification link stays valid (`EMAIL_VERIFICATION_TTL_HOURS`)
+fn get_verification_ttl() -> Duration {
+    let hours = env::var("EMAIL_VERIFICATION_TTL_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse::<u64>().ok())
+        .unwrap_or(24);
+    Duration::from_secs(hours * 60 * 60)
+}
+
+/// Whether the user has to verify the email before logging in or authorizing applications
+pub async fn is_verification_required(user: &User) -> bool {
+    !user.email_verified && SETTINGS.lock().await.require_email_verification
+}
+
+/// Sends a verification link to the current email of the user
+pub async fn send_verification_mail(user: &User) -> ApiResult<()> {
+    let token = generate_url_token();
+    let ttl = get_verification_ttl();
+    let verification = EmailVerification {
+        user_id: user.id,
+        email: user.email.clone(),
+    };
+    store::put(
+        EMAIL_VERIFICATIONS_NAMESPACE,
+        &hash_token(&token),
+        &verification,
+        ttl,
+    )
+    .await?;
+
+    mail::send(Mail {
+        to: user.email.clone(),
+        subject: "Verify your email".to_string(),
+        body: format!(
+            "Hi {},\n\nplease confirm that this is your email by opening the link below, it is valid for {} hours:\n\n{}/verify-email?token={}\n\nIf you didn't create an account, you can ignore this mail.",
+            user.first_name,
+            ttl.as_secs() / 60 / 60,
+            get_frontend_url(),
+            token
+        ),
+    })
+    .await?;
+
+    tracing::info!(user_id = %user.id, "Email verification mail sent");
+    Ok(())
+}
+
+/// Sends the verification mail, a failed delivery is only logged since the user can request a new one
+pub async fn try_send_verification_mail(user: &User) {
+    if let Err(err) = send_verification_mail(user).await {
+        tracing::error!(user_id = %user.id, error = %err, "Failed to send email verification mail");
+    }
+}
+
+/// Marks the email the token was sent to as verified, each token can only be used once
+pub async fn verify(token: &str, db: &Connection<AuthRsDatabase>) -> ApiResult<User> {
+    let invalid_token =
+        || ApiError::BadRequest("Invalid or expired verification token".to_string());
+
+    let verification =
+        store::take::<EmailVerification>(EMAIL_VERIFICATIONS_NAMESPACE, &hash_token(token))
+            .await?
+            .ok_or_else(invalid_token)?;
+
+    let mut user = User::get_by_id(verification.user_id, db)
+        .await
+        .map_err(|_| invalid_token())?;
+    if user.email_verified && user.email == verification.email {
+        return Ok(user);
+    }
+
+    let verified = user
+        .verify_email(&verification.email, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to verify email: {}", err)))?;
+    if !verified {
+        return Err(invalid_token());
+    }
+
+    AuditLog::new(
+        user.id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Update,
+        "Verify email.".to_string(),
+        user.id,
+        Some(HashMap::from([(
+            "email_verified".to_string(),
+            "false".to_string(),
+        )])),
+        Some(HashMap::from([(
+            "email_verified".to_string(),
+            "true".to_string(),
+        )])),
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    user.email_verified = true;
+    Ok(user)
+}
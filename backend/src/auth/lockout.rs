@@ -0,0 +1,182 @@
+use std::{env, time::Duration};
+
+use mongodb::bson::DateTime;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+
+use crate::{
+    db::AuthRsDatabase,
+    errors::{ApiError, ApiResult},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        user::User,
+    },
+    store,
+};
+
+use super::client_info::ClientInfo;
+
+const IP_NAMESPACE: &str = "failed-login-attempts";
+
+/// Failed attempts of a client IP, kept in the ephemeral store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct IpAttempts {
+    failures: u32,
+    locked_until: Option<DateTime>,
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Failed attempts before an account gets locked (`LOCKOUT_THRESHOLD`)
+fn account_threshold() -> u32 {
+    env_or("LOCKOUT_THRESHOLD", 5) as u32
+}
+
+/// Failed attempts before a client IP gets locked (`LOCKOUT_IP_THRESHOLD`)
+fn ip_threshold() -> u32 {
+    env_or("LOCKOUT_IP_THRESHOLD", 20) as u32
+}
+
+fn max_lockout_seconds() -> u64 {
+    env_or("LOCKOUT_MAX_SECONDS", 60 * 60)
+}
+
+/// The lockout doubles with every failed attempt past the threshold, starting at `LOCKOUT_BASE_SECONDS`
+fn get_lockout_duration(failures: u32, threshold: u32) -> Option<Duration> {
+    if threshold == 0 || failures < threshold {
+        return None;
+    }
+
+    let base = env_or("LOCKOUT_BASE_SECONDS", 30);
+    let exponent = (failures - threshold).min(32);
+    let seconds = base
+        .saturating_mul(1 << exponent)
+        .min(max_lockout_seconds());
+    Some(Duration::from_secs(seconds))
+}
+
+fn get_locked_until(duration: Duration) -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + duration.as_millis() as i64)
+}
+
+fn too_many_attempts(locked_until: DateTime) -> ApiError {
+    let seconds = (locked_until.timestamp_millis() - DateTime::now().timestamp_millis()) / 1000;
+    ApiError::TooManyRequests(format!(
+        "Too many failed attempts, try again in {} seconds",
+        seconds.max(1)
+    ))
+}
+
+/// Rejects the request if the client IP or the account is locked
+pub async fn check(user: Option<&User>, client_info: &ClientInfo) -> ApiResult<()> {
+    if let Some(ip_address) = &client_info.ip_address {
+        let attempts = store::get::<IpAttempts>(IP_NAMESPACE, ip_address).await?;
+        if let Some(locked_until) = attempts
+            .and_then(|attempts| attempts.locked_until)
+            .filter(|locked_until| *locked_until > DateTime::now())
+        {
+            return Err(too_many_attempts(locked_until));
+        }
+    }
+
+    if let Some(user) = user.filter(|user| user.is_locked()) {
+        return Err(too_many_attempts(user.locked_until.unwrap()));
+    }
+
+    Ok(())
+}
+
+/// Counts a failed attempt for the client IP and, if known, the account, locking them once over the threshold
+pub async fn record_failure(
+    user: Option<&User>,
+    client_info: &ClientInfo,
+    db: &Connection<AuthRsDatabase>,
+) {
+    if let Some(ip_address) = &client_info.ip_address {
+        let mut attempts = store::get::<IpAttempts>(IP_NAMESPACE, ip_address)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        attempts.failures += 1;
+
+        if let Some(duration) = get_lockout_duration(attempts.failures, ip_threshold()) {
+            attempts.locked_until = Some(get_locked_until(duration));
+            tracing::warn!(
+                ip_address = %ip_address,
+                failures = attempts.failures,
+                lockout_seconds = duration.as_secs(),
+                "Client IP locked after failed attempts"
+            );
+        }
+
+        // The counter is forgotten once the longest possible lockout has passed without failures
+        let ttl = Duration::from_secs(max_lockout_seconds().max(60));
+        if let Err(err) = store::put(IP_NAMESPACE, ip_address, &attempts, ttl).await {
+            tracing::error!(error = %err, "Failed to store failed attempts of client IP");
+        }
+    }
+
+    let Some(user) = user else {
+        return;
+    };
+
+    let failures = match user.register_failed_attempt(db).await {
+        Ok(failures) => failures,
+        Err(err) => {
+            tracing::error!(user_id = %user.id, error = %err, "Failed to count failed attempt");
+            return;
+        }
+    };
+
+    let Some(duration) = get_lockout_duration(failures, account_threshold()) else {
+        return;
+    };
+
+    let locked_until = get_locked_until(duration);
+    if let Err(err) = user.lock(locked_until, db).await {
+        tracing::error!(user_id = %user.id, error = %err, "Failed to lock user");
+        return;
+    }
+
+    tracing::warn!(
+        user_id = %user.id,
+        failures = failures,
+        lockout_seconds = duration.as_secs(),
+        "User locked after failed attempts"
+    );
+
+    AuditLog::new(
+        user.id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Lockout,
+        format!(
+            "Account locked for {} seconds after {} failed attempts.",
+            duration.as_secs(),
+            failures
+        ),
+        user.id,
+        None,
+        None,
+    )
+    .insert(db)
+    .await
+    .ok();
+}
+
+/// Resets the failed attempts of the account after a completed login
+pub async fn record_success(user: &User, db: &Connection<AuthRsDatabase>) {
+    if user.failed_login_attempts == 0 && user.locked_until.is_none() {
+        return;
+    }
+
+    if let Err(err) = user.unlock(db).await {
+        tracing::error!(user_id = %user.id, error = %err, "Failed to reset failed attempts");
+    }
+}
@@ -1,166 +1,475 @@
-use std::{collections::HashMap, env, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Result;
-use mongodb::bson::{doc, Uuid};
-use rocket::tokio::{spawn, time::sleep};
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use mongodb::bson::{doc, DateTime, Uuid};
+use rand::Rng;
+use rocket::{
+    serde::{Deserialize, Serialize},
+    tokio,
+};
 use rocket_db_pools::Connection;
 use totp_rs::{Algorithm, Secret, TOTP};
+use webauthn_rs::prelude::PasskeyAuthentication;
 
 use crate::{
     db::{get_main_db, AuthRsDatabase},
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        passkey::Passkey,
+        role::Role,
+        session::Session,
+        settings::Settings,
         user::User,
     },
-    MFA_SESSIONS,
+    store::{self, StoreResult},
 };
 
 use super::AuthEntity;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub enum MfaState {
     Pending,
     Complete,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub enum MfaType {
     Totp,
     EnableTotp,
+    // Mandatory TOTP setup during login, required by the MFA policy
+    EnrollTotp,
+    Passkey,
+}
+
+// The ways a user can complete the MFA step of a login
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum MfaFactor {
+    Totp,
+    RecoveryCode,
+    Passkey,
+}
+
+// Where a user without MFA stands with the MFA policy
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct MfaEnrollment {
+    pub required: bool,
+    pub deadline: DateTime,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub struct MfaHandler {
     pub flow_id: Uuid,
     pub state: MfaState,
     pub r#type: MfaType,
-    pub user: User,
+    // The user is loaded when needed, so no password hash or secret ends up in the store
+    pub user_id: Uuid,
+    // Only set while setting up TOTP, login flows build it from the stored secret
     pub totp: Option<TOTP>,
+    #[serde(default)]
+    pub factors: Vec<MfaFactor>,
+    #[serde(default)]
+    pub passkey_authentication: Option<PasskeyAuthentication>,
 }
 
 impl MfaHandler {
-    pub fn is_mfa_required(user: &User) -> bool {
-        user.totp_secret.is_some()
+    pub const STORE_NAMESPACE: &'static str = "mfa-flows";
+
+    pub const RECOVERY_CODE_COUNT: usize = 10;
+    const RECOVERY_CODE_LENGTH: usize = 10;
+
+    pub async fn get_flow(flow_id: Uuid) -> StoreResult<Option<Self>> {
+        store::get(Self::STORE_NAMESPACE, &flow_id.to_string()).await
+    }
+
+    async fn save(&self) -> Result<(), String> {
+        store::put(
+            Self::STORE_NAMESPACE,
+            &self.flow_id.to_string(),
+            self,
+            store::DEFAULT_TTL,
+        )
+        .await
+        .map_err(|err| format!("Failed to store MFA flow: {}", err))
+    }
+
+    /// Returns the second factors the user has set up, MFA is required if there are any
+    pub async fn get_factors(
+        user: &User,
+        db: &Connection<AuthRsDatabase>,
+    ) -> Result<Vec<MfaFactor>, String> {
+        let mut factors = Vec::new();
+
+        if user.totp_secret.is_some() {
+            factors.push(MfaFactor::Totp);
+            if !user.recovery_codes.is_empty() {
+                factors.push(MfaFactor::RecoveryCode);
+            }
+        }
+
+        let passkeys = Passkey::get_by_owner(user.id, db)
+            .await
+            .map_err(|err| format!("Failed to get passkeys: {}", err))?;
+        if !passkeys.is_empty() {
+            factors.push(MfaFactor::Passkey);
+        }
+
+        Ok(factors)
+    }
+
+    /// Returns every role whose members fall under the MFA policy, directly or through inheritance
+    pub async fn get_enforced_roles(
+        settings: &Settings,
+        db: &Connection<AuthRsDatabase>,
+    ) -> Result<HashSet<Uuid>, String> {
+        let mut enforced_roles = HashSet::new();
+        if settings.mfa_required_roles.is_empty() {
+            return Ok(enforced_roles);
+        }
+
+        let roles = Role::get_all(db, None)
+            .await
+            .map_err(|err| format!("Failed to get roles: {}", err.message()))?;
+        for role in roles {
+            let ancestors = Role::get_with_ancestors(&[role.id], &get_main_db(db))
+                .await
+                .map_err(|err| format!("Failed to get roles: {}", err.message()))?;
+            if ancestors
+                .iter()
+                .any(|ancestor| settings.mfa_required_roles.contains(&ancestor.id))
+            {
+                enforced_roles.insert(role.id);
+            }
+        }
+
+        Ok(enforced_roles)
+    }
+
+    pub fn get_enrollment_deadline(since: DateTime, settings: &Settings) -> DateTime {
+        DateTime::from_millis(
+            since.timestamp_millis() + settings.mfa_grace_period_days as i64 * 24 * 60 * 60 * 1000,
+        )
+    }
+
+    /// Checks the MFA policy for a user without any second factor.
+    /// The grace period starts the first time the policy applies to the user.
+    pub async fn get_policy_enrollment(
+        user: &User,
+        settings: &Settings,
+        db: &Connection<AuthRsDatabase>,
+    ) -> Result<Option<MfaEnrollment>, String> {
+        let enforced = !settings.mfa_required_roles.is_empty()
+            && Role::get_with_ancestors(&user.roles, &get_main_db(db))
+                .await
+                .map_err(|err| format!("Failed to get roles: {}", err.message()))?
+                .iter()
+                .any(|role| settings.mfa_required_roles.contains(&role.id));
+
+        if !enforced {
+            // Start over if the user falls under the policy again later on
+            if user.mfa_required_since.is_some() {
+                user.set_mfa_required_since(None, db)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            }
+            return Ok(None);
+        }
+
+        let since = match user.mfa_required_since {
+            Some(since) => since,
+            None => {
+                let now = DateTime::now();
+                user.set_mfa_required_since(Some(now), db)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                now
+            }
+        };
+
+        let deadline = Self::get_enrollment_deadline(since, settings);
+        Ok(Some(MfaEnrollment {
+            required: DateTime::now() >= deadline,
+            deadline,
+        }))
     }
 
     pub async fn start_enable_flow(user: &User) -> Result<Self, String> {
+        Self::start_totp_setup_flow(user, MfaType::EnableTotp).await
+    }
+
+    pub async fn start_enroll_flow(user: &User) -> Result<Self, String> {
+        Self::start_totp_setup_flow(user, MfaType::EnrollTotp).await
+    }
+
+    async fn start_totp_setup_flow(user: &User, r#type: MfaType) -> Result<Self, String> {
         let flow = Self {
             flow_id: Uuid::new(),
             state: MfaState::Pending,
-            r#type: MfaType::EnableTotp,
-            user: user.clone(),
-            totp: Some(
-                TOTP::new(
-                    Algorithm::SHA1,
-                    6,
-                    1,
-                    30,
-                    Secret::generate_secret().to_bytes().unwrap(),
-                    Some(env::var("TOTP_ISSUER_NAME").unwrap_or_else(|_| "auth-rs".to_string())),
-                    user.email.to_string(),
-                )
-                .unwrap(),
-            ),
+            r#type,
+            user_id: user.id,
+            factors: Vec::from([MfaFactor::Totp]),
+            passkey_authentication: None,
+            totp: Some(Self::build_totp(
+                Secret::generate_secret().to_bytes().unwrap(),
+                user,
+            )?),
         };
 
-        let mut mfa_sessions = MFA_SESSIONS.lock().await;
-        mfa_sessions.insert(flow.flow_id, flow.clone());
-        drop(mfa_sessions);
-
-        //TODO: Store this state in the db with a timestamp, and check for expiration
-        //  We can not and should not rely on the application not crashing or restarting
-        //  This application should be completely stateless
-        // invalidate flow after 5 minutes
-        spawn(async move {
-            sleep(Duration::from_secs(300)).await;
-
-            let mut mfa_sessions = MFA_SESSIONS.lock().await;
-            mfa_sessions.remove(&flow.flow_id);
-            drop(mfa_sessions);
-        });
+        flow.save().await?;
 
         Ok(flow)
     }
 
-    pub async fn start_login_flow(user: &User) -> Result<Self, String> {
-        if user.totp_secret.is_none() {
-            return Err("User does not have TOTP enabled".to_string());
+    pub async fn start_login_flow(user: &User, factors: Vec<MfaFactor>) -> Result<Self, String> {
+        if factors.is_empty() {
+            return Err("User does not have MFA enabled".to_string());
         }
 
-        let mut flow = Self {
+        let flow = Self {
             flow_id: Uuid::new(),
             state: MfaState::Pending,
-            r#type: MfaType::Totp,
-            user: user.clone(),
+            r#type: if user.totp_secret.is_some() {
+                MfaType::Totp
+            } else {
+                MfaType::Passkey
+            },
+            user_id: user.id,
             totp: None,
+            factors,
+            passkey_authentication: None,
         };
 
-        flow.totp = Some(
-            TOTP::new(
-                Algorithm::SHA1,
-                6,
-                1,
-                30,
-                Secret::Encoded(user.totp_secret.as_ref().unwrap().to_string())
-                    .to_bytes()
-                    .unwrap(),
-                Some(env::var("TOTP_ISSUER_NAME").unwrap_or_else(|_| "auth-rs".to_string())),
-                user.email.to_string(),
-            )
-            .unwrap(),
-        );
-
-        let mut mfa_sessions = MFA_SESSIONS.lock().await;
-        mfa_sessions.insert(flow.flow_id, flow.clone());
-        drop(mfa_sessions);
-
-        //TODO: Store this state in the db with a timestamp, and check for expiration
-        //  We can not and should not rely on the application not crashing or restarting
-        //  This application should be completely stateless
-        // invalidate flow after 5 minutes
-        spawn(async move {
-            sleep(Duration::from_secs(300)).await;
-
-            let mut mfa_sessions = MFA_SESSIONS.lock().await;
-            mfa_sessions.remove(&flow.flow_id);
-            drop(mfa_sessions);
-        });
+        flow.save().await?;
 
         Ok(flow)
     }
 
-    pub async fn verify_current_totp(&self, code: &str) -> bool {
-        if self.totp.is_none() || code.is_empty() {
+    fn build_totp(secret: Vec<u8>, user: &User) -> Result<TOTP, String> {
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            secret,
+            Some(env::var("TOTP_ISSUER_NAME").unwrap_or_else(|_| "auth-rs".to_string())),
+            user.email.to_string(),
+        )
+        .map_err(|err| format!("Failed to create TOTP: {}", err))
+    }
+
+    /// How many steps a code may be ahead of or behind the server clock (`TOTP_SKEW_STEPS`)
+    fn skew_steps() -> i64 {
+        env::var("TOTP_SKEW_STEPS")
+            .ok()
+            .and_then(|skew| skew.parse::<u8>().ok())
+            .unwrap_or(1) as i64
+    }
+
+    /// Finds the time step within the skew window the code belongs to.
+    /// Steps up to and including `last_step` are skipped so a code can never be used twice.
+    fn find_totp_step(totp: &TOTP, code: &str, last_step: Option<i64>) -> Option<i64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let current_step = (now / totp.step) as i64;
+        let skew = Self::skew_steps();
+
+        (current_step - skew..=current_step + skew)
+            .filter(|step| *step >= 0 && !matches!(last_step, Some(last) if *step <= last))
+            .find(|step| totp.generate(*step as u64 * totp.step) == code)
+    }
+
+    /// Verifies the code and completes the flow, returning the accepted time step.
+    /// For login flows the step is recorded on the user right away.
+    pub async fn verify_current_totp(
+        &self,
+        code: &str,
+        db: &Connection<AuthRsDatabase>,
+    ) -> Option<i64> {
+        if code.is_empty() {
+            return None;
+        }
+
+        let step = match self.r#type {
+            // The secret is brand new, so there is nothing to replay yet
+            MfaType::EnableTotp | MfaType::EnrollTotp => {
+                Self::find_totp_step(self.totp.as_ref()?, code, None)?
+            }
+            MfaType::Totp | MfaType::Passkey => {
+                let user = User::get_by_id(self.user_id, db).await.ok()?;
+                Self::verify_user_totp(&user, code, db).await?
+            }
+        };
+
+        // Taking the flow makes sure the same flow can not be completed twice
+        match store::take::<Self>(Self::STORE_NAMESPACE, &self.flow_id.to_string()).await {
+            Ok(Some(_)) => Some(step),
+            _ => None,
+        }
+    }
+
+    /// TOTP codes are always 6 digits, everything else is treated as a recovery code
+    pub fn is_recovery_code(code: &str) -> bool {
+        !(code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    fn normalize_recovery_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    }
+
+    /// Returns the plain codes, which are only shown to the user once, and the hashes to store
+    pub fn generate_recovery_codes() -> Result<(Vec<String>, Vec<String>), String> {
+        let argon2 = Argon2::default();
+        let mut codes = Vec::with_capacity(Self::RECOVERY_CODE_COUNT);
+        let mut hashes = Vec::with_capacity(Self::RECOVERY_CODE_COUNT);
+
+        for _ in 0..Self::RECOVERY_CODE_COUNT {
+            let code = rand::rng()
+                .sample_iter(rand::distr::Alphanumeric)
+                .take(Self::RECOVERY_CODE_LENGTH)
+                .map(char::from)
+                .collect::<String>()
+                .to_lowercase();
+
+            let salt = SaltString::generate(&mut OsRng);
+            let hash = argon2
+                .hash_password(code.as_bytes(), &salt)
+                .map_err(|err| format!("Failed to hash recovery code: {}", err))?
+                .to_string();
+
+            let (first, second) = code.split_at(Self::RECOVERY_CODE_LENGTH / 2);
+            codes.push(format!("{}-{}", first, second));
+            hashes.push(hash);
+        }
+
+        Ok((codes, hashes))
+    }
+
+    /// Completes a login flow with one of the user's recovery codes instead of a TOTP code.
+    /// The code is used up and the use is audited.
+    pub async fn verify_recovery_code(&self, code: &str, db: &Connection<AuthRsDatabase>) -> bool {
+        if self.r#type != MfaType::Totp {
             return false;
         }
 
-        if self.totp.as_ref().unwrap().generate_current().unwrap() == code {
-            let mut mfa_sessions = MFA_SESSIONS.lock().await;
-            mfa_sessions.remove(&self.flow_id);
-            drop(mfa_sessions);
+        let code = Self::normalize_recovery_code(code);
+        if code.is_empty() {
+            return false;
+        }
 
-            true
-        } else {
-            false
+        let user = match User::get_by_id(self.user_id, db).await {
+            Ok(user) => user,
+            Err(_) => return false,
+        };
+
+        // Up to one Argon2 verify per stored code, so it runs off the async executor
+        let hashes = user.recovery_codes.clone();
+        let hash = match tokio::task::spawn_blocking(move || {
+            let argon2 = Argon2::default();
+            hashes.into_iter().find(|hash| {
+                PasswordHash::new(hash)
+                    .map(|hash| argon2.verify_password(code.as_bytes(), &hash).is_ok())
+                    .unwrap_or(false)
+            })
+        })
+        .await
+        {
+            Ok(Some(hash)) => hash,
+            _ => return false,
+        };
+
+        if !matches!(
+            store::take::<Self>(Self::STORE_NAMESPACE, &self.flow_id.to_string()).await,
+            Ok(Some(_))
+        ) {
+            return false;
+        }
+
+        match user.remove_recovery_code(&hash, db).await {
+            Ok(true) => (),
+            _ => return false,
         }
+
+        let remaining = user.recovery_codes.len() - 1;
+        match AuditLog::new(
+            user.id.to_string(),
+            AuditLogEntityType::User,
+            AuditLogAction::UseRecoveryCode,
+            format!("Recovery code used, {} remaining.", remaining),
+            user.id,
+            None,
+            None,
+        )
+        .insert(db)
+        .await
+        {
+            Ok(_) => tracing::info!(
+                user_id = %user.id,
+                remaining = remaining,
+                "Recovery code used for login"
+            ),
+            Err(err) => tracing::error!(
+                user_id = %user.id,
+                error = ?err,
+                "Failed to insert audit log for recovery code use"
+            ),
+        };
+
+        true
     }
 
-    pub async fn verify_totp(user: &User, secret: String, code: &str) -> bool {
-        let totp_result = TOTP::new(
-            Algorithm::SHA1,
-            6,
-            1,
-            30,
-            Secret::Encoded(secret).to_bytes().unwrap(),
-            Some(env::var("TOTP_ISSUER_NAME").unwrap_or_else(|_| "auth-rs".to_string())),
-            user.email.to_string(),
-        );
+    /// Keeps the WebAuthn challenge sent to the user until the assertion comes back
+    pub async fn start_passkey_authentication(
+        &mut self,
+        state: PasskeyAuthentication,
+    ) -> Result<(), String> {
+        if !self.factors.contains(&MfaFactor::Passkey) {
+            return Err("User does not have any passkeys".to_string());
+        }
+
+        self.passkey_authentication = Some(state);
+        self.save().await
+    }
 
-        if totp_result.is_err() {
+    /// Completes the flow once the passkey assertion has been verified
+    pub async fn complete_passkey_authentication(&self) -> bool {
+        if !self.factors.contains(&MfaFactor::Passkey) || self.passkey_authentication.is_none() {
             return false;
         }
 
-        totp_result.unwrap().check_current(code).unwrap_or(false)
+        // Taking the flow makes sure the same flow can not be completed twice
+        matches!(
+            store::take::<Self>(Self::STORE_NAMESPACE, &self.flow_id.to_string()).await,
+            Ok(Some(_))
+        )
+    }
+
+    /// Verifies a code of the user's enabled secret and records its time step, so neither this
+    /// code nor an older one can be used again
+    pub async fn verify_user_totp(
+        user: &User,
+        code: &str,
+        db: &Connection<AuthRsDatabase>,
+    ) -> Option<i64> {
+        let secret = Secret::Encoded(user.totp_secret.clone()?).to_bytes().ok()?;
+        let totp = Self::build_totp(secret, user).ok()?;
+        let step = Self::find_totp_step(&totp, code, user.last_totp_step)?;
+        if !user.claim_totp_step(step, db).await.unwrap_or(false) {
+            return None;
+        }
+        Some(step)
     }
 
     pub async fn disable_totp(
@@ -168,18 +477,23 @@ impl MfaHandler {
         req_user: AuthEntity,
         db: &Connection<AuthRsDatabase>,
     ) -> Result<User, String> {
-        let mut new_values =
-            HashMap::from([("totp_secret".to_string(), "***********".to_string())]);
-        let mut old_values =
-            HashMap::from([("totp_secret".to_string(), "***********".to_string())]);
+        let new_values = HashMap::from([("totp_secret".to_string(), "***********".to_string())]);
+        let old_values = HashMap::from([("totp_secret".to_string(), "***********".to_string())]);
 
         user.totp_secret = None;
+        user.recovery_codes = Vec::new();
+        user.last_totp_step = None;
 
-        let new_token = User::generate_token();
-        new_values.insert("token".to_string(), "***********".to_string());
-        old_values.insert("token".to_string(), "***********".to_string());
-
-        user.token = new_token;
+        // Log out every other device, only the session disabling TOTP stays valid
+        let mut session_filter = doc! {
+            "userId": user.id
+        };
+        if let Some(session) = req_user.session.as_ref().filter(|s| s.user_id == user.id) {
+            session_filter.insert("_id", doc! { "$ne": session.id });
+        }
+        Session::delete_all_matching(session_filter, db)
+            .await
+            .map_err(|err| format!("Failed to revoke sessions: {:?}", err))?;
 
         let filter = doc! {
             "_id": user.id
@@ -233,7 +547,10 @@ impl PartialEq for MfaType {
     fn eq(&self, other: &Self) -> bool {
         matches!(
             (self, other),
-            (MfaType::Totp, MfaType::Totp) | (MfaType::EnableTotp, MfaType::EnableTotp)
+            (MfaType::Totp, MfaType::Totp)
+                | (MfaType::EnableTotp, MfaType::EnableTotp)
+                | (MfaType::EnrollTotp, MfaType::EnrollTotp)
+                | (MfaType::Passkey, MfaType::Passkey)
         )
     }
 }
@@ -1,27 +1,43 @@
 use crate::{
     db::{get_main_db_name, AuthRsDatabase},
     errors::{AppError, AppResult},
-    models::{oauth_token::OAuthToken, user::User},
+    fairings::rate_limiter::{RateLimitDecision, RateLimitIdentity, RateLimiter},
+    models::{
+        oauth_token::OAuthToken, permission::Permission, role::Role, session::Session, user::User,
+    },
 };
 use mongodb::bson::Uuid;
 use rocket::{http::Status, outcome::Outcome, request::FromRequest, Request};
+use rocket_db_pools::mongodb::Database;
+use std::collections::HashSet;
 
+pub mod breached_passwords;
+pub mod client_info;
+pub mod email_verification;
+pub mod lockout;
 pub mod mfa;
+pub mod oidc;
+pub mod pkce;
+pub mod scim;
 
 #[derive(Debug, Clone)]
 pub struct AuthEntity {
     pub user_id: Uuid,
     pub user: Option<User>,
+    pub session: Option<Session>,
     pub token: Option<OAuthToken>,
+    pub permissions: HashSet<Permission>,
 }
 
 #[allow(unused)]
 impl AuthEntity {
-    pub fn from_user(user: User) -> Self {
+    pub fn from_session(user: User, session: Session, permissions: HashSet<Permission>) -> Self {
         Self {
             user_id: user.id,
             user: Some(user),
+            session: Some(session),
             token: None,
+            permissions,
         }
     }
 
@@ -29,7 +45,18 @@ impl AuthEntity {
         Self {
             user_id: token.user_id,
             user: None,
+            session: None,
             token: Some(token),
+            permissions: HashSet::new(),
+        }
+    }
+
+    /// The single place routes check permissions. OAuth tokens never carry permissions,
+    /// they are limited by their scopes instead.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match &self.user {
+            Some(user) => user.is_system_admin() || self.permissions.contains(&permission),
+            None => false,
         }
     }
 
@@ -56,15 +83,17 @@ pub struct OptionalAuthEntity {
     pub user_id: Option<Uuid>,
     pub user: Option<User>,
     pub token: Option<OAuthToken>,
+    pub permissions: HashSet<Permission>,
 }
 
 #[allow(unused)]
 impl OptionalAuthEntity {
-    pub fn from_user(user: User) -> Self {
+    pub fn from_user(user: User, permissions: HashSet<Permission>) -> Self {
         Self {
             user_id: Some(user.id),
             user: Some(user),
             token: None,
+            permissions,
         }
     }
 
@@ -73,6 +102,7 @@ impl OptionalAuthEntity {
             user_id: Some(token.user_id),
             user: None,
             token: Some(token),
+            permissions: HashSet::new(),
         }
     }
 
@@ -81,6 +111,14 @@ impl OptionalAuthEntity {
             user_id: None,
             user: None,
             token: None,
+            permissions: HashSet::new(),
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match &self.user {
+            Some(user) => user.is_system_admin() || self.permissions.contains(&permission),
+            None => false,
         }
     }
 
@@ -107,6 +145,39 @@ pub enum AuthError {
     InvalidToken,
     Unauthorized,
     Forbidden,
+    RateLimited,
+}
+
+/// Resolves a session token to its user and the permissions granted by the user's roles,
+/// and refreshes the session's last seen time
+async fn get_session_user(
+    token: &str,
+    db: &Database,
+) -> Option<(User, Session, HashSet<Permission>)> {
+    let mut session = Session::get_by_token(token, db).await.ok()?;
+    let user = User::get_full_by_session(&session, db).await.ok()?;
+
+    if let Err(err) = session.touch(db).await {
+        tracing::warn!(session_id = %session.id, error = %err, "Failed to refresh session");
+    }
+
+    let permissions = match Role::get_permissions(&user.roles, db).await {
+        Ok(permissions) => permissions,
+        Err(err) => {
+            tracing::error!(user_id = %user.id, error = %err, "Failed to resolve permissions");
+            HashSet::new()
+        }
+    };
+
+    Some((user, session, permissions))
+}
+
+/// Validated credentials are limited per identity on top of the per IP limit
+async fn is_rate_limited(request: &Request<'_>, identity: RateLimitIdentity) -> bool {
+    matches!(
+        RateLimiter::acquire_identity(request, identity).await,
+        RateLimitDecision::Limited { .. }
+    )
 }
 
 impl From<AuthError> for AppError {
@@ -116,6 +187,9 @@ impl From<AuthError> for AppError {
             AuthError::InvalidToken => AppError::InvalidToken,
             AuthError::Unauthorized => AppError::AuthenticationError("Unauthorized".to_string()),
             AuthError::Forbidden => AppError::UserDisabled,
+            AuthError::RateLimited => {
+                AppError::AuthenticationError("Too many requests".to_string())
+            }
         }
     }
 }
@@ -150,15 +224,21 @@ impl<'r> FromRequest<'r> for AuthEntity {
                 }
 
                 match token_type {
-                    "Bearer" => match User::get_full_by_token(token_value.to_owned(), &db).await {
-                        Ok(user) => {
+                    "Bearer" => match get_session_user(token_value, &db).await {
+                        Some((user, session, permissions)) => {
                             if user.disabled {
                                 return Outcome::Error((Status::Forbidden, AuthError::Forbidden));
                             }
+                            if is_rate_limited(request, RateLimitIdentity::User(user.id)).await {
+                                return Outcome::Error((
+                                    Status::TooManyRequests,
+                                    AuthError::RateLimited,
+                                ));
+                            }
 
-                            Outcome::Success(AuthEntity::from_user(user))
+                            Outcome::Success(AuthEntity::from_session(user, session, permissions))
                         }
-                        Err(_) => match OAuthToken::get_by_token(token_value, &db).await {
+                        None => match OAuthToken::get_by_token(token_value, &db).await {
                             Ok(token) => {
                                 if token.is_expired() {
                                     return Outcome::Error((
@@ -166,6 +246,16 @@ impl<'r> FromRequest<'r> for AuthEntity {
                                         AuthError::InvalidToken,
                                     ));
                                 }
+                                let identity = RateLimitIdentity::OAuthClient {
+                                    application_id: token.application_id,
+                                    user_id: token.user_id,
+                                };
+                                if is_rate_limited(request, identity).await {
+                                    return Outcome::Error((
+                                        Status::TooManyRequests,
+                                        AuthError::RateLimited,
+                                    ));
+                                }
 
                                 Outcome::Success(AuthEntity::from_token(token))
                             }
@@ -212,19 +302,35 @@ impl<'r> FromRequest<'r> for OptionalAuthEntity {
                 }
 
                 match token_type {
-                    "Bearer" => match User::get_full_by_token(token_value.to_owned(), &db).await {
-                        Ok(user) => {
+                    "Bearer" => match get_session_user(token_value, &db).await {
+                        Some((user, _, permissions)) => {
                             if user.disabled {
                                 return Outcome::Success(OptionalAuthEntity::from_empty());
                             }
+                            if is_rate_limited(request, RateLimitIdentity::User(user.id)).await {
+                                return Outcome::Error((
+                                    Status::TooManyRequests,
+                                    AuthError::RateLimited,
+                                ));
+                            }
 
-                            Outcome::Success(OptionalAuthEntity::from_user(user))
+                            Outcome::Success(OptionalAuthEntity::from_user(user, permissions))
                         }
-                        Err(_) => match OAuthToken::get_by_token(token_value, &db).await {
+                        None => match OAuthToken::get_by_token(token_value, &db).await {
                             Ok(token) => {
                                 if token.is_expired() {
                                     return Outcome::Success(OptionalAuthEntity::from_empty());
                                 }
+                                let identity = RateLimitIdentity::OAuthClient {
+                                    application_id: token.application_id,
+                                    user_id: token.user_id,
+                                };
+                                if is_rate_limited(request, identity).await {
+                                    return Outcome::Error((
+                                        Status::TooManyRequests,
+                                        AuthError::RateLimited,
+                                    ));
+                                }
 
                                 Outcome::Success(OptionalAuthEntity::from_token(token))
                             }
@@ -0,0 +1,175 @@
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mongodb::bson::Uuid;
+use rocket::serde::Serialize;
+use rocket_db_pools::Connection;
+
+use crate::{
+    db::{get_main_db, AuthRsDatabase},
+    errors::AppResult,
+    models::{
+        oauth_application::OAuthApplication,
+        oauth_scope::{OAuthScope, ScopeActions},
+        role::Role,
+        signing_key::{SigningKey, SigningKeyResult},
+        user::UserDTO,
+    },
+};
+
+// ID tokens are only used to prove the login to the client, so they can be short lived
+pub const ID_TOKEN_LIFETIME: u64 = 60 * 60;
+
+/// The public base url of the api, e.g. `https://auth.example.com/api`
+pub fn get_issuer() -> String {
+    env::var("OIDC_ISSUER")
+        .unwrap_or_else(|_| "http://localhost:8000/api".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// The public url of the frontend, which hosts the authorization page
+pub fn get_frontend_url() -> String {
+    env::var("FRONTEND_URL")
+        .or_else(|_| env::var("WEBAUTHN_RP_ORIGIN"))
+        .unwrap_or_else(|_| "http://localhost:3000".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn now_in_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GroupClaim {
+    pub id: String,
+    pub name: String,
+}
+
+/// Resolves the roles (including inherited ones) of the user that the application is allowed to
+/// see. Returns `None` if the roles scope wasn't granted.
+pub async fn get_group_claims(
+    user: &UserDTO,
+    application_id: Uuid,
+    scope: &[OAuthScope],
+    db: &Connection<AuthRsDatabase>,
+) -> AppResult<Option<Vec<GroupClaim>>> {
+    if !scope.contains(&OAuthScope::Roles(ScopeActions::Read))
+        && !scope.contains(&OAuthScope::Roles(ScopeActions::All))
+    {
+        return Ok(None);
+    }
+
+    let application = OAuthApplication::get_by_id(application_id, db).await?;
+    let roles = Role::get_with_ancestors(&user.roles, &get_main_db(db)).await?;
+
+    Ok(Some(
+        roles
+            .into_iter()
+            .filter(|role| application.disclosed_roles.contains(&role.id))
+            .map(|role| GroupClaim {
+                id: role.id.to_string(),
+                name: role.name,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserInfoClaims {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<GroupClaim>>,
+}
+
+impl UserInfoClaims {
+    /// Only discloses the claims that are covered by the granted scopes, the groups have to be
+    /// resolved beforehand through [`get_group_claims`]
+    pub fn from_user(
+        user: &UserDTO,
+        scope: &[OAuthScope],
+        groups: Option<Vec<GroupClaim>>,
+    ) -> Self {
+        let mut claims = Self {
+            sub: user.id.to_string(),
+            name: None,
+            given_name: None,
+            family_name: None,
+            email: None,
+            groups,
+        };
+
+        if scope.contains(&OAuthScope::Profile) {
+            claims.name = Some(
+                format!("{} {}", user.first_name, user.last_name)
+                    .trim()
+                    .to_string(),
+            );
+            claims.given_name = Some(user.first_name.clone());
+            if !user.last_name.is_empty() {
+                claims.family_name = Some(user.last_name.clone());
+            }
+        }
+
+        if scope.contains(&OAuthScope::Email) {
+            claims.email = Some(user.email.clone());
+        }
+
+        claims
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub exp: u64,
+    pub iat: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user_info: UserInfoClaims,
+}
+
+impl IdTokenClaims {
+    pub fn new(
+        user: &UserDTO,
+        client_id: Uuid,
+        scope: &[OAuthScope],
+        groups: Option<Vec<GroupClaim>>,
+        nonce: Option<String>,
+    ) -> Self {
+        let now = now_in_seconds();
+
+        Self {
+            iss: get_issuer(),
+            aud: client_id.to_string(),
+            exp: now + ID_TOKEN_LIFETIME,
+            iat: now,
+            nonce,
+            user_info: UserInfoClaims::from_user(user, scope, groups),
+        }
+    }
+
+    /// Signs the token (RS256) with the given key, clients verify it through the jwks endpoint
+    pub fn sign(&self, signing_key: &SigningKey) -> SigningKeyResult<String> {
+        signing_key.sign(self)
+    }
+}
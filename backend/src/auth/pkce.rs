@@ -0,0 +1,48 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rocket::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Proof Key for Code Exchange, see RFC 7636
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum CodeChallengeMethod {
+    #[serde(rename = "plain")]
+    Plain,
+    S256,
+}
+
+impl CodeChallengeMethod {
+    /// The method defaults to `plain` if the client only sends a challenge
+    pub fn parse(value: Option<&str>) -> Option<Self> {
+        match value {
+            None | Some("plain") => Some(Self::Plain),
+            Some("S256") => Some(Self::S256),
+            _ => None,
+        }
+    }
+}
+
+/// Verifiers (and therefore plain challenges) are 43 to 128 unreserved characters
+pub fn is_valid_code(value: &str) -> bool {
+    (43..=128).contains(&value.len())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+pub fn verify_code_challenge(
+    code_verifier: &str,
+    code_challenge: &str,
+    method: &CodeChallengeMethod,
+) -> bool {
+    if !is_valid_code(code_verifier) {
+        return false;
+    }
+
+    match method {
+        CodeChallengeMethod::Plain => code_verifier == code_challenge,
+        CodeChallengeMethod::S256 => {
+            URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::env;
+
+use rocket::{http::Status, outcome::Outcome, request::FromRequest, Request};
+
+use crate::{scim::ScimError, utils::hash_token::hash_token};
+
+/// The provisioning client of the SCIM API, authenticated with the `SCIM_TOKEN` bearer token
+#[derive(Debug, Clone, Copy)]
+pub struct ScimClient;
+
+/// The SCIM API is disabled unless `SCIM_TOKEN` is set
+fn get_token() -> Option<String> {
+    env::var("SCIM_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ScimClient {
+    type Error = ScimError;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<ScimClient, (Status, ScimError), Status> {
+        let Some(token) = get_token() else {
+            let error = ScimError::not_found("SCIM provisioning is disabled");
+            return Outcome::Error((error.status, error));
+        };
+
+        let bearer = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        // Comparing hashes keeps the comparison time independent of the token
+        match bearer {
+            Some(bearer) if hash_token(bearer) == hash_token(&token) => {
+                Outcome::Success(ScimClient)
+            }
+            _ => {
+                let error = ScimError::unauthorized("Invalid or missing provisioning token");
+                Outcome::Error((error.status, error))
+            }
+        }
+    }
+}
@@ -286,6 +286,9 @@ pub enum ApiError {
 
     #[error("Invalid UUID")]
     InvalidUUID,
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
 }
 
 // Implement conversion from ApiError to HttpResponse
@@ -308,6 +311,11 @@ impl<T> From<ApiError> for HttpResponse<T> {
                 message: "Invalid UUID".to_string(),
                 data: None,
             },
+            ApiError::TooManyRequests(msg) => HttpResponse {
+                status: 429,
+                message: msg,
+                data: None,
+            },
         }
     }
 }
@@ -342,6 +350,8 @@ impl From<UserError> for AppError {
             UserError::PasswordToShort => {
                 AppError::InvalidOrMissingFields("Password too short".to_string())
             }
+            UserError::PasswordPolicyViolation(msg) => AppError::InvalidOrMissingFields(msg),
+            UserError::InvalidImport(msg) => AppError::InvalidOrMissingFields(msg),
             UserError::RegistrationClosed => AppError::MissingPermissions,
             UserError::RegistrationCodeInvalid => {
                 AppError::InvalidOrMissingFields("Registration code invalid".to_string())
@@ -376,6 +386,13 @@ impl From<RoleError> for AppError {
                 AppError::ValidationError(format!("Role with name {} already exists", name))
             }
             RoleError::SystemRoleModification => AppError::SystemUserModification,
+            RoleError::CyclicInheritance(id) => {
+                AppError::ValidationError(format!("Role {} cannot inherit from itself", id))
+            }
+            RoleError::InvalidParent(id) => {
+                AppError::ValidationError(format!("Role {} cannot be used as a parent role", id))
+            }
+            RoleError::MissingPermissions => AppError::MissingPermissions,
             RoleError::DatabaseError(msg) => AppError::DatabaseError(msg),
             RoleError::InternalServerError(msg) => AppError::InternalServerError(msg),
         }
@@ -394,6 +411,7 @@ impl From<ApiError> for RoleError {
             ApiError::AppError(err) => err.into(),
             ApiError::InvalidState(msg) => RoleError::DatabaseError(msg),
             ApiError::InvalidUUID => RoleError::NotFound(Uuid::new()),
+            ApiError::TooManyRequests(msg) => RoleError::InternalServerError(msg),
         }
     }
 }
@@ -409,6 +427,7 @@ impl From<ApiError> for OAuthApplicationError {
             ApiError::AppError(err) => err.into(),
             ApiError::InvalidState(msg) => OAuthApplicationError::InvalidData(msg),
             ApiError::InvalidUUID => OAuthApplicationError::NotFound(Uuid::new()),
+            ApiError::TooManyRequests(msg) => OAuthApplicationError::InternalServerError(msg),
         }
     }
 }
@@ -440,6 +459,7 @@ impl From<ApiError> for SettingsError {
             ApiError::AppError(err) => err.into(),
             ApiError::InvalidState(msg) => SettingsError::DatabaseError(msg),
             ApiError::InvalidUUID => SettingsError::DatabaseError("Invalid UUID".to_string()),
+            ApiError::TooManyRequests(msg) => SettingsError::Forbidden(msg),
         }
     }
 }
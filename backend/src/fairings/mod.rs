@@ -1 +1,2 @@
+pub mod rate_limiter;
 pub mod request_logger;
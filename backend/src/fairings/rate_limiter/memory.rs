@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use rocket::{async_trait, tokio::sync::Mutex};
+
+use super::{RateLimitBackend, RateLimitBudget, RateLimitDecision};
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    // A full bucket is indistinguishable from a missing one, so it can be dropped after this point
+    full_at: Instant,
+}
+
+/// Keeps the buckets in process memory, limits are per instance
+#[derive(Default)]
+pub struct MemoryRateLimitBackend {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl MemoryRateLimitBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for MemoryRateLimitBackend {
+    async fn acquire(&self, key: &str, budget: RateLimitBudget, now: Instant) -> RateLimitDecision {
+        let mut buckets = self.buckets.lock().await;
+        let capacity = budget.capacity();
+        let refill_rate = budget.refill_rate();
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            full_at: now,
+        });
+
+        let elapsed = now
+            .saturating_duration_since(bucket.updated_at)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.full_at =
+                now + Duration::from_secs_f64((capacity - bucket.tokens) / refill_rate);
+            return RateLimitDecision::Allowed;
+        }
+
+        RateLimitDecision::Limited {
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / refill_rate),
+        }
+    }
+
+    async fn purge(&self, now: Instant) -> usize {
+        let mut buckets = self.buckets.lock().await;
+
+        let count = buckets.len();
+        buckets.retain(|_, bucket| bucket.full_at > now);
+
+        count - buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One token per second
+    fn budget() -> RateLimitBudget {
+        RateLimitBudget::per_minute(60)
+    }
+
+    #[rocket::async_test]
+    async fn allows_a_burst_of_the_whole_budget() {
+        let backend = MemoryRateLimitBackend::new();
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            assert_eq!(
+                backend.acquire("key", budget(), now).await,
+                RateLimitDecision::Allowed
+            );
+        }
+        assert_eq!(
+            backend.acquire("key", budget(), now).await,
+            RateLimitDecision::Limited {
+                retry_after: Duration::from_secs(1)
+            }
+        );
+    }
+
+    #[rocket::async_test]
+    async fn refills_over_time() {
+        let backend = MemoryRateLimitBackend::new();
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            backend.acquire("key", budget(), now).await;
+        }
+
+        let later = now + Duration::from_secs(2);
+        for _ in 0..2 {
+            assert_eq!(
+                backend.acquire("key", budget(), later).await,
+                RateLimitDecision::Allowed
+            );
+        }
+        assert!(matches!(
+            backend.acquire("key", budget(), later).await,
+            RateLimitDecision::Limited { .. }
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn never_refills_beyond_the_capacity() {
+        let backend = MemoryRateLimitBackend::new();
+        let now = Instant::now();
+        backend.acquire("key", budget(), now).await;
+
+        let later = now + Duration::from_secs(3600);
+        for _ in 0..60 {
+            assert_eq!(
+                backend.acquire("key", budget(), later).await,
+                RateLimitDecision::Allowed
+            );
+        }
+        assert!(matches!(
+            backend.acquire("key", budget(), later).await,
+            RateLimitDecision::Limited { .. }
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn keys_have_separate_buckets() {
+        let backend = MemoryRateLimitBackend::new();
+        let budget = RateLimitBudget::per_minute(1);
+        let now = Instant::now();
+
+        assert_eq!(
+            backend.acquire("a", budget, now).await,
+            RateLimitDecision::Allowed
+        );
+        assert!(matches!(
+            backend.acquire("a", budget, now).await,
+            RateLimitDecision::Limited { .. }
+        ));
+        assert_eq!(
+            backend.acquire("b", budget, now).await,
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[rocket::async_test]
+    async fn purges_only_full_buckets() {
+        let backend = MemoryRateLimitBackend::new();
+        let now = Instant::now();
+        backend.acquire("key", budget(), now).await;
+
+        assert_eq!(backend.purge(now).await, 0);
+        assert_eq!(backend.purge(now + Duration::from_secs(2)).await, 1);
+    }
+}
@@ -0,0 +1,402 @@
+pub mod memory;
+
+use std::{
+    env,
+    io::Cursor,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use mongodb::bson::Uuid;
+use rocket::{
+    async_trait,
+    fairing::{Fairing, Info, Kind},
+    http::{uri::Origin, ContentType, Header, Method, Status},
+    tokio, Data, Orbit, Request, Response, Rocket,
+};
+
+use crate::models::http_response::HttpResponse;
+
+use self::memory::MemoryRateLimitBackend;
+
+// Limited requests are routed here, no route matches so no handler runs before the 429 is sent
+const RATE_LIMITED_PATH: &str = "/rate-limited";
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many requests a client may send per minute, bursts of up to the whole budget are allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitBudget {
+    pub requests_per_minute: u32,
+}
+
+impl RateLimitBudget {
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+        }
+    }
+
+    /// Maximum number of tokens in a bucket
+    pub fn capacity(&self) -> f64 {
+        self.requests_per_minute as f64
+    }
+
+    /// Tokens added to a bucket per second
+    pub fn refill_rate(&self) -> f64 {
+        self.requests_per_minute as f64 / 60.0
+    }
+}
+
+/// Applies a budget to all requests whose path starts with the prefix
+#[derive(Debug, Clone)]
+pub struct RateLimitRule {
+    pub name: String,
+    pub path_prefix: String,
+    pub budget: RateLimitBudget,
+}
+
+impl RateLimitRule {
+    pub fn new(name: &str, path_prefix: &str, budget: RateLimitBudget) -> Self {
+        Self {
+            name: name.to_string(),
+            path_prefix: path_prefix.to_string(),
+            budget,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Storage for the token buckets
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    /// Takes a token from the bucket with the key, creating a full bucket if there is none
+    async fn acquire(&self, key: &str, budget: RateLimitBudget, now: Instant) -> RateLimitDecision;
+
+    /// Removes all buckets that have refilled completely and returns how many were removed
+    async fn purge(&self, now: Instant) -> usize;
+}
+
+/// Who sent a request, only known once the auth guards have validated the credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitIdentity {
+    User(Uuid),
+    // An OAuth application acting for a user
+    OAuthClient { application_id: Uuid, user_id: Uuid },
+}
+
+impl RateLimitIdentity {
+    fn key(&self) -> String {
+        match self {
+            RateLimitIdentity::User(user_id) => format!("user:{}", user_id),
+            RateLimitIdentity::OAuthClient {
+                application_id,
+                user_id,
+            } => format!("client:{}:{}", application_id, user_id),
+        }
+    }
+}
+
+// Set for requests that were rejected, read again when the response is built
+#[derive(Default)]
+struct RateLimited(Option<Duration>);
+
+// Auth guards can run more than once per request, the identity bucket is only charged once
+struct IdentityChecked(RateLimitDecision);
+
+/// Limits request rates with token buckets per client IP and route group.
+/// Authenticated requests are limited per identity on top, see `RateLimiter::acquire_identity`.
+#[derive(Clone)]
+pub struct RateLimiter {
+    backend: Arc<dyn RateLimitBackend>,
+    // The first rule with a matching prefix applies
+    rules: Vec<RateLimitRule>,
+}
+
+impl RateLimiter {
+    pub fn new(backend: Arc<dyn RateLimitBackend>, rules: Vec<RateLimitRule>) -> Self {
+        Self { backend, rules }
+    }
+
+    /// Builds the limiter from `RATE_LIMIT_*` variables, a budget of 0 disables the rule
+    pub fn from_env() -> Self {
+        let enabled = env::var("RATE_LIMIT_ENABLED")
+            .map(|value| value.to_lowercase() != "false")
+            .unwrap_or(true);
+        if !enabled {
+            return Self::new(Arc::new(MemoryRateLimitBackend::new()), Vec::new());
+        }
+
+        let budget = |name: &str, default: u32| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        let rules = [
+            (
+                "oauth-token",
+                "/api/oauth/token",
+                budget("RATE_LIMIT_TOKEN_PER_MINUTE", 60),
+            ),
+            (
+                "auth",
+                "/api/auth/",
+                budget("RATE_LIMIT_AUTH_PER_MINUTE", 30),
+            ),
+            ("default", "/", budget("RATE_LIMIT_DEFAULT_PER_MINUTE", 300)),
+        ]
+        .into_iter()
+        .map(|(name, path_prefix, requests_per_minute)| {
+            RateLimitRule::new(
+                name,
+                path_prefix,
+                RateLimitBudget::per_minute(requests_per_minute),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        Self::new(Arc::new(MemoryRateLimitBackend::new()), rules)
+    }
+
+    fn get_rule(&self, path: &str) -> Option<&RateLimitRule> {
+        self.rules
+            .iter()
+            .find(|rule| path.starts_with(&rule.path_prefix))
+    }
+
+    /// Every request is charged to the bucket of its IP address. Headers and body can be
+    /// forged, so they never pick the bucket before the credentials in them are validated.
+    fn get_ip_key(request: &Request<'_>) -> String {
+        match request.client_ip() {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        }
+    }
+
+    async fn acquire(&self, path: &str, client_key: &str) -> Option<RateLimitDecision> {
+        let rule = self.get_rule(path)?;
+        if rule.budget.requests_per_minute == 0 {
+            return None;
+        }
+
+        let key = format!("{}:{}", rule.name, client_key);
+        let decision = self
+            .backend
+            .acquire(&key, rule.budget, Instant::now())
+            .await;
+        if let RateLimitDecision::Limited { retry_after } = decision {
+            tracing::warn!(
+                uri = %path,
+                rule = %rule.name,
+                client = %client_key,
+                retry_after_secs = retry_after.as_secs_f64().ceil() as u64,
+                "Request rate limited"
+            );
+        }
+        Some(decision)
+    }
+
+    /// Charges the bucket of the authenticated identity, in addition to the IP bucket taken in
+    /// `on_request`. Called by the auth guards once the credentials are known to be valid.
+    pub async fn acquire_identity(
+        request: &Request<'_>,
+        identity: RateLimitIdentity,
+    ) -> RateLimitDecision {
+        let Some(limiter) = request.rocket().state::<RateLimiter>() else {
+            return RateLimitDecision::Allowed;
+        };
+
+        let IdentityChecked(decision) = request
+            .local_cache_async(async {
+                let path = request.uri().path().to_string();
+                let decision = limiter.acquire(&path, &identity.key()).await;
+                IdentityChecked(decision.unwrap_or(RateLimitDecision::Allowed))
+            })
+            .await;
+
+        // The response is turned into a 429 with Retry-After in `on_response`
+        if let RateLimitDecision::Limited { retry_after } = decision {
+            request.local_cache(|| RateLimited(Some(*retry_after)));
+        }
+        *decision
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate Limiter",
+            kind: Kind::Liftoff | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_liftoff(&self, _: &Rocket<Orbit>) {
+        let backend = self.backend.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PURGE_INTERVAL).await;
+
+                let removed = backend.purge(Instant::now()).await;
+                if removed > 0 {
+                    tracing::debug!(removed, "Purged full rate limit buckets");
+                }
+            }
+        });
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        // CORS preflights are answered without touching any route
+        if request.method() == Method::Options {
+            return;
+        }
+
+        let path = request.uri().path().to_string();
+        let client_key = Self::get_ip_key(request);
+
+        if let Some(RateLimitDecision::Limited { retry_after }) =
+            self.acquire(&path, &client_key).await
+        {
+            request.local_cache(|| RateLimited(Some(retry_after)));
+            request.set_uri(Origin::parse(RATE_LIMITED_PATH).unwrap());
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RateLimited(Some(retry_after)) = request.local_cache(RateLimited::default) else {
+            return;
+        };
+
+        let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let body = serde_json::to_string(&HttpResponse::<()>::error(
+            429,
+            &format!("Too many requests, try again in {} seconds", retry_after),
+        ))
+        .unwrap_or_default();
+
+        response.set_status(Status::TooManyRequests);
+        response.set_header(ContentType::JSON);
+        response.set_header(Header::new("Retry-After", retry_after.to_string()));
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use rocket::{local::asynchronous::Client, post, routes};
+
+    use super::*;
+
+    #[post("/api/oauth/token")]
+    fn token() -> &'static str {
+        "token"
+    }
+
+    async fn client(requests_per_minute: u32) -> Client {
+        let limiter = RateLimiter::new(
+            Arc::new(MemoryRateLimitBackend::new()),
+            vec![RateLimitRule::new(
+                "oauth-token",
+                "/api/oauth/token",
+                RateLimitBudget::per_minute(requests_per_minute),
+            )],
+        );
+        let rocket = rocket::build()
+            .mount("/", routes![token])
+            .manage(limiter.clone())
+            .attach(limiter);
+
+        Client::tracked(rocket)
+            .await
+            .expect("valid rocket instance")
+    }
+
+    fn remote(ip: &str) -> SocketAddr {
+        format!("{}:8000", ip).parse().unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn forged_client_ids_and_tokens_share_the_ip_bucket() {
+        let client = client(2).await;
+
+        for i in 0..3 {
+            let response = client
+                .post("/api/oauth/token")
+                .remote(remote("10.0.0.1"))
+                .header(ContentType::Form)
+                .header(Header::new("Authorization", format!("Bearer forged-{}", i)))
+                .body(format!("client_id=forged-{}", i))
+                .dispatch()
+                .await;
+
+            if i < 2 {
+                assert_eq!(response.status(), Status::Ok);
+            } else {
+                assert_eq!(response.status(), Status::TooManyRequests);
+                assert!(response.headers().get_one("Retry-After").is_some());
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn ips_have_separate_buckets() {
+        let client = client(1).await;
+        let send = |ip: &'static str| {
+            client
+                .post("/api/oauth/token")
+                .remote(remote(ip))
+                .dispatch()
+        };
+
+        assert_eq!(send("10.0.0.1").await.status(), Status::Ok);
+        assert_eq!(send("10.0.0.1").await.status(), Status::TooManyRequests);
+        assert_eq!(send("10.0.0.2").await.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn identities_have_separate_buckets() {
+        let client = &client(1).await;
+        let alice = RateLimitIdentity::User(Uuid::new());
+        let bob = RateLimitIdentity::OAuthClient {
+            application_id: Uuid::new(),
+            user_id: Uuid::new(),
+        };
+
+        let acquire = |identity| async move {
+            let request = client.post("/api/oauth/token");
+            RateLimiter::acquire_identity(request.inner(), identity).await
+        };
+
+        assert_eq!(acquire(alice).await, RateLimitDecision::Allowed);
+        assert!(matches!(
+            acquire(alice).await,
+            RateLimitDecision::Limited { .. }
+        ));
+        assert_eq!(acquire(bob).await, RateLimitDecision::Allowed);
+    }
+
+    #[rocket::async_test]
+    async fn identity_is_charged_once_per_request() {
+        let client = client(1).await;
+        let identity = RateLimitIdentity::User(Uuid::new());
+        let request = client.post("/api/oauth/token");
+
+        // A second guard in the same request sees the first decision
+        for _ in 0..2 {
+            assert_eq!(
+                RateLimiter::acquire_identity(request.inner(), identity).await,
+                RateLimitDecision::Allowed
+            );
+        }
+    }
+}
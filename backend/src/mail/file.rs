@@ -0,0 +1,50 @@
+use rocket::{
+    async_trait,
+    tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex},
+};
+
+use mongodb::bson::DateTime;
+
+use super::{get_sender, Mail, MailError, MailResult, Mailer};
+
+/// Appends mails to a file instead of sending them, so tests can read them back
+pub struct FileMailer {
+    path: String,
+    // Concurrent mails must not interleave in the file
+    lock: Mutex<()>,
+}
+
+impl FileMailer {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> MailResult<()> {
+        let entry = format!(
+            "Date: {}\nFrom: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+            DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+            get_sender(),
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+
+        let _guard = self.lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| MailError::TransportError(err.to_string()))?;
+
+        file.write_all(entry.as_bytes())
+            .await
+            .map_err(|err| MailError::TransportError(err.to_string()))
+    }
+}
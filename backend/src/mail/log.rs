@@ -0,0 +1,27 @@
+use rocket::async_trait;
+
+use super::{Mail, MailResult, Mailer};
+
+/// Writes mails to the log instead of sending them, only meant for development and tests
+#[derive(Default)]
+pub struct LogMailer;
+
+impl LogMailer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> MailResult<()> {
+        tracing::info!(
+            to = %mail.to,
+            subject = %mail.subject,
+            body = %mail.body,
+            "Mail not sent, logged instead"
+        );
+
+        Ok(())
+    }
+}
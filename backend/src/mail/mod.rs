@@ -0,0 +1,86 @@
+pub mod file;
+pub mod log;
+pub mod smtp;
+
+use std::{env, sync::Arc};
+
+use rocket::{async_trait, tokio::sync::RwLock};
+use thiserror::Error;
+
+use crate::errors::ApiError;
+
+use self::{file::FileMailer, log::LogMailer, smtp::SmtpMailer};
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("Invalid configuration: {0}")]
+    ConfigurationError(String),
+
+    #[error("Invalid mail: {0}")]
+    InvalidMail(String),
+
+    #[error("Transport error: {0}")]
+    TransportError(String),
+}
+
+impl From<MailError> for ApiError {
+    fn from(error: MailError) -> Self {
+        ApiError::InternalError(error.to_string())
+    }
+}
+
+pub type MailResult<T> = Result<T, MailError>;
+
+/// Delivers mails to users, e.g. password reset links
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> MailResult<()>;
+}
+
+lazy_static::lazy_static! {
+    static ref MAILER: RwLock<Arc<dyn Mailer>> = RwLock::new(Arc::new(LogMailer::new()));
+}
+
+/// Sender address of all mails (`MAIL_FROM`)
+pub fn get_sender() -> String {
+    env::var("MAIL_FROM").unwrap_or_else(|_| "auth-rs <no-reply@localhost>".to_string())
+}
+
+/// Selects the transport configured via `MAIL_TRANSPORT`
+pub async fn initialize() -> MailResult<()> {
+    let transport = env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "log".to_string());
+
+    let mailer: Arc<dyn Mailer> = match transport.to_lowercase().as_str() {
+        "log" => Arc::new(LogMailer::new()),
+        "file" => Arc::new(FileMailer::new(
+            env::var("MAIL_FILE_PATH").unwrap_or_else(|_| "mails.log".to_string()),
+        )),
+        "smtp" => Arc::new(SmtpMailer::from_env()?),
+        other => {
+            return Err(MailError::ConfigurationError(format!(
+                "Unknown mail transport: {}",
+                other
+            )))
+        }
+    };
+
+    *MAILER.write().await = mailer;
+    tracing::info!(transport = %transport, "Mailer initialized");
+
+    Ok(())
+}
+
+pub async fn get_mailer() -> Arc<dyn Mailer> {
+    MAILER.read().await.clone()
+}
+
+pub async fn send(mail: Mail) -> MailResult<()> {
+    get_mailer().await.send(&mail).await
+}
@@ -0,0 +1,79 @@
+use std::env;
+
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use rocket::async_trait;
+
+use super::{get_sender, Mail, MailError, MailResult, Mailer};
+
+/// Sends mails through an SMTP relay
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// Configures the relay from the `SMTP_*` variables
+    pub fn from_env() -> MailResult<Self> {
+        let host = env::var("SMTP_HOST")
+            .map_err(|_| MailError::ConfigurationError("SMTP_HOST is not set".to_string()))?;
+        let tls = env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+
+        let mut builder = match tls.to_lowercase().as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                .map_err(|err| MailError::ConfigurationError(err.to_string()))?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+                .map_err(|err| MailError::ConfigurationError(err.to_string()))?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            other => {
+                return Err(MailError::ConfigurationError(format!(
+                    "Unknown SMTP TLS mode: {}",
+                    other
+                )))
+            }
+        };
+
+        if let Some(port) = env::var("SMTP_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+        {
+            builder = builder.port(port);
+        }
+
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD"))
+        {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> MailResult<()> {
+        let message = Message::builder()
+            .from(
+                get_sender()
+                    .parse()
+                    .map_err(|_| MailError::ConfigurationError("Invalid MAIL_FROM".to_string()))?,
+            )
+            .to(mail
+                .to
+                .parse()
+                .map_err(|_| MailError::InvalidMail(format!("Invalid recipient: {}", mail.to)))?)
+            .subject(mail.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body.clone())
+            .map_err(|err| MailError::InvalidMail(err.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| MailError::TransportError(err.to_string()))
+    }
+}
@@ -3,17 +3,22 @@ mod db;
 mod errors;
 mod fairings;
 mod logging;
+mod mail;
 mod models;
 mod routes;
+mod scim;
+mod store;
 mod utils;
 
-use std::{collections::HashMap, env};
+use std::env;
 
-use auth::mfa::MfaHandler;
 use db::AuthRsDatabase;
 use dotenv::dotenv;
 use errors::{AppError, AppResult};
-use models::{role::Role, settings::Settings, user::User};
+use models::{
+    audit_log::AuditLog, oauth_token::OAuthToken, refresh_token::RefreshToken, role::Role,
+    session::Session, settings::Settings, signing_key::SigningKey, user::User,
+};
 use mongodb::bson::{doc, Uuid};
 use rocket::{
     fairing::AdHoc,
@@ -23,18 +28,9 @@ use rocket::{
 };
 use rocket_cors::{AllowedHeaders, AllowedOrigins, CorsOptions};
 use rocket_db_pools::{mongodb::Collection, Database};
-use routes::oauth::token::TokenOAuthData;
-use webauthn_rs::prelude::{DiscoverableAuthentication, PasskeyRegistration};
 
-// oauth codes stored in memory
+// mfa and passkey flows live in the ephemeral store, see store/mod.rs
 lazy_static::lazy_static! {
-    //TODO: Replace with Redis or other cache, so this application can be stateless
-    static ref OAUTH_CODES: Mutex<HashMap<u32, TokenOAuthData>> = Mutex::new(HashMap::new());
-    static ref MFA_SESSIONS: Mutex<HashMap<Uuid, MfaHandler>> = Mutex::new(HashMap::new());
-    static ref REGISTRATIONS: Mutex<HashMap<Uuid, (Uuid, PasskeyRegistration)>> =
-        Mutex::new(HashMap::new());
-    static ref AUTHENTICATIONS: Mutex<HashMap<Uuid, DiscoverableAuthentication>> =
-        Mutex::new(HashMap::new());
     static ref SETTINGS: Mutex<Settings> = Mutex::new(Settings::default());
 
     static ref SETTINGS_ID: Uuid = Uuid::parse_str("00000000-0000-0000-0000-000000000000")
@@ -52,6 +48,13 @@ async fn initialize_database(db: &AuthRsDatabase) -> AppResult<()> {
     let data_db = db.database(db::get_main_db_name());
 
     let settings_collection: Collection<Settings> = data_db.collection(Settings::COLLECTION_NAME);
+    let signing_keys_collection: Collection<SigningKey> =
+        data_db.collection(SigningKey::COLLECTION_NAME);
+    let sessions_collection: Collection<Session> = data_db.collection(Session::COLLECTION_NAME);
+    let oauth_tokens_collection: Collection<OAuthToken> =
+        data_db.collection(OAuthToken::COLLECTION_NAME);
+    let refresh_tokens_collection: Collection<RefreshToken> =
+        data_db.collection(RefreshToken::COLLECTION_NAME);
     let roles_collection: Collection<Role> = data_db.collection(Role::COLLECTION_NAME);
     let users_collection: Collection<User> = data_db.collection(User::COLLECTION_NAME);
 
@@ -70,6 +73,29 @@ async fn initialize_database(db: &AuthRsDatabase) -> AppResult<()> {
         *SETTINGS.lock().await = settings.unwrap();
     }
 
+    // Generate the first signing key if there is no active one
+    SigningKey::initialize(&signing_keys_collection)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    // Sessions expire through a TTL index
+    Session::initialize(&sessions_collection)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    // Expired oauth and refresh tokens are removed through TTL indexes
+    OAuthToken::initialize(&oauth_tokens_collection)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    RefreshToken::initialize(&refresh_tokens_collection)
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    // Indexes for the audit log search
+    AuditLog::initialize(&db.database(db::get_logs_db_name()))
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
     // Initialize default roles if they don't exist
     let roles_count = roles_collection
         .count_documents(None, None)
@@ -105,6 +131,7 @@ async fn initialize_database(db: &AuthRsDatabase) -> AppResult<()> {
 
         tracing::info!(email = %system_email, "Creating system user");
 
+        let hashing = SETTINGS.lock().await.password_hashing.clone();
         let system_user = User::new_system(
             *SYSTEM_USER_ID,
             system_email,
@@ -112,6 +139,7 @@ async fn initialize_database(db: &AuthRsDatabase) -> AppResult<()> {
             "System".to_string(),
             "".to_string(),
             Vec::from([(*ADMIN_ROLE_ID).to_string(), (*DEFAULT_ROLE_ID).to_string()]),
+            &hashing,
         )
         .map_err(AppError::from)?;
 
@@ -147,9 +175,14 @@ fn rocket() -> _ {
         .allowed_headers(AllowedHeaders::all())
         .allow_credentials(true);
 
+    // Managed as well, the auth guards charge the identity buckets through it
+    let rate_limiter = fairings::rate_limiter::RateLimiter::from_env();
+
     rocket::build()
         .attach(db::AuthRsDatabase::init())
         .attach(cors.to_cors().expect("Failed to create CORS fairing"))
+        .manage(rate_limiter.clone())
+        .attach(rate_limiter)
         .attach(fairings::request_logger::RequestLogger)
         .attach(AdHoc::try_on_ignite("Default Values", |rocket| async {
             let db = match AuthRsDatabase::fetch(&rocket) {
@@ -160,13 +193,21 @@ fn rocket() -> _ {
                 }
             };
 
-            match initialize_database(db).await {
-                Ok(_) => {
-                    tracing::info!("Database initialized successfully");
-                    Ok(rocket)
-                }
+            if let Err(err) = initialize_database(db).await {
+                tracing::error!(error = %err, "Failed to initialize database");
+                return Err(rocket);
+            }
+            tracing::info!("Database initialized successfully");
+
+            if let Err(err) = store::initialize(db).await {
+                tracing::error!(error = %err, "Failed to initialize ephemeral store");
+                return Err(rocket);
+            }
+
+            match mail::initialize().await {
+                Ok(_) => Ok(rocket),
                 Err(err) => {
-                    tracing::error!(error = %err, "Failed to initialize database");
+                    tracing::error!(error = %err, "Failed to initialize mailer");
                     Err(rocket)
                 }
             }
@@ -178,12 +219,17 @@ fn rocket() -> _ {
                 // Settings routes
                 routes::settings::get::get_settings,
                 routes::settings::update::update_settings,
+                routes::settings::mfa_compliance::get_mfa_compliance,
+                // Signing Key routes
+                routes::signing_keys::get_all::get_all_signing_keys,
+                routes::signing_keys::rotate::rotate_signing_key,
                 // Audit Log routes
                 routes::audit_logs::get_by_type::get_audit_logs_by_type,
                 routes::audit_logs::get_by_id::get_audit_log_by_id,
                 routes::audit_logs::get_by_entity_id::get_audit_log_by_entity_id,
                 routes::audit_logs::get_by_user_id::get_audit_logs_by_user_id,
                 routes::audit_logs::get_all::get_all_audit_logs,
+                routes::audit_logs::search::search_audit_logs,
                 // User Routes
                 routes::users::create::create_user,
                 routes::users::get_all::get_all_users,
@@ -191,10 +237,19 @@ fn rocket() -> _ {
                 routes::users::me::get_current_user,
                 // this is mainly used for oauth apps
                 routes::users::me::get_current_user_plain,
+                routes::users::me::get_current_user_permissions,
                 routes::users::mfa::enable_totp_mfa,
                 routes::users::mfa::disable_totp_mfa,
+                routes::users::mfa::regenerate_recovery_codes,
                 routes::users::update::update_user,
                 routes::users::delete::delete_user,
+                routes::users::unlock::unlock_user,
+                routes::users::import::import_users_json,
+                routes::users::import::import_users_csv,
+                routes::users::export::export_users,
+                // Session Routes
+                routes::sessions::get_all::get_all_sessions,
+                routes::sessions::revoke::revoke_session,
                 // Role Routes
                 routes::roles::create::create_role,
                 routes::roles::get_all::get_all_roles,
@@ -212,6 +267,10 @@ fn rocket() -> _ {
                 routes::oauth::token::get_oauth_token_json,
                 routes::oauth::authorize::authorize_oauth_application,
                 routes::oauth::revoke::revoke_oauth_token,
+                // OpenID Connect Routes
+                routes::oidc::discovery::openid_configuration,
+                routes::oidc::jwks::get_jwks,
+                routes::oidc::userinfo::get_userinfo,
                 // Connection Routes
                 routes::connections::get_by_user_id::get_by_user_id,
                 routes::connections::disconnect::disconnect,
@@ -224,7 +283,15 @@ fn rocket() -> _ {
                 // Auth Routes
                 routes::auth::register::register,
                 routes::auth::login::login,
+                routes::auth::logout::logout,
+                routes::auth::logout::logout_everywhere,
                 routes::auth::mfa::mfa,
+                routes::auth::mfa::mfa_passkey_start,
+                routes::auth::mfa::mfa_passkey_finish,
+                routes::auth::password_reset::request_password_reset,
+                routes::auth::password_reset::confirm_password_reset,
+                routes::auth::verify_email::verify_email,
+                routes::auth::verify_email::resend_verification_email,
                 // Passkey Routes
                 routes::auth::passkey::authenticate_start,
                 routes::auth::passkey::authenticate_finish,
@@ -236,4 +303,24 @@ fn rocket() -> _ {
                 routes::passkeys::update::update_passkey
             ],
         )
+        .mount(
+            "/api/scim/v2",
+            routes![
+                routes::scim::service_provider_config::get_service_provider_config,
+                // SCIM User Routes
+                routes::scim::users::get_users,
+                routes::scim::users::get_user_by_id,
+                routes::scim::users::create_user,
+                routes::scim::users::replace_user,
+                routes::scim::users::patch_user,
+                routes::scim::users::delete_user,
+                // SCIM Group Routes
+                routes::scim::groups::get_groups,
+                routes::scim::groups::get_group_by_id,
+                routes::scim::groups::create_group,
+                routes::scim::groups::replace_group,
+                routes::scim::groups::patch_group,
+                routes::scim::groups::delete_group
+            ],
+        )
 }
@@ -1,11 +1,15 @@
 use crate::db::{get_logs_db, AuthRsDatabase};
+use crate::utils::pagination::{Page, Pagination};
 use anyhow::Result;
-use mongodb::bson::{doc, DateTime, Uuid};
+use mongodb::bson::{self, doc, Bson, DateTime, Document, Uuid};
 use rocket::{
-    futures::StreamExt,
+    futures::{StreamExt, TryStreamExt},
     serde::{Deserialize, Serialize},
 };
-use rocket_db_pools::{mongodb::Collection, Connection};
+use rocket_db_pools::{
+    mongodb::{Collection, Database, IndexModel},
+    Connection,
+};
 use std::collections::HashMap;
 use std::fmt;
 use thiserror::Error;
@@ -60,8 +64,14 @@ pub struct AuditLog {
 #[serde(crate = "rocket::serde")]
 pub enum AuditLogAction {
     Create,
+    Import,
     Update,
     Login,
+    Logout,
+    LogoutEverywhere,
+    UseRecoveryCode,
+    Lockout,
+    Unlock,
     Delete,
 }
 
@@ -74,6 +84,7 @@ pub enum AuditLogEntityType {
     Settings,
     RegistrationToken,
     Passkey,
+    SigningKey,
     Unknown,
 }
 
@@ -85,6 +96,9 @@ impl AuditLogEntityType {
             "ROLE" => Ok(AuditLogEntityType::Role),
             "OAUTH_APPLICATION" => Ok(AuditLogEntityType::OAuthApplication),
             "SETTINGS" => Ok(AuditLogEntityType::Settings),
+            "REGISTRATION_TOKEN" => Ok(AuditLogEntityType::RegistrationToken),
+            "PASSKEY" => Ok(AuditLogEntityType::Passkey),
+            "SIGNING_KEY" => Ok(AuditLogEntityType::SigningKey),
             _ => Err(AuditLogError::InvalidInput(format!(
                 "Unknown entity type: {}",
                 entity_type
@@ -93,6 +107,76 @@ impl AuditLogEntityType {
     }
 }
 
+impl AuditLogAction {
+    pub fn from_string(action: &str) -> Result<Self, AuditLogError> {
+        match action.to_uppercase().as_str() {
+            "CREATE" => Ok(AuditLogAction::Create),
+            "IMPORT" => Ok(AuditLogAction::Import),
+            "UPDATE" => Ok(AuditLogAction::Update),
+            "LOGIN" => Ok(AuditLogAction::Login),
+            "LOGOUT" => Ok(AuditLogAction::Logout),
+            "LOGOUT_EVERYWHERE" => Ok(AuditLogAction::LogoutEverywhere),
+            "USE_RECOVERY_CODE" => Ok(AuditLogAction::UseRecoveryCode),
+            "LOCKOUT" => Ok(AuditLogAction::Lockout),
+            "UNLOCK" => Ok(AuditLogAction::Unlock),
+            "DELETE" => Ok(AuditLogAction::Delete),
+            _ => Err(AuditLogError::InvalidInput(format!(
+                "Unknown action: {}",
+                action
+            ))),
+        }
+    }
+}
+
+/// Criteria of the audit log search, unset criteria match every log
+#[derive(Debug, Default)]
+pub struct AuditLogSearch {
+    pub author_id: Option<Uuid>,
+    pub entity_id: Option<String>,
+    pub entity_type: Option<AuditLogEntityType>,
+    pub action: Option<AuditLogAction>,
+    // Inclusive
+    pub from: Option<DateTime>,
+    // Exclusive
+    pub to: Option<DateTime>,
+}
+
+impl AuditLogSearch {
+    pub fn to_filter(&self) -> Result<Document, AuditLogError> {
+        let mut filter = Document::new();
+        if let Some(author_id) = self.author_id {
+            filter.insert("authorId", author_id);
+        }
+        if let Some(entity_id) = &self.entity_id {
+            filter.insert("entityId", entity_id);
+        }
+        // Settings and signing keys share a collection, so the type is matched on the log itself
+        if let Some(entity_type) = &self.entity_type {
+            filter.insert("entityType", Self::to_bson(entity_type)?);
+        }
+        if let Some(action) = &self.action {
+            filter.insert("action", Self::to_bson(action)?);
+        }
+
+        let mut created_at = Document::new();
+        if let Some(from) = self.from {
+            created_at.insert("$gte", from);
+        }
+        if let Some(to) = self.to {
+            created_at.insert("$lt", to);
+        }
+        if !created_at.is_empty() {
+            filter.insert("createdAt", created_at);
+        }
+
+        Ok(filter)
+    }
+
+    fn to_bson<T: Serialize>(value: &T) -> Result<Bson, AuditLogError> {
+        bson::to_bson(value).map_err(|err| AuditLogError::InvalidInput(err.to_string()))
+    }
+}
+
 impl fmt::Display for AuditLogEntityType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -102,6 +186,7 @@ impl fmt::Display for AuditLogEntityType {
             AuditLogEntityType::Settings => write!(f, "SETTINGS"),
             AuditLogEntityType::RegistrationToken => write!(f, "REGISTRATION_TOKEN"),
             AuditLogEntityType::Passkey => write!(f, "PASSKEY"),
+            AuditLogEntityType::SigningKey => write!(f, "SIGNING_KEY"),
             AuditLogEntityType::Unknown => write!(f, "UNKNOWN"),
         }
     }
@@ -114,6 +199,14 @@ impl AuditLog {
     pub const COLLECTION_NAME_REGISTRATION_TOKENS: &'static str = "registration-token-logs";
     pub const COLLECTION_NAME_PASSKEYS: &'static str = "passkey-logs";
     pub const COLLECTION_NAME_SYSTEM: &'static str = "system-logs";
+    pub const COLLECTION_NAMES: [&'static str; 6] = [
+        Self::COLLECTION_NAME_USERS,
+        Self::COLLECTION_NAME_ROLES,
+        Self::COLLECTION_NAME_OAUTH_APPLICATIONS,
+        Self::COLLECTION_NAME_REGISTRATION_TOKENS,
+        Self::COLLECTION_NAME_PASSKEYS,
+        Self::COLLECTION_NAME_SYSTEM,
+    ];
 
     #[allow(unused)]
     pub fn new(
@@ -471,6 +564,133 @@ impl AuditLog {
         }
     }
 
+    /// One page of the logs of an entity type, see `PageQuery` for the parameters
+    pub async fn get_page_from_type(
+        entity_type: AuditLogEntityType,
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<Page<Self>, AuditLogError> {
+        let db = match Self::get_collection(&entity_type, connection) {
+            Some(db) => db,
+            None => {
+                return Err(AuditLogError::InvalidEntityType(format!(
+                    "Invalid entity type: {:?}",
+                    entity_type
+                )))
+            }
+        };
+
+        pagination.find(&db, filter).await.map_err(|err| {
+            AuditLogError::DatabaseError(format!("Error fetching audit logs: {}", err))
+        })
+    }
+
+    /// Creates the indexes of the search fields in every log collection
+    pub async fn initialize(db: &Database) -> Result<(), AuditLogError> {
+        for collection_name in Self::COLLECTION_NAMES {
+            // Every search is sorted by the creation time, with the id as tie breaker
+            let indexes = vec![
+                IndexModel::builder()
+                    .keys(doc! { "createdAt": 1, "_id": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "authorId": 1, "createdAt": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "entityId": 1, "createdAt": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "action": 1, "createdAt": 1 })
+                    .build(),
+            ];
+
+            db.collection::<AuditLog>(collection_name)
+                .create_indexes(indexes, None)
+                .await
+                .map_err(|err| {
+                    AuditLogError::DatabaseError(format!(
+                        "Error creating audit log indexes: {:?}",
+                        err
+                    ))
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// One page of the logs of all entity types matching the search
+    pub async fn search(
+        search: &AuditLogSearch,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<Page<Self>, AuditLogError> {
+        Self::get_page(search.to_filter()?, pagination, connection).await
+    }
+
+    /// One page of the logs of all entity types, merged in a single sort order
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<Page<Self>, AuditLogError> {
+        let db = get_logs_db(connection);
+        let collection_names = Self::COLLECTION_NAMES;
+
+        let mut total = 0;
+        for collection_name in collection_names {
+            total += db
+                .collection::<AuditLog>(collection_name)
+                .count_documents(filter.clone(), None)
+                .await
+                .map_err(|err| {
+                    AuditLogError::DatabaseError(format!("Error counting audit logs: {}", err))
+                })?;
+        }
+
+        // Every collection only contributes the entries that can end up on this page, the
+        // union is then sorted and limited again
+        let mut page_filter = filter;
+        if let Some(cursor_filter) = pagination.cursor_filter() {
+            page_filter = doc! { "$and": [page_filter, cursor_filter] };
+        }
+        let collection_stages = vec![
+            doc! { "$match": page_filter },
+            doc! { "$sort": pagination.sort() },
+            doc! { "$limit": pagination.skip() as i64 + pagination.fetch_limit() },
+        ];
+
+        let mut pipeline = collection_stages.clone();
+        for collection_name in &collection_names[1..] {
+            pipeline.push(doc! {
+                "$unionWith": {
+                    "coll": *collection_name,
+                    "pipeline": collection_stages.clone()
+                }
+            });
+        }
+        pipeline.push(doc! { "$sort": pagination.sort() });
+        pipeline.push(doc! { "$skip": pagination.skip() as i64 });
+        pipeline.push(doc! { "$limit": pagination.fetch_limit() });
+
+        let logs = db
+            .collection::<AuditLog>(collection_names[0])
+            .aggregate(pipeline, None)
+            .await
+            .map_err(|err| {
+                AuditLogError::DatabaseError(format!("Error fetching audit logs: {}", err))
+            })?
+            .try_collect::<Vec<Document>>()
+            .await
+            .map_err(|err| AuditLogError::DatabaseError(err.to_string()))?
+            .into_iter()
+            .map(bson::from_document::<AuditLog>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| AuditLogError::DatabaseError(err.to_string()))?;
+
+        Ok(pagination.to_page(logs, total))
+    }
+
     #[allow(unused)]
     pub async fn insert(
         &self,
@@ -511,7 +731,9 @@ impl AuditLog {
                 Some(db.collection(Self::COLLECTION_NAME_REGISTRATION_TOKENS))
             }
             AuditLogEntityType::Passkey => Some(db.collection(Self::COLLECTION_NAME_PASSKEYS)),
-            AuditLogEntityType::Settings => Some(db.collection(Self::COLLECTION_NAME_SYSTEM)),
+            AuditLogEntityType::Settings | AuditLogEntityType::SigningKey => {
+                Some(db.collection(Self::COLLECTION_NAME_SYSTEM))
+            }
             AuditLogEntityType::Unknown => None,
         }
     }
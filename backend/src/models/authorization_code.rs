@@ -0,0 +1,119 @@
+use super::{http_response::HttpResponse, oauth_scope::OAuthScope};
+use crate::auth::pkce::CodeChallengeMethod;
+use crate::store::{self, StoreError};
+use crate::utils::hash_token::hash_token;
+use anyhow::Result;
+use mongodb::bson::{DateTime, Uuid};
+use rand::Rng;
+use rocket::serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[allow(unused)]
+pub enum AuthorizationCodeError {
+    #[error("Authorization code not found")]
+    NotFound,
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl<T> From<AuthorizationCodeError> for HttpResponse<T> {
+    fn from(error: AuthorizationCodeError) -> Self {
+        match error {
+            AuthorizationCodeError::NotFound => {
+                HttpResponse::not_found("Authorization code not found")
+            }
+            AuthorizationCodeError::DatabaseError(msg) => {
+                HttpResponse::internal_error(&format!("Database error: {}", msg))
+            }
+        }
+    }
+}
+
+impl From<StoreError> for AuthorizationCodeError {
+    fn from(error: StoreError) -> Self {
+        AuthorizationCodeError::DatabaseError(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizationCode {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub code_hash: String,
+    pub client_id: Uuid,
+    pub user_id: Uuid,
+    pub scope: Vec<OAuthScope>,
+    pub redirect_uri: String,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<CodeChallengeMethod>,
+    pub created_at: DateTime,
+}
+
+impl AuthorizationCode {
+    pub const STORE_NAMESPACE: &'static str = "oauth-codes";
+
+    pub const AUTHORIZATION_CODE_LIFETIME: u64 = 5 * 60;
+
+    fn generate_code() -> String {
+        rand::rng()
+            .sample_iter(rand::distr::Alphanumeric)
+            .take(64)
+            .map(char::from)
+            .collect()
+    }
+
+    /// Returns the new authorization code together with its plain value, which is only known at this point
+    pub fn new(
+        client_id: Uuid,
+        user_id: Uuid,
+        scope: Vec<OAuthScope>,
+        redirect_uri: String,
+        nonce: Option<String>,
+        code_challenge: Option<String>,
+        code_challenge_method: Option<CodeChallengeMethod>,
+    ) -> (Self, String) {
+        let code = Self::generate_code();
+
+        (
+            Self {
+                id: Uuid::new(),
+                code_hash: hash_token(&code),
+                client_id,
+                user_id,
+                scope,
+                redirect_uri,
+                nonce,
+                code_challenge,
+                code_challenge_method,
+                created_at: DateTime::now(),
+            },
+            code,
+        )
+    }
+
+    /// Codes are keyed by their hash, the store takes care of the expiry
+    pub async fn insert(&self) -> Result<AuthorizationCode, AuthorizationCodeError> {
+        store::put(
+            Self::STORE_NAMESPACE,
+            &self.code_hash,
+            self,
+            Duration::from_secs(Self::AUTHORIZATION_CODE_LIFETIME),
+        )
+        .await?;
+
+        Ok(self.clone())
+    }
+
+    /// Takes the code out of the store, so it can only ever be redeemed once
+    pub async fn redeem(code: &str) -> Result<AuthorizationCode, AuthorizationCodeError> {
+        store::take::<Self>(Self::STORE_NAMESPACE, &hash_token(code))
+            .await?
+            .ok_or(AuthorizationCodeError::NotFound)
+    }
+}
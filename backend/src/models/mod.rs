@@ -1,11 +1,18 @@
 pub mod audit_log;
+pub mod authorization_code;
 pub mod http_response;
 pub mod oauth_application;
 pub mod oauth_scope;
 pub mod oauth_token;
 pub mod passkey;
+pub mod password_hashing;
+pub mod password_policy;
+pub mod permission;
+pub mod refresh_token;
 pub mod registration_token;
 pub mod role;
+pub mod session;
 pub mod settings;
+pub mod signing_key;
 pub mod user;
 pub mod user_error;
@@ -1,4 +1,5 @@
 use crate::db::{get_main_db, AuthRsDatabase};
+use crate::utils::pagination::{Page, Pagination};
 use anyhow::Result;
 use mongodb::bson::{doc, DateTime, Document, Uuid};
 use rand::Rng;
@@ -84,6 +85,12 @@ pub struct OAuthApplication {
     pub description: Option<String>,
     pub redirect_uris: Vec<String>,
     pub secret: String,
+    // Public clients (SPAs, mobile apps) can not keep a secret and have to use PKCE instead
+    #[serde(default)]
+    pub public: bool,
+    // Roles the application may learn about through the roles:read scope
+    #[serde(default)]
+    pub disclosed_roles: Vec<Uuid>,
     pub owner: Uuid,
     pub created_at: DateTime,
 }
@@ -97,6 +104,8 @@ pub struct OAuthApplicationDTO {
     pub name: String,
     pub description: Option<String>,
     pub redirect_uris: Vec<String>,
+    pub public: bool,
+    pub disclosed_roles: Vec<Uuid>,
     pub owner: Uuid,
     pub created_at: DateTime,
 }
@@ -116,6 +125,7 @@ impl OAuthApplication {
         name: String,
         description: Option<String>,
         redirect_uris: Vec<String>,
+        public: bool,
         owner: Uuid,
     ) -> OAuthApplicationResult<Self> {
         Ok(Self {
@@ -124,6 +134,8 @@ impl OAuthApplication {
             description,
             redirect_uris,
             secret: Self::generate_secret(),
+            public,
+            disclosed_roles: Vec::new(),
             owner,
             created_at: DateTime::now(),
         })
@@ -135,6 +147,8 @@ impl OAuthApplication {
             name: self.name.clone(),
             description: self.description.clone(),
             redirect_uris: self.redirect_uris.clone(),
+            public: self.public,
+            disclosed_roles: self.disclosed_roles.clone(),
             owner: self.owner,
             created_at: self.created_at,
         }
@@ -185,6 +199,22 @@ impl OAuthApplication {
         }
     }
 
+    /// One page of the OAuth Applications matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> OAuthApplicationResult<Page<OAuthApplication>> {
+        let db = Self::get_collection(connection);
+
+        pagination.find(&db, filter).await.map_err(|err| {
+            OAuthApplicationError::DatabaseError(format!(
+                "Error fetching OAuth Applications: {:?}",
+                err
+            ))
+        })
+    }
+
     #[allow(unused)]
     pub async fn insert(
         &self,
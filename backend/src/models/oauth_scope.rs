@@ -6,6 +6,9 @@ use rocket::serde::{de::Error, Deserialize, Deserializer};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum OAuthScope {
+    OpenId,
+    Profile,
+    Email,
     Roles(ScopeActions),
     AuditLogs(ScopeActions),
     Users(ScopeActions),
@@ -86,6 +89,9 @@ impl<'de> Deserialize<'de> for ScopeActions {
 impl Display for OAuthScope {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            OAuthScope::OpenId => write!(f, "openid"),
+            OAuthScope::Profile => write!(f, "profile"),
+            OAuthScope::Email => write!(f, "email"),
             OAuthScope::Roles(actions) => write!(f, "roles:{}", actions),
             OAuthScope::AuditLogs(actions) => write!(f, "audit_logs:{}", actions),
             OAuthScope::Users(actions) => write!(f, "user:{}", actions),
@@ -99,6 +105,13 @@ impl TryFrom<String> for OAuthScope {
     type Error = &'static str;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "openid" => return Ok(OAuthScope::OpenId),
+            "profile" => return Ok(OAuthScope::Profile),
+            "email" => return Ok(OAuthScope::Email),
+            _ => {}
+        }
+
         let parts: Vec<&str> = value.split(':').collect();
         if parts.len() != 2 {
             return Err("Invalid scope format");
@@ -121,6 +134,9 @@ impl Serialize for OAuthScope {
         S: Serializer,
     {
         let s = match self {
+            OAuthScope::OpenId => "openid".to_string(),
+            OAuthScope::Profile => "profile".to_string(),
+            OAuthScope::Email => "email".to_string(),
             OAuthScope::Roles(action) => format!("roles:{}", action),
             OAuthScope::AuditLogs(action) => format!("audit_logs:{}", action),
             OAuthScope::Users(action) => format!("user:{}", action),
@@ -137,6 +153,13 @@ impl<'de> Deserialize<'de> for OAuthScope {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "openid" => return Ok(OAuthScope::OpenId),
+            "profile" => return Ok(OAuthScope::Profile),
+            "email" => return Ok(OAuthScope::Email),
+            _ => {}
+        }
+
         let parts: Vec<&str> = s.split(':').collect();
         if parts.len() != 2 {
             return Err(Error::custom("Invalid scope"));
@@ -1,4 +1,4 @@
-use super::{http_response::HttpResponse, oauth_scope::OAuthScope};
+use super::{http_response::HttpResponse, oauth_scope::OAuthScope, refresh_token::RefreshToken};
 use crate::db::{get_main_db, AuthRsDatabase};
 use crate::errors::AppError;
 use anyhow::Result;
@@ -10,7 +10,7 @@ use rocket::{
     serde::{Deserialize, Serialize},
 };
 use rocket_db_pools::{
-    mongodb::{Collection, Database},
+    mongodb::{options::IndexOptions, Collection, Database, IndexModel},
     Connection,
 };
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -72,12 +72,18 @@ pub struct OAuthToken {
     pub token: String,
     pub scope: Vec<OAuthScope>,
     pub expires_in: u64,
+    // The token stays stored while its family can be refreshed, a TTL index removes it afterwards
+    #[serde(default = "OAuthToken::family_expires_at")]
+    pub expires_at: DateTime,
     pub created_at: DateTime,
 }
 
 impl OAuthToken {
     pub const COLLECTION_NAME: &'static str = "oauth-tokens";
 
+    // Access tokens are short lived, clients use their refresh token to get a new one
+    pub const ACCESS_TOKEN_LIFETIME: u64 = 60 * 60;
+
     fn generate_token() -> String {
         let mut rng = rand::rng();
         let token: String = (0..128)
@@ -93,6 +99,49 @@ impl OAuthToken {
         token
     }
 
+    fn family_expires_at() -> DateTime {
+        DateTime::from_millis(
+            DateTime::now().timestamp_millis()
+                + (RefreshToken::REFRESH_TOKEN_LIFETIME * 1000) as i64,
+        )
+    }
+
+    /// Backfills the expiry of older tokens and creates the TTL index that removes them
+    pub async fn initialize(db: &Collection<OAuthToken>) -> Result<(), OAuthTokenError> {
+        let backfill = vec![doc! {
+            "$set": {
+                "expiresAt": {
+                    "$add": ["$createdAt", (RefreshToken::REFRESH_TOKEN_LIFETIME * 1000) as i64]
+                }
+            }
+        }];
+        db.update_many(doc! { "expiresAt": { "$exists": false } }, backfill, None)
+            .await
+            .map_err(|err| {
+                OAuthTokenError::DatabaseError(format!(
+                    "Error backfilling oauth token expiry: {:?}",
+                    err
+                ))
+            })?;
+
+        let index = IndexModel::builder()
+            .keys(doc! { "expiresAt": 1 })
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(0))
+                    .build(),
+            )
+            .build();
+
+        match db.create_index(index, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(OAuthTokenError::DatabaseError(format!(
+                "Error creating oauth token indexes: {:?}",
+                err
+            ))),
+        }
+    }
+
     /// Checks if the token is expired
     pub fn is_expired(&self) -> bool {
         let created_at = self.created_at.timestamp_millis() as u64;
@@ -121,6 +170,7 @@ impl OAuthToken {
             token: Self::generate_token(),
             scope,
             expires_in,
+            expires_at: Self::family_expires_at(),
             created_at: DateTime::now(),
         })
     }
@@ -172,6 +222,26 @@ impl OAuthToken {
         }
     }
 
+    #[allow(unused)]
+    pub async fn get_by_id(
+        id: Uuid,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<OAuthToken, OAuthTokenError> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": id
+        };
+        match db.find_one(filter, None).await {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => Err(OAuthTokenError::NotFound),
+            Err(err) => Err(OAuthTokenError::DatabaseError(format!(
+                "Error finding token: {:?}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     pub async fn get_by_application_id(
         application_id: Uuid,
@@ -271,7 +341,8 @@ impl OAuthToken {
         };
 
         self.scope = scope;
-        self.expires_in = 30 * 24 * 60 * 60;
+        self.expires_in = Self::ACCESS_TOKEN_LIFETIME;
+        self.expires_at = Self::family_expires_at();
         self.created_at = DateTime::now();
 
         match db.replace_one(filter, self.clone(), None).await {
@@ -283,6 +354,32 @@ impl OAuthToken {
         }
     }
 
+    /// Replaces the access token value, used when the client redeems a refresh token
+    #[allow(unused)]
+    pub async fn rotate(
+        &mut self,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<OAuthToken, OAuthTokenError> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id
+        };
+
+        self.token = Self::generate_token();
+        self.expires_in = Self::ACCESS_TOKEN_LIFETIME;
+        self.expires_at = Self::family_expires_at();
+        self.created_at = DateTime::now();
+
+        match db.replace_one(filter, self.clone(), None).await {
+            Ok(_) => Ok(self.clone()),
+            Err(err) => Err(OAuthTokenError::DatabaseError(format!(
+                "Error rotating token: {:?}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     pub async fn delete(
         &self,
@@ -290,6 +387,10 @@ impl OAuthToken {
     ) -> Result<OAuthToken, OAuthTokenError> {
         let db = Self::get_collection(connection);
 
+        RefreshToken::delete_all_matching(doc! { "familyId": self.id }, connection)
+            .await
+            .map_err(|err| OAuthTokenError::DatabaseError(err.to_string()))?;
+
         let filter = doc! {
             "_id": self.id
         };
@@ -309,6 +410,17 @@ impl OAuthToken {
     ) -> Result<(), OAuthTokenError> {
         let db = Self::get_collection(connection);
 
+        // The access token ids are the families of the refresh tokens
+        let family_ids = db
+            .distinct("_id", filter.clone(), None)
+            .await
+            .map_err(|err| {
+                OAuthTokenError::DatabaseError(format!("Error finding OAuth Tokens: {:?}", err))
+            })?;
+        RefreshToken::delete_all_matching(doc! { "familyId": { "$in": family_ids } }, connection)
+            .await
+            .map_err(|err| OAuthTokenError::DatabaseError(err.to_string()))?;
+
         match db.delete_many(filter, None).await {
             Ok(_) => Ok(()),
             Err(err) => Err(OAuthTokenError::DatabaseError(format!(
@@ -1,5 +1,6 @@
 use super::http_response::HttpResponse;
 use crate::db::{get_main_db, AuthRsDatabase};
+use crate::utils::pagination::{Page, Pagination};
 use anyhow::Result;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
@@ -220,6 +221,19 @@ impl Passkey {
         }
     }
 
+    /// One page of the passkeys matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> PasskeyResult<Page<Passkey>> {
+        let db = Self::get_collection(connection);
+
+        pagination.find(&db, filter).await.map_err(|err| {
+            PasskeyError::DatabaseError(format!("Error fetching passkeys: {:?}", err))
+        })
+    }
+
     #[allow(unused)]
     pub async fn insert(&self, connection: &Connection<AuthRsDatabase>) -> PasskeyResult<Passkey> {
         let db = Self::get_collection(connection);
@@ -0,0 +1,210 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version};
+use pbkdf2::Pbkdf2;
+use rocket::serde::{Deserialize, Serialize};
+use scrypt::Scrypt;
+
+use super::user_error::{UserError, UserResult};
+
+// Upper bounds so a misconfiguration can't turn every login into a denial of service
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+// The same kind of bounds for imported hashes, every login against them pays their cost
+const MAX_BCRYPT_COST: u32 = 16;
+const MAX_SCRYPT_LOG_N: u32 = 20;
+const MAX_SCRYPT_BLOCK_SIZE: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 2_000_000;
+
+/// Argon2id parameters for new password hashes, part of the global settings
+///
+/// Hashes made with other parameters or imported from bcrypt, scrypt or PBKDF2
+/// keep working and are re-hashed with these parameters on the next login.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordHashing {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashing {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl PasswordHashing {
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Checks that the parameters are usable and within sane bounds
+    pub fn validate(&self) -> Result<(), String> {
+        if self.memory_kib > MAX_MEMORY_KIB {
+            return Err(format!("Memory must be at most {} KiB", MAX_MEMORY_KIB));
+        }
+        if !(1..=MAX_ITERATIONS).contains(&self.iterations) {
+            return Err(format!(
+                "Iterations must be between 1 and {}",
+                MAX_ITERATIONS
+            ));
+        }
+        if !(1..=MAX_PARALLELISM).contains(&self.parallelism) {
+            return Err(format!(
+                "Parallelism must be between 1 and {}",
+                MAX_PARALLELISM
+            ));
+        }
+
+        self.argon2().map(|_| ()).map_err(|err| err.to_string())
+    }
+
+    pub fn hash(&self, password: &str) -> UserResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .map_err(|_| UserError::PasswordHashingError)?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|_| UserError::PasswordHashingError)
+    }
+
+    /// Verifies a password against an Argon2, scrypt, PBKDF2 (PHC strings) or bcrypt hash
+    pub fn verify(password: &str, password_hash: &str) -> UserResult<()> {
+        if Self::is_bcrypt(password_hash) {
+            return match bcrypt::verify(password, password_hash) {
+                Ok(true) => Ok(()),
+                _ => Err(UserError::PasswordHashingError),
+            };
+        }
+
+        let hash = PasswordHash::new(password_hash).map_err(|_| UserError::PasswordHashingError)?;
+        hash.verify_password(&[&Argon2::default(), &Scrypt, &Pbkdf2], password)
+            .map_err(|_| UserError::PasswordHashingError)
+    }
+
+    /// Whether the hash is in a format `verify` understands, used for imported hashes
+    pub fn is_supported(password_hash: &str) -> bool {
+        if Self::is_bcrypt(password_hash) {
+            return password_hash.parse::<bcrypt::HashParts>().is_ok();
+        }
+
+        match PasswordHash::new(password_hash) {
+            Ok(hash) => matches!(
+                hash.algorithm.as_str(),
+                "argon2id" | "argon2i" | "argon2d" | "scrypt" | "pbkdf2-sha256" | "pbkdf2-sha512"
+            ),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether verifying against the hash stays within the bounds of new hashes, imported
+    /// hashes with more expensive parameters are rejected
+    pub fn is_within_cost_limits(password_hash: &str) -> bool {
+        if Self::is_bcrypt(password_hash) {
+            return password_hash
+                .parse::<bcrypt::HashParts>()
+                .is_ok_and(|parts| parts.get_cost() <= MAX_BCRYPT_COST);
+        }
+
+        let Ok(hash) = PasswordHash::new(password_hash) else {
+            return false;
+        };
+        // Parameters that are left out fall back to the cheap defaults of the algorithm
+        let param = |name: &str| hash.params.get_decimal(name).unwrap_or(0);
+        match hash.algorithm.as_str() {
+            "argon2id" | "argon2i" | "argon2d" => Params::try_from(&hash).is_ok_and(|params| {
+                params.m_cost() <= MAX_MEMORY_KIB
+                    && params.t_cost() <= MAX_ITERATIONS
+                    && params.p_cost() <= MAX_PARALLELISM
+            }),
+            "scrypt" => {
+                let (log_n, block_size) = (param("ln"), param("r"));
+                // Memory is 128 * r * 2^ln bytes
+                log_n <= MAX_SCRYPT_LOG_N
+                    && block_size <= MAX_SCRYPT_BLOCK_SIZE
+                    && param("p") <= MAX_PARALLELISM
+                    && (128u64 * block_size as u64) << log_n <= MAX_MEMORY_KIB as u64 * 1024
+            }
+            "pbkdf2-sha256" | "pbkdf2-sha512" => param("i") <= MAX_PBKDF2_ROUNDS,
+            _ => false,
+        }
+    }
+
+    /// Whether the hash was made with another algorithm or outdated parameters
+    pub fn needs_rehash(&self, password_hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(password_hash) else {
+            return true;
+        };
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        match Params::try_from(&hash) {
+            Ok(params) => {
+                params.m_cost() != self.memory_kib
+                    || params.t_cost() != self.iterations
+                    || params.p_cost() != self.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn is_bcrypt(password_hash: &str) -> bool {
+        ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| password_hash.starts_with(prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT_AND_OUTPUT: &str = "c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA";
+
+    fn phc(algorithm: &str, params: &str) -> String {
+        format!("${}${}${}", algorithm, params, SALT_AND_OUTPUT)
+    }
+
+    #[test]
+    fn accepts_common_parameters() {
+        for hash in [
+            phc("argon2id$v=19", "m=19456,t=2,p=1"),
+            phc("scrypt", "ln=15,r=8,p=1"),
+            phc("pbkdf2-sha256", "i=600000"),
+        ] {
+            assert!(PasswordHashing::is_within_cost_limits(&hash), "{}", hash);
+        }
+        let bcrypt = bcrypt::hash("password", 4).unwrap();
+        assert!(PasswordHashing::is_within_cost_limits(&bcrypt));
+    }
+
+    #[test]
+    fn rejects_expensive_parameters() {
+        for hash in [
+            phc("argon2id$v=19", "m=4194304,t=2,p=1"),
+            phc("argon2id$v=19", "m=19456,t=1000,p=1"),
+            phc("scrypt", "ln=30,r=8,p=1"),
+            phc("scrypt", "ln=15,r=8,p=1000"),
+            // Within each bound, but 2 GiB of memory in total
+            phc("scrypt", "ln=20,r=16,p=1"),
+            phc("pbkdf2-sha256", "i=4000000000"),
+        ] {
+            assert!(!PasswordHashing::is_within_cost_limits(&hash), "{}", hash);
+        }
+        let bcrypt = bcrypt::hash("password", 4)
+            .unwrap()
+            .replacen("$04$", "$31$", 1);
+        assert!(!PasswordHashing::is_within_cost_limits(&bcrypt));
+    }
+}
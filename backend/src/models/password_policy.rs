@@ -0,0 +1,142 @@
+use rocket::serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::auth::breached_passwords;
+
+use super::user_error::{UserError, UserResult};
+
+// Parts of names or emails shorter than this are too common to reject passwords for
+const MIN_PERSONAL_INFO_LENGTH: usize = 3;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PasswordPolicyViolation {
+    #[error("Password must be at least {0} characters long")]
+    TooShort(u32),
+
+    #[error("Password must contain an uppercase letter")]
+    MissingUppercase,
+
+    #[error("Password must contain a lowercase letter")]
+    MissingLowercase,
+
+    #[error("Password must contain a digit")]
+    MissingDigit,
+
+    #[error("Password must contain a symbol")]
+    MissingSymbol,
+
+    #[error("Password must not contain your name or email")]
+    ContainsPersonalInfo,
+
+    #[error("Password has appeared in a data breach")]
+    Breached,
+}
+
+impl From<PasswordPolicyViolation> for UserError {
+    fn from(violation: PasswordPolicyViolation) -> Self {
+        match violation {
+            PasswordPolicyViolation::TooShort(_) => UserError::PasswordToShort,
+            violation => UserError::PasswordPolicyViolation(violation.to_string()),
+        }
+    }
+}
+
+/// Rules every new password has to follow, part of the global settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordPolicy {
+    pub min_length: u32,
+    #[serde(default)]
+    pub require_uppercase: bool,
+    #[serde(default)]
+    pub require_lowercase: bool,
+    #[serde(default)]
+    pub require_digit: bool,
+    #[serde(default)]
+    pub require_symbol: bool,
+    // Rejects passwords containing the email or parts of the name
+    #[serde(default)]
+    pub deny_personal_info: bool,
+    // Rejects passwords found in the local breached password list
+    #[serde(default)]
+    pub deny_breached: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            deny_personal_info: false,
+            deny_breached: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks all rules that don't need any lookups, personal info is e.g. the email and name of the user
+    pub fn check(
+        &self,
+        password: &str,
+        personal_info: &[&str],
+    ) -> Result<(), PasswordPolicyViolation> {
+        if password.chars().count() < self.min_length as usize {
+            return Err(PasswordPolicyViolation::TooShort(self.min_length));
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            return Err(PasswordPolicyViolation::MissingUppercase);
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            return Err(PasswordPolicyViolation::MissingLowercase);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err(PasswordPolicyViolation::MissingDigit);
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            return Err(PasswordPolicyViolation::MissingSymbol);
+        }
+        if self.deny_personal_info && Self::contains_personal_info(password, personal_info) {
+            return Err(PasswordPolicyViolation::ContainsPersonalInfo);
+        }
+
+        Ok(())
+    }
+
+    /// Checks all rules, including the breached password list
+    pub async fn validate(&self, password: &str, personal_info: &[&str]) -> UserResult<()> {
+        self.check(password, personal_info)?;
+
+        if self.deny_breached {
+            match breached_passwords::is_breached(password).await {
+                Ok(true) => return Err(PasswordPolicyViolation::Breached.into()),
+                Ok(false) => {}
+                // A broken list must not stop users from setting passwords
+                Err(err) => tracing::error!(error = %err, "Failed to check breached passwords"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn contains_personal_info(password: &str, personal_info: &[&str]) -> bool {
+        let password = password.to_lowercase();
+
+        personal_info
+            .iter()
+            .copied()
+            .flat_map(|info| {
+                // Emails are also checked by their local part and its pieces, but not by the domain
+                let local_part = info.split('@').next().unwrap_or(info);
+                [info, local_part]
+                    .into_iter()
+                    .chain(local_part.split(|c: char| !c.is_alphanumeric()))
+            })
+            .map(|part| part.trim().to_lowercase())
+            .filter(|part| part.chars().count() >= MIN_PERSONAL_INFO_LENGTH)
+            .any(|part| password.contains(&part))
+    }
+}
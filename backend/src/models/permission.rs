@@ -0,0 +1,125 @@
+use rocket::serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Fine-grained permissions a role can grant. Permissions only cover actions on other
+/// users' data or the system itself, users can always manage their own account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum Permission {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:create")]
+    UsersCreate,
+    #[serde(rename = "users:update")]
+    UsersUpdate,
+    #[serde(rename = "users:disable")]
+    UsersDisable,
+    #[serde(rename = "users:delete")]
+    UsersDelete,
+    #[serde(rename = "users:mfa")]
+    UsersMfa,
+    #[serde(rename = "roles:create")]
+    RolesCreate,
+    #[serde(rename = "roles:update")]
+    RolesUpdate,
+    #[serde(rename = "roles:delete")]
+    RolesDelete,
+    #[serde(rename = "roles:assign")]
+    RolesAssign,
+    #[serde(rename = "oauth_applications:create")]
+    OAuthApplicationsCreate,
+    #[serde(rename = "oauth_applications:read")]
+    OAuthApplicationsRead,
+    #[serde(rename = "oauth_applications:update")]
+    OAuthApplicationsUpdate,
+    #[serde(rename = "oauth_applications:delete")]
+    OAuthApplicationsDelete,
+    #[serde(rename = "connections:read")]
+    ConnectionsRead,
+    #[serde(rename = "connections:delete")]
+    ConnectionsDelete,
+    #[serde(rename = "registration_tokens:read")]
+    RegistrationTokensRead,
+    #[serde(rename = "registration_tokens:create")]
+    RegistrationTokensCreate,
+    #[serde(rename = "registration_tokens:update")]
+    RegistrationTokensUpdate,
+    #[serde(rename = "registration_tokens:delete")]
+    RegistrationTokensDelete,
+    #[serde(rename = "passkeys:read")]
+    PasskeysRead,
+    #[serde(rename = "audit_logs:read")]
+    AuditLogsRead,
+    #[serde(rename = "settings:update")]
+    SettingsUpdate,
+    #[serde(rename = "signing_keys:read")]
+    SigningKeysRead,
+    #[serde(rename = "signing_keys:rotate")]
+    SigningKeysRotate,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 25] = [
+        Permission::UsersRead,
+        Permission::UsersCreate,
+        Permission::UsersUpdate,
+        Permission::UsersDisable,
+        Permission::UsersDelete,
+        Permission::UsersMfa,
+        Permission::RolesCreate,
+        Permission::RolesUpdate,
+        Permission::RolesDelete,
+        Permission::RolesAssign,
+        Permission::OAuthApplicationsCreate,
+        Permission::OAuthApplicationsRead,
+        Permission::OAuthApplicationsUpdate,
+        Permission::OAuthApplicationsDelete,
+        Permission::ConnectionsRead,
+        Permission::ConnectionsDelete,
+        Permission::RegistrationTokensRead,
+        Permission::RegistrationTokensCreate,
+        Permission::RegistrationTokensUpdate,
+        Permission::RegistrationTokensDelete,
+        Permission::PasskeysRead,
+        Permission::AuditLogsRead,
+        Permission::SettingsUpdate,
+        Permission::SigningKeysRead,
+        Permission::SigningKeysRotate,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::UsersRead => "users:read",
+            Permission::UsersCreate => "users:create",
+            Permission::UsersUpdate => "users:update",
+            Permission::UsersDisable => "users:disable",
+            Permission::UsersDelete => "users:delete",
+            Permission::UsersMfa => "users:mfa",
+            Permission::RolesCreate => "roles:create",
+            Permission::RolesUpdate => "roles:update",
+            Permission::RolesDelete => "roles:delete",
+            Permission::RolesAssign => "roles:assign",
+            Permission::OAuthApplicationsCreate => "oauth_applications:create",
+            Permission::OAuthApplicationsRead => "oauth_applications:read",
+            Permission::OAuthApplicationsUpdate => "oauth_applications:update",
+            Permission::OAuthApplicationsDelete => "oauth_applications:delete",
+            Permission::ConnectionsRead => "connections:read",
+            Permission::ConnectionsDelete => "connections:delete",
+            Permission::RegistrationTokensRead => "registration_tokens:read",
+            Permission::RegistrationTokensCreate => "registration_tokens:create",
+            Permission::RegistrationTokensUpdate => "registration_tokens:update",
+            Permission::RegistrationTokensDelete => "registration_tokens:delete",
+            Permission::PasskeysRead => "passkeys:read",
+            Permission::AuditLogsRead => "audit_logs:read",
+            Permission::SettingsUpdate => "settings:update",
+            Permission::SigningKeysRead => "signing_keys:read",
+            Permission::SigningKeysRotate => "signing_keys:rotate",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
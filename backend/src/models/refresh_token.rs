@@ -0,0 +1,215 @@
+use super::{http_response::HttpResponse, oauth_scope::OAuthScope};
+use crate::db::{get_main_db, AuthRsDatabase};
+use crate::utils::hash_token::hash_token;
+use anyhow::Result;
+use mongodb::bson::{doc, DateTime, Document, Uuid};
+use rand::Rng;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{
+    mongodb::{options::IndexOptions, Collection, IndexModel},
+    Connection,
+};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[allow(unused)]
+pub enum RefreshTokenError {
+    #[error("Refresh token not found")]
+    NotFound,
+
+    #[error("Refresh token expired")]
+    Expired,
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl<T> From<RefreshTokenError> for HttpResponse<T> {
+    fn from(error: RefreshTokenError) -> Self {
+        match error {
+            RefreshTokenError::NotFound => HttpResponse::not_found("Refresh token not found"),
+            RefreshTokenError::Expired => HttpResponse::unauthorized("Refresh token expired"),
+            RefreshTokenError::DatabaseError(msg) => {
+                HttpResponse::internal_error(&format!("Database error: {}", msg))
+            }
+        }
+    }
+}
+
+/// Refresh tokens are single use. Every refresh rotates the token, all tokens issued
+/// for the same authorization share a family, which is the id of their access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshToken {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub application_id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub scope: Vec<OAuthScope>,
+    pub used: bool,
+    pub expires_at: DateTime,
+    pub created_at: DateTime,
+}
+
+impl RefreshToken {
+    pub const COLLECTION_NAME: &'static str = "refresh-tokens";
+
+    pub const REFRESH_TOKEN_LIFETIME: u64 = 30 * 24 * 60 * 60;
+
+    fn generate_token() -> String {
+        rand::rng()
+            .sample_iter(rand::distr::Alphanumeric)
+            .take(128)
+            .map(char::from)
+            .collect()
+    }
+
+    /// Returns the new refresh token together with its plain value, which is only known at this point
+    pub fn new(
+        family_id: Uuid,
+        application_id: Uuid,
+        user_id: Uuid,
+        scope: Vec<OAuthScope>,
+    ) -> (Self, String) {
+        let token = Self::generate_token();
+        let now = DateTime::now();
+
+        (
+            Self {
+                id: Uuid::new(),
+                family_id,
+                application_id,
+                user_id,
+                token_hash: hash_token(&token),
+                scope,
+                used: false,
+                expires_at: DateTime::from_millis(
+                    now.timestamp_millis() + (Self::REFRESH_TOKEN_LIFETIME * 1000) as i64,
+                ),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Creates the family index and the TTL index that removes expired tokens
+    pub async fn initialize(db: &Collection<RefreshToken>) -> Result<(), RefreshTokenError> {
+        let indexes = vec![
+            IndexModel::builder().keys(doc! { "familyId": 1 }).build(),
+            IndexModel::builder()
+                .keys(doc! { "expiresAt": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        ];
+
+        match db.create_indexes(indexes, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RefreshTokenError::DatabaseError(format!(
+                "Error creating refresh token indexes: {:?}",
+                err
+            ))),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.timestamp_millis() <= DateTime::now().timestamp_millis()
+    }
+
+    #[allow(unused)]
+    pub async fn insert(
+        &self,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<RefreshToken, RefreshTokenError> {
+        let db = Self::get_collection(connection);
+
+        match db.insert_one(self.clone(), None).await {
+            Ok(_) => Ok(self.clone()),
+            Err(err) => Err(RefreshTokenError::DatabaseError(format!(
+                "Error inserting refresh token: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn get_by_token(
+        token: &str,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<RefreshToken, RefreshTokenError> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "tokenHash": hash_token(token)
+        };
+        match db.find_one(filter, None).await {
+            Ok(Some(refresh_token)) => {
+                if refresh_token.is_expired() {
+                    Err(RefreshTokenError::Expired)
+                } else {
+                    Ok(refresh_token)
+                }
+            }
+            Ok(None) => Err(RefreshTokenError::NotFound),
+            Err(err) => Err(RefreshTokenError::DatabaseError(format!(
+                "Error finding refresh token: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// Atomically marks the token as used, returns false if it was already used before
+    #[allow(unused)]
+    pub async fn mark_used(
+        &self,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<bool, RefreshTokenError> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id,
+            "used": false
+        };
+        let update = doc! {
+            "$set": {
+                "used": true
+            }
+        };
+        match db.find_one_and_update(filter, update, None).await {
+            Ok(refresh_token) => Ok(refresh_token.is_some()),
+            Err(err) => Err(RefreshTokenError::DatabaseError(format!(
+                "Error updating refresh token: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn delete_all_matching(
+        filter: Document,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> Result<(), RefreshTokenError> {
+        let db = Self::get_collection(connection);
+
+        match db.delete_many(filter, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RefreshTokenError::DatabaseError(format!(
+                "Error deleting refresh tokens: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    fn get_collection(connection: &Connection<AuthRsDatabase>) -> Collection<Self> {
+        let db = get_main_db(connection);
+        db.collection(Self::COLLECTION_NAME)
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     db::{get_main_db, AuthRsDatabase},
     errors::ApiError,
+    utils::pagination::{Page, Pagination},
 };
 use anyhow::Result;
 use mongodb::bson::{doc, DateTime, Document, Uuid};
@@ -188,6 +189,22 @@ impl RegistrationToken {
         }
     }
 
+    /// One page of the registration tokens matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> RegistrationTokenResult<Page<RegistrationToken>> {
+        let db = Self::get_collection(connection);
+
+        pagination.find(&db, filter).await.map_err(|err| {
+            RegistrationTokenError::DatabaseError(format!(
+                "Error fetching registration tokens: {:?}",
+                err
+            ))
+        })
+    }
+
     #[allow(unused)]
     pub async fn get_by_code(
         code: String,
@@ -1,14 +1,22 @@
-use crate::db::{get_main_db, AuthRsDatabase};
+use crate::{
+    db::{get_main_db, AuthRsDatabase},
+    utils::pagination::{Page, Pagination},
+    ADMIN_ROLE_ID,
+};
 use anyhow::Result;
 use mongodb::bson::{doc, DateTime, Document, Uuid};
 use rocket::{
     futures::StreamExt,
     serde::{Deserialize, Serialize},
 };
-use rocket_db_pools::{mongodb::Collection, Connection};
+use rocket_db_pools::{
+    mongodb::{Collection, Database},
+    Connection,
+};
+use std::collections::HashSet;
 use thiserror::Error;
 
-use super::{http_response::HttpResponse, user::User};
+use super::{http_response::HttpResponse, permission::Permission, user::User};
 
 #[derive(Error, Debug)]
 #[allow(unused)]
@@ -25,6 +33,15 @@ pub enum RoleError {
     #[error("Cannot modify system role")]
     SystemRoleModification,
 
+    #[error("Role {0} cannot inherit from itself")]
+    CyclicInheritance(Uuid),
+
+    #[error("Role {0} cannot be used as a parent role")]
+    InvalidParent(Uuid),
+
+    #[error("Missing permissions to perform this action")]
+    MissingPermissions,
+
     #[error("Database error: {0}")]
     DatabaseError(String),
 
@@ -40,6 +57,11 @@ impl RoleError {
             RoleError::NameNotFound(name) => format!("Role with name {} not found", name),
             RoleError::NameAlreadyExists(name) => format!("Role with name {} already exists", name),
             RoleError::SystemRoleModification => "Cannot modify system role".to_string(),
+            RoleError::CyclicInheritance(id) => format!("Role {} cannot inherit from itself", id),
+            RoleError::InvalidParent(id) => format!("Role {} cannot be used as a parent role", id),
+            RoleError::MissingPermissions => {
+                "Missing permissions to perform this action".to_string()
+            }
             RoleError::DatabaseError(msg) => format!("Database error: {}", msg),
             RoleError::InternalServerError(msg) => format!("Internal server error: {}", msg),
         }
@@ -70,6 +92,21 @@ impl<T> From<RoleError> for HttpResponse<T> {
                 message: "Cannot modify system role".to_string(),
                 data: None,
             },
+            RoleError::CyclicInheritance(id) => HttpResponse {
+                status: 400,
+                message: format!("Role {} cannot inherit from itself", id),
+                data: None,
+            },
+            RoleError::InvalidParent(id) => HttpResponse {
+                status: 400,
+                message: format!("Role {} cannot be used as a parent role", id),
+                data: None,
+            },
+            RoleError::MissingPermissions => HttpResponse {
+                status: 403,
+                message: "Missing permissions to perform this action".to_string(),
+                data: None,
+            },
             RoleError::DatabaseError(msg) => HttpResponse {
                 status: 500,
                 message: format!("Database error: {}", msg),
@@ -112,17 +149,24 @@ pub struct Role {
     pub id: Uuid,
     pub name: String,
     pub system: bool,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+    // Roles this role inherits permissions from
+    #[serde(default)]
+    pub parents: Vec<Uuid>,
     pub created_at: DateTime,
 }
 
 impl Role {
     pub const COLLECTION_NAME: &'static str = "roles";
 
-    pub fn new(name: String) -> RoleResult<Self> {
+    pub fn new(name: String, permissions: Vec<Permission>, parents: Vec<Uuid>) -> RoleResult<Self> {
         Ok(Self {
             id: Uuid::new(),
             name,
             system: false,
+            permissions,
+            parents,
             created_at: DateTime::now(),
         })
     }
@@ -132,10 +176,102 @@ impl Role {
             id,
             name,
             system: true,
+            permissions: Vec::new(),
+            parents: Vec::new(),
             created_at: DateTime::now(),
         })
     }
 
+    /// Resolves the permissions granted by the given roles and everything they inherit from.
+    /// The admin role always grants every permission, so existing admin roles keep working
+    /// without storing them.
+    #[allow(unused)]
+    pub async fn get_permissions(
+        role_ids: &[Uuid],
+        db: &Database,
+    ) -> RoleResult<HashSet<Permission>> {
+        if role_ids.contains(&ADMIN_ROLE_ID) {
+            return Ok(HashSet::from(Permission::ALL));
+        }
+
+        let roles = Self::get_with_ancestors(role_ids, db).await?;
+        if roles.iter().any(|role| role.id == *ADMIN_ROLE_ID) {
+            return Ok(HashSet::from(Permission::ALL));
+        }
+
+        Ok(roles
+            .into_iter()
+            .flat_map(|role| role.permissions)
+            .collect())
+    }
+
+    /// Returns the given roles together with every role they inherit from, each role only once
+    #[allow(unused)]
+    pub async fn get_with_ancestors(role_ids: &[Uuid], db: &Database) -> RoleResult<Vec<Role>> {
+        let db: Collection<Role> = db.collection(Self::COLLECTION_NAME);
+
+        let mut visited = HashSet::new();
+        let mut roles = Vec::new();
+        let mut pending = role_ids.to_vec();
+
+        // One query per level of the hierarchy, already visited roles are skipped so a cycle can't loop
+        loop {
+            pending.retain(|id| visited.insert(*id));
+            if pending.is_empty() {
+                break;
+            }
+
+            let filter = doc! {
+                "_id": {
+                    "$in": &pending
+                }
+            };
+            let level = match db.find(filter, None).await {
+                Ok(cursor) => {
+                    cursor
+                        .filter_map(|role| async { role.ok() })
+                        .collect::<Vec<Role>>()
+                        .await
+                }
+                Err(err) => {
+                    return Err(RoleError::DatabaseError(format!(
+                        "Error fetching role hierarchy: {:?}",
+                        err
+                    )))
+                }
+            };
+
+            pending = level
+                .iter()
+                .flat_map(|role| role.parents.iter().copied())
+                .collect();
+            roles.extend(level);
+        }
+
+        Ok(roles)
+    }
+
+    /// Checks that the given roles can be used as parents of this role. System roles can't be
+    /// inherited from and a role may not end up among its own ancestors.
+    #[allow(unused)]
+    pub async fn validate_parents(&self, parents: &[Uuid], db: &Database) -> RoleResult<()> {
+        let ancestors = Self::get_with_ancestors(parents, db).await?;
+
+        for parent in parents {
+            match ancestors.iter().find(|role| role.id == *parent) {
+                Some(role) if role.system => return Err(RoleError::InvalidParent(*parent)),
+                Some(_) => (),
+                None => return Err(RoleError::NotFound(*parent)),
+            }
+        }
+
+        if ancestors.iter().any(|role| role.id == self.id) {
+            return Err(RoleError::CyclicInheritance(self.id));
+        }
+
+        Ok(())
+    }
+
     #[allow(unused)]
     pub async fn get_by_id(id: Uuid, connection: &Connection<AuthRsDatabase>) -> RoleResult<Role> {
         let db = Self::get_collection(connection);
@@ -192,6 +328,31 @@ impl Role {
         }
     }
 
+    /// One page of the roles matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> RoleResult<Page<Role>> {
+        let db = Self::get_collection(connection);
+
+        pagination
+            .find(&db, filter)
+            .await
+            .map_err(|err| RoleError::DatabaseError(format!("Error fetching roles: {:?}", err)))
+    }
+
+    pub async fn count(
+        filter: Document,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> RoleResult<u64> {
+        let db = Self::get_collection(connection);
+
+        db.count_documents(filter, None)
+            .await
+            .map_err(|err| RoleError::DatabaseError(format!("Error counting roles: {:?}", err)))
+    }
+
     #[allow(unused)]
     pub async fn insert(&self, connection: &Connection<AuthRsDatabase>) -> RoleResult<Role> {
         let db = Self::get_collection(connection);
@@ -240,6 +401,9 @@ impl Role {
             .await
             .map_err(|err| RoleError::DatabaseError(err.to_string()))?;
 
+        // Child roles inherit from this role's parents instead
+        self.remove_parent_from_all(connection).await?;
+
         let filter = doc! {
             "_id": self.id
         };
@@ -252,6 +416,47 @@ impl Role {
         }
     }
 
+    #[allow(unused)]
+    async fn remove_parent_from_all(
+        &self,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> RoleResult<()> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "parents": self.id
+        };
+
+        if !self.parents.is_empty() {
+            let update = doc! {
+                "$addToSet": {
+                    "parents": {
+                        "$each": &self.parents
+                    }
+                }
+            };
+            if let Err(err) = db.update_many(filter.clone(), update, None).await {
+                return Err(RoleError::DatabaseError(format!(
+                    "Error re-parenting child roles: {:?}",
+                    err
+                )));
+            }
+        }
+
+        let update = doc! {
+            "$pull": {
+                "parents": self.id
+            }
+        };
+        match db.update_many(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(RoleError::DatabaseError(format!(
+                "Error removing parent role from all roles: {:?}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     fn get_collection(connection: &Connection<AuthRsDatabase>) -> Collection<Self> {
         let db = get_main_db(connection);
@@ -0,0 +1,354 @@
+use super::http_response::HttpResponse;
+use crate::auth::client_info::ClientInfo;
+use crate::db::{get_main_db, AuthRsDatabase};
+use crate::utils::hash_token::hash_token;
+use crate::utils::pagination::{Page, Pagination};
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use mongodb::bson::{doc, DateTime, Document, Uuid};
+use rand::Rng;
+use rocket::{
+    futures::StreamExt,
+    serde::{Deserialize, Serialize},
+};
+use rocket_db_pools::{
+    mongodb::{
+        options::{FindOptions, IndexOptions},
+        Collection, Database, IndexModel,
+    },
+    Connection,
+};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[allow(unused)]
+pub enum SessionError {
+    #[error("Session not found")]
+    NotFound,
+
+    #[error("Session expired")]
+    Expired,
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl<T> From<SessionError> for HttpResponse<T> {
+    fn from(error: SessionError) -> Self {
+        match error {
+            SessionError::NotFound => HttpResponse::not_found("Session not found"),
+            SessionError::Expired => HttpResponse::unauthorized("Session expired"),
+            SessionError::DatabaseError(msg) => {
+                HttpResponse::internal_error(&format!("Database error: {}", msg))
+            }
+        }
+    }
+}
+
+pub type SessionResult<T> = Result<T, SessionError>;
+
+/// A login of a user on one device. Only the hash of the token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub last_seen_at: DateTime,
+    // Moves forward with every use but never past the absolute expiry, the TTL index removes the session afterwards
+    pub idle_expires_at: DateTime,
+    pub expires_at: DateTime,
+    pub created_at: DateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDTO {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub last_seen_at: DateTime,
+    pub expires_at: DateTime,
+    pub created_at: DateTime,
+    pub current: bool,
+}
+
+impl Session {
+    pub const COLLECTION_NAME: &'static str = "sessions";
+
+    pub const SESSION_IDLE_TIMEOUT: u64 = 7 * 24 * 60 * 60;
+    pub const SESSION_LIFETIME: u64 = 30 * 24 * 60 * 60;
+
+    // Avoids a write on every request, the last seen time is only refreshed once a minute
+    const LAST_SEEN_PRECISION: u64 = 60;
+
+    fn generate_token() -> String {
+        let mut rng = rand::rng();
+        let mut buffer = [0u8; 64]; // 512 bits of randomness
+        rng.fill(&mut buffer);
+
+        general_purpose::STANDARD.encode(buffer)
+    }
+
+    fn offset(time: DateTime, seconds: u64) -> DateTime {
+        DateTime::from_millis(time.timestamp_millis() + (seconds * 1000) as i64)
+    }
+
+    /// Returns the new session together with its plain token, which is only known at this point
+    pub fn new(user_id: Uuid, client_info: &ClientInfo) -> (Self, String) {
+        let token = Self::generate_token();
+        let now = DateTime::now();
+
+        (
+            Self {
+                id: Uuid::new(),
+                user_id,
+                token_hash: hash_token(&token),
+                ip_address: client_info.ip_address.clone(),
+                user_agent: client_info.user_agent.clone(),
+                last_seen_at: now,
+                idle_expires_at: Self::offset(now, Self::SESSION_IDLE_TIMEOUT),
+                expires_at: Self::offset(now, Self::SESSION_LIFETIME),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    pub fn to_dto(&self, current_session_id: Option<Uuid>) -> SessionDTO {
+        SessionDTO {
+            id: self.id,
+            ip_address: self.ip_address.clone(),
+            user_agent: self.user_agent.clone(),
+            last_seen_at: self.last_seen_at,
+            expires_at: self.expires_at,
+            created_at: self.created_at,
+            current: current_session_id == Some(self.id),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        let now = DateTime::now().timestamp_millis();
+        self.idle_expires_at.timestamp_millis() <= now || self.expires_at.timestamp_millis() <= now
+    }
+
+    /// Creates the indexes for the token lookup and the automatic expiry
+    pub async fn initialize(db: &Collection<Session>) -> SessionResult<()> {
+        let indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "tokenHash": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder().keys(doc! { "userId": 1 }).build(),
+            IndexModel::builder()
+                .keys(doc! { "idleExpiresAt": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        ];
+
+        match db.create_indexes(indexes, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error creating session indexes: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn insert(&self, connection: &Connection<AuthRsDatabase>) -> SessionResult<Session> {
+        let db = Self::get_collection(connection);
+
+        match db.insert_one(self.clone(), None).await {
+            Ok(_) => Ok(self.clone()),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error inserting session: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// Used by the request guards, which only have access to the database itself
+    #[allow(unused)]
+    pub async fn get_by_token(token: &str, db: &Database) -> SessionResult<Session> {
+        let db: Collection<Session> = db.collection(Self::COLLECTION_NAME);
+
+        let filter = doc! {
+            "tokenHash": hash_token(token)
+        };
+        match db.find_one(filter, None).await {
+            Ok(Some(session)) => {
+                if session.is_expired() {
+                    Err(SessionError::Expired)
+                } else {
+                    Ok(session)
+                }
+            }
+            Ok(None) => Err(SessionError::NotFound),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error finding session: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn get_by_id(
+        id: Uuid,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SessionResult<Session> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": id
+        };
+        match db.find_one(filter, None).await {
+            Ok(Some(session)) => Ok(session),
+            Ok(None) => Err(SessionError::NotFound),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error finding session: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn get_by_user_id(
+        user_id: Uuid,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SessionResult<Vec<Session>> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "userId": user_id
+        };
+        let options = FindOptions::builder()
+            .sort(doc! { "lastSeenAt": -1 })
+            .build();
+        match db.find(filter, options).await {
+            Ok(cursor) => {
+                let sessions = cursor
+                    .filter_map(|session| async { session.ok() })
+                    .filter(|session| {
+                        let active = !session.is_expired();
+                        async move { active }
+                    })
+                    .collect::<Vec<Session>>()
+                    .await;
+                Ok(sessions)
+            }
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error finding sessions: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// One page of the user's active sessions, see `PageQuery` for the parameters
+    pub async fn get_page_by_user_id(
+        user_id: Uuid,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SessionResult<Page<Session>> {
+        let db = Self::get_collection(connection);
+
+        // Expired sessions wait for the TTL index, they are left out like in `is_expired`
+        let now = DateTime::now();
+        let filter = doc! {
+            "userId": user_id,
+            "idleExpiresAt": { "$gt": now },
+            "expiresAt": { "$gt": now }
+        };
+        pagination.find(&db, filter).await.map_err(|err| {
+            SessionError::DatabaseError(format!("Error finding sessions: {:?}", err))
+        })
+    }
+
+    /// Refreshes the last seen time and pushes the idle expiry forward
+    #[allow(unused)]
+    pub async fn touch(&mut self, db: &Database) -> SessionResult<()> {
+        let now = DateTime::now();
+        if now.timestamp_millis() - self.last_seen_at.timestamp_millis()
+            < (Self::LAST_SEEN_PRECISION * 1000) as i64
+        {
+            return Ok(());
+        }
+
+        let idle_expires_at = Self::offset(now, Self::SESSION_IDLE_TIMEOUT);
+        self.last_seen_at = now;
+        self.idle_expires_at =
+            if idle_expires_at.timestamp_millis() < self.expires_at.timestamp_millis() {
+                idle_expires_at
+            } else {
+                self.expires_at
+            };
+
+        let db: Collection<Session> = db.collection(Self::COLLECTION_NAME);
+        let filter = doc! {
+            "_id": self.id
+        };
+        let update = doc! {
+            "$set": {
+                "lastSeenAt": self.last_seen_at,
+                "idleExpiresAt": self.idle_expires_at
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error updating session: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn delete(&self, connection: &Connection<AuthRsDatabase>) -> SessionResult<Session> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id
+        };
+        match db.delete_one(filter, None).await {
+            Ok(_) => Ok(self.clone()),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error deleting session: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// Returns the number of deleted sessions
+    #[allow(unused)]
+    pub async fn delete_all_matching(
+        filter: Document,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SessionResult<u64> {
+        let db = Self::get_collection(connection);
+
+        match db.delete_many(filter, None).await {
+            Ok(result) => Ok(result.deleted_count),
+            Err(err) => Err(SessionError::DatabaseError(format!(
+                "Error deleting sessions: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    fn get_collection(connection: &Connection<AuthRsDatabase>) -> Collection<Self> {
+        let db = get_main_db(connection);
+        db.collection(Self::COLLECTION_NAME)
+    }
+}
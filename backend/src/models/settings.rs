@@ -8,7 +8,9 @@ use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::{mongodb::Collection, Connection};
 use thiserror::Error;
 
-use super::http_response::HttpResponse;
+use super::{
+    http_response::HttpResponse, password_hashing::PasswordHashing, password_policy::PasswordPolicy,
+};
 
 #[derive(Error, Debug)]
 #[allow(unused)]
@@ -19,6 +21,9 @@ pub enum SettingsError {
     #[error("Forbidden: {0}")]
     Forbidden(String),
 
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+
     #[error("Database error: {0}")]
     DatabaseError(String),
 
@@ -33,6 +38,7 @@ impl SettingsError {
         match self {
             SettingsError::Unauthorized(msg) => format!("Unauthorized: {}", msg),
             SettingsError::Forbidden(msg) => format!("Forbidden: {}", msg),
+            SettingsError::InvalidData(msg) => format!("Invalid data: {}", msg),
             SettingsError::DatabaseError(msg) => format!("Database error: {}", msg),
             SettingsError::InternalServerError(msg) => format!("Internal server error: {}", msg),
         }
@@ -53,6 +59,11 @@ impl<T> From<SettingsError> for HttpResponse<T> {
                 message: format!("Forbidden: {}", msg),
                 data: None,
             },
+            SettingsError::InvalidData(msg) => HttpResponse {
+                status: 400,
+                message: format!("Invalid data: {}", msg),
+                data: None,
+            },
             SettingsError::DatabaseError(msg) => HttpResponse {
                 status: 500,
                 message: format!("Database error: {}", msg),
@@ -94,6 +105,20 @@ pub struct Settings {
     pub id: Uuid,
     pub open_registration: bool,
     pub allow_oauth_apps_for_users: bool,
+    // Members of these roles (or roles inheriting from them) have to set up MFA
+    #[serde(default)]
+    pub mfa_required_roles: Vec<Uuid>,
+    // How long affected users can still log in without MFA before enrollment is forced
+    #[serde(default = "Settings::default_mfa_grace_period_days")]
+    pub mfa_grace_period_days: u32,
+    // Users can't log in or authorize OAuth applications until their email is verified
+    #[serde(default)]
+    pub require_email_verification: bool,
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    // Argon2id parameters for new hashes, outdated hashes are upgraded on login
+    #[serde(default)]
+    pub password_hashing: PasswordHashing,
 }
 
 impl Default for Settings {
@@ -102,6 +127,11 @@ impl Default for Settings {
             id: *SETTINGS_ID,
             open_registration: true,
             allow_oauth_apps_for_users: true,
+            mfa_required_roles: Vec::new(),
+            mfa_grace_period_days: Self::default_mfa_grace_period_days(),
+            require_email_verification: false,
+            password_policy: PasswordPolicy::default(),
+            password_hashing: PasswordHashing::default(),
         }
     }
 }
@@ -109,6 +139,10 @@ impl Default for Settings {
 impl Settings {
     pub const COLLECTION_NAME: &'static str = "settings";
 
+    fn default_mfa_grace_period_days() -> u32 {
+        7
+    }
+
     pub async fn initialize(db: &Collection<Settings>) -> SettingsResult<()> {
         let settings = Settings::default();
 
@@ -0,0 +1,333 @@
+use crate::{
+    auth::oidc::ID_TOKEN_LIFETIME,
+    db::{get_main_db, AuthRsDatabase},
+    utils::pagination::{Page, Pagination},
+};
+use anyhow::Result;
+use argon2::password_hash::rand_core::OsRng;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use mongodb::bson::{doc, DateTime, Document, Uuid};
+use rocket::{
+    futures::StreamExt,
+    serde::{Deserialize, Serialize},
+};
+use rocket_db_pools::{
+    mongodb::{options::FindOneOptions, Collection},
+    Connection,
+};
+use rsa::{
+    pkcs8::{EncodePrivateKey, LineEnding},
+    traits::PublicKeyParts,
+    RsaPrivateKey,
+};
+use thiserror::Error;
+
+use super::http_response::HttpResponse;
+
+#[derive(Error, Debug)]
+#[allow(unused)]
+pub enum SigningKeyError {
+    #[error("No active signing key found")]
+    NoActiveKey,
+
+    #[error("Key generation error: {0}")]
+    KeyGenerationError(String),
+
+    #[error("Signing error: {0}")]
+    SigningError(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+// Implement conversion from SigningKeyError to HttpResponse
+impl<T> From<SigningKeyError> for HttpResponse<T> {
+    fn from(error: SigningKeyError) -> Self {
+        match error {
+            SigningKeyError::NoActiveKey => {
+                HttpResponse::internal_error("No active signing key found")
+            }
+            SigningKeyError::KeyGenerationError(msg) => {
+                HttpResponse::internal_error(&format!("Key generation error: {}", msg))
+            }
+            SigningKeyError::SigningError(msg) => {
+                HttpResponse::internal_error(&format!("Signing error: {}", msg))
+            }
+            SigningKeyError::Forbidden(msg) => HttpResponse::forbidden(&msg),
+            SigningKeyError::DatabaseError(msg) => {
+                HttpResponse::internal_error(&format!("Database error: {}", msg))
+            }
+        }
+    }
+}
+
+// Define a Result type alias for signing key operations
+pub type SigningKeyResult<T> = Result<T, SigningKeyError>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum SigningKeyStatus {
+    Active,
+    Retired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct SigningKey {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub algorithm: String,
+    pub private_key: String,
+    pub modulus: String,
+    pub exponent: String,
+    pub status: SigningKeyStatus,
+    pub created_at: DateTime,
+    pub retired_at: Option<DateTime>,
+    // Retired keys stay published until every token they signed has expired
+    pub expires_at: Option<DateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct SigningKeyDTO {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub algorithm: String,
+    pub status: SigningKeyStatus,
+    pub created_at: DateTime,
+    pub retired_at: Option<DateTime>,
+    pub expires_at: Option<DateTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub r#use: String,
+    pub alg: String,
+    pub kid: String,
+    pub n: String,
+    pub e: String,
+}
+
+impl SigningKey {
+    pub const COLLECTION_NAME: &'static str = "signing-keys";
+
+    // The longest lifetime of any token signed with these keys
+    pub const MAX_SIGNED_TOKEN_LIFETIME: u64 = ID_TOKEN_LIFETIME;
+
+    pub fn generate() -> SigningKeyResult<Self> {
+        let private_key = RsaPrivateKey::new(&mut OsRng, 2048)
+            .map_err(|err| SigningKeyError::KeyGenerationError(err.to_string()))?;
+        let pem = private_key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|err| SigningKeyError::KeyGenerationError(err.to_string()))?;
+
+        Ok(Self {
+            id: Uuid::new(),
+            algorithm: "RS256".to_string(),
+            private_key: pem.to_string(),
+            modulus: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+            exponent: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+            status: SigningKeyStatus::Active,
+            created_at: DateTime::now(),
+            retired_at: None,
+            expires_at: None,
+        })
+    }
+
+    pub fn to_dto(&self) -> SigningKeyDTO {
+        SigningKeyDTO {
+            id: self.id,
+            algorithm: self.algorithm.clone(),
+            status: self.status.clone(),
+            created_at: self.created_at,
+            retired_at: self.retired_at,
+            expires_at: self.expires_at,
+        }
+    }
+
+    pub fn to_jwk(&self) -> Jwk {
+        Jwk {
+            kty: "RSA".to_string(),
+            r#use: "sig".to_string(),
+            alg: self.algorithm.clone(),
+            kid: self.id.to_string(),
+            n: self.modulus.clone(),
+            e: self.exponent.clone(),
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> SigningKeyResult<String> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.id.to_string());
+
+        let key = EncodingKey::from_rsa_pem(self.private_key.as_bytes())
+            .map_err(|err| SigningKeyError::SigningError(err.to_string()))?;
+
+        encode(&header, claims, &key).map_err(|err| SigningKeyError::SigningError(err.to_string()))
+    }
+
+    /// Generates the first signing key if there is no active one yet
+    pub async fn initialize(db: &Collection<SigningKey>) -> SigningKeyResult<()> {
+        let active_keys = db
+            .count_documents(doc! { "status": "Active" }, None)
+            .await
+            .map_err(|err| SigningKeyError::DatabaseError(err.to_string()))?;
+
+        if active_keys > 0 {
+            return Ok(());
+        }
+
+        let signing_key = Self::generate()?;
+
+        match db.insert_one(signing_key.clone(), None).await {
+            Ok(_) => {
+                tracing::info!(kid = %signing_key.id, "Generated initial signing key");
+                Ok(())
+            }
+            Err(err) => Err(SigningKeyError::DatabaseError(format!(
+                "Error initializing signing key: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn get_active(connection: &Connection<AuthRsDatabase>) -> SigningKeyResult<Self> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "status": "Active"
+        };
+        let options = FindOneOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .build();
+        match db.find_one(filter, options).await {
+            Ok(Some(signing_key)) => Ok(signing_key),
+            Ok(None) => Err(SigningKeyError::NoActiveKey),
+            Err(err) => Err(SigningKeyError::DatabaseError(err.to_string())),
+        }
+    }
+
+    /// All keys relying parties may still need to verify tokens with
+    #[allow(unused)]
+    pub async fn get_published(
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SigningKeyResult<Vec<Self>> {
+        let filter = doc! {
+            "$or": [
+                { "status": "Active" },
+                { "status": "Retired", "expiresAt": { "$gt": DateTime::now() } }
+            ]
+        };
+
+        Self::find_all(filter, connection).await
+    }
+
+    #[allow(unused)]
+    pub async fn get_all(connection: &Connection<AuthRsDatabase>) -> SigningKeyResult<Vec<Self>> {
+        Self::find_all(doc! {}, connection).await
+    }
+
+    /// One page of the signing keys matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SigningKeyResult<Page<SigningKey>> {
+        let db = Self::get_collection(connection);
+
+        pagination.find(&db, filter).await.map_err(|err| {
+            SigningKeyError::DatabaseError(format!("Error fetching signing keys: {:?}", err))
+        })
+    }
+
+    /// Creates a new active key, retires the previous ones and removes retired keys
+    /// whose tokens have all expired
+    #[allow(unused)]
+    pub async fn rotate(connection: &Connection<AuthRsDatabase>) -> SigningKeyResult<Self> {
+        let db = Self::get_collection(connection);
+
+        let signing_key = Self::generate()?;
+
+        db.insert_one(signing_key.clone(), None)
+            .await
+            .map_err(|err| {
+                SigningKeyError::DatabaseError(format!("Error inserting signing key: {:?}", err))
+            })?;
+
+        let now = DateTime::now();
+        let expires_at = DateTime::from_millis(
+            now.timestamp_millis() + (Self::MAX_SIGNED_TOKEN_LIFETIME * 1000) as i64,
+        );
+
+        let filter = doc! {
+            "status": "Active",
+            "_id": { "$ne": signing_key.id }
+        };
+        let update = doc! {
+            "$set": {
+                "status": "Retired",
+                "retiredAt": now,
+                "expiresAt": expires_at
+            }
+        };
+        db.update_many(filter, update, None).await.map_err(|err| {
+            SigningKeyError::DatabaseError(format!("Error retiring signing keys: {:?}", err))
+        })?;
+
+        let filter = doc! {
+            "status": "Retired",
+            "expiresAt": { "$lte": now }
+        };
+        db.delete_many(filter, None).await.map_err(|err| {
+            SigningKeyError::DatabaseError(format!(
+                "Error deleting expired signing keys: {:?}",
+                err
+            ))
+        })?;
+
+        Ok(signing_key)
+    }
+
+    async fn find_all(
+        filter: mongodb::bson::Document,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> SigningKeyResult<Vec<Self>> {
+        let db = Self::get_collection(connection);
+
+        match db.find(filter, None).await {
+            Ok(cursor) => {
+                let mut signing_keys = Vec::new();
+                let mut stream = cursor;
+
+                while let Some(result) = stream.next().await {
+                    match result {
+                        Ok(signing_key) => signing_keys.push(signing_key),
+                        Err(err) => return Err(SigningKeyError::DatabaseError(err.to_string())),
+                    }
+                }
+
+                Ok(signing_keys)
+            }
+            Err(err) => Err(SigningKeyError::DatabaseError(format!(
+                "Error fetching signing keys: {:?}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    fn get_collection(connection: &Connection<AuthRsDatabase>) -> Collection<Self> {
+        let db = get_main_db(connection);
+        db.collection(Self::COLLECTION_NAME)
+    }
+}
@@ -1,26 +1,27 @@
 use super::user_error::{UserError, UserResult};
 use super::{
     http_response::HttpResponse, oauth_application::OAuthApplication, oauth_token::OAuthToken,
+    password_hashing::PasswordHashing, session::Session,
 };
 use crate::{
     db::{get_main_db, AuthRsDatabase},
-    ADMIN_ROLE_ID, DEFAULT_ROLE_ID, SYSTEM_USER_ID,
+    utils::pagination::{Page, Pagination},
+    DEFAULT_ROLE_ID, SETTINGS, SYSTEM_USER_ID,
 };
 use anyhow::Result;
-use argon2::password_hash::rand_core::OsRng;
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use base64::{engine::general_purpose, Engine as _};
-use mongodb::bson::{doc, DateTime, Uuid};
-use rand::Rng;
+use mongodb::bson::{doc, DateTime, Document, Uuid};
 use rocket::{
     futures::StreamExt,
     serde::{Deserialize, Serialize},
 };
 use rocket_db_pools::{
-    mongodb::{Collection, Database},
+    mongodb::{
+        options::{FindOneAndUpdateOptions, InsertManyOptions, ReturnDocument},
+        Collection, Cursor, Database,
+    },
     Connection,
 };
+use std::collections::HashSet;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -29,12 +30,28 @@ pub struct User {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub email: String,
+    // Accounts created before verification existed are treated as verified
+    #[serde(default = "User::default_email_verified")]
+    pub email_verified: bool,
     pub first_name: String,
     pub last_name: String,
+    // Argon2id PHC string, imported users can also have bcrypt, scrypt or PBKDF2 hashes
     pub password_hash: String,
-    pub salt: String,
     pub totp_secret: Option<String>,
-    pub token: String,
+    // Argon2 hashes of the unused recovery codes
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    // Time step of the last accepted TOTP code, codes of this or an earlier step are rejected
+    #[serde(default)]
+    pub last_totp_step: Option<i64>,
+    // When the MFA policy first applied to the user without MFA, the grace period starts here
+    #[serde(default)]
+    pub mfa_required_since: Option<DateTime>,
+    // Failed login and MFA attempts since the last successful login
+    #[serde(default)]
+    pub failed_login_attempts: u32,
+    #[serde(default)]
+    pub locked_until: Option<DateTime>,
     pub roles: Vec<Uuid>,
     pub disabled: bool,
     pub created_at: DateTime,
@@ -47,10 +64,13 @@ pub struct UserDTO {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub email: String,
+    pub email_verified: bool,
     pub first_name: String,
     pub last_name: String,
     pub roles: Vec<Uuid>,
     pub mfa: bool,
+    pub recovery_codes_remaining: usize,
+    pub locked_until: Option<DateTime>,
     pub disabled: bool,
     pub created_at: DateTime,
 }
@@ -58,32 +78,80 @@ pub struct UserDTO {
 impl User {
     pub const COLLECTION_NAME: &'static str = "users";
 
-    pub fn generate_token() -> String {
-        // Generate a more secure token using a cryptographically secure RNG
-        let mut rng = rand::rng();
-        let mut buffer = [0u8; 64]; // 512 bits of randomness
-        rng.fill(&mut buffer);
-
-        // Convert to base64 for string representation
-        general_purpose::STANDARD.encode(buffer)
+    fn default_email_verified() -> bool {
+        true
     }
 
     pub fn verify_password(&self, password: &str) -> Result<(), UserError> {
-        let hash =
-            PasswordHash::new(&self.password_hash).map_err(|_| UserError::PasswordHashingError)?;
-        Argon2::default()
-            .verify_password(password.as_bytes(), &hash)
-            .map_err(|_| UserError::PasswordHashingError)
+        PasswordHashing::verify(password, &self.password_hash)
+    }
+
+    /// Re-hashes a verified password if its hash is outdated, see `PasswordHashing::needs_rehash`
+    pub async fn upgrade_password_hash(
+        &mut self,
+        password: &str,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<bool> {
+        let hashing = SETTINGS.lock().await.password_hashing.clone();
+        if !hashing.needs_rehash(&self.password_hash) {
+            return Ok(false);
+        }
+
+        let password_hash = hashing.hash(password)?;
+        let db = Self::get_collection(connection);
+
+        // Only replaces the hash it verified, a concurrent password change wins
+        let filter = doc! {
+            "_id": self.id,
+            "passwordHash": self.password_hash.as_str()
+        };
+        let update = doc! {
+            "$set": {
+                "passwordHash": password_hash.as_str()
+            },
+            "$unset": {
+                "salt": ""
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(result) if result.modified_count == 1 => {
+                self.password_hash = password_hash;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error upgrading password hash: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Checks a new password against the password policy from the settings
+    pub async fn validate_new_password(&self, password: &str) -> UserResult<()> {
+        let policy = SETTINGS.lock().await.password_policy.clone();
+        policy
+            .validate(password, &[&self.email, &self.first_name, &self.last_name])
+            .await
+    }
+
+    /// Replaces the password with a fresh hash, see `validate_new_password` for the policy
+    pub async fn set_password(&mut self, password: &str) -> UserResult<()> {
+        let hashing = SETTINGS.lock().await.password_hashing.clone();
+        self.password_hash = hashing.hash(password)?;
+        Ok(())
     }
 
     pub fn to_dto(&self) -> UserDTO {
         UserDTO {
             id: self.id,
             email: self.email.clone(),
+            email_verified: self.email_verified,
             first_name: self.first_name.clone(),
             last_name: self.last_name.clone(),
             roles: self.roles.clone(),
             mfa: self.totp_secret.is_some(),
+            recovery_codes_remaining: self.recovery_codes.len(),
+            locked_until: self.locked_until.filter(|_| self.is_locked()),
             disabled: self.disabled,
             created_at: self.created_at,
         }
@@ -94,27 +162,42 @@ impl User {
         password: String,
         first_name: String,
         last_name: String,
+        hashing: &PasswordHashing,
     ) -> UserResult<Self> {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|_| UserError::PasswordHashingError)?
-            .to_string();
+        let password_hash = hashing.hash(&password)?;
 
-        Ok(Self {
+        Ok(Self::with_password_hash(
+            email,
+            password_hash,
+            first_name,
+            last_name,
+        ))
+    }
+
+    /// Creates a user from an existing hash, see `PasswordHashing::verify` for the supported formats
+    pub fn with_password_hash(
+        email: String,
+        password_hash: String,
+        first_name: String,
+        last_name: String,
+    ) -> Self {
+        Self {
             id: Uuid::new(),
             email,
+            email_verified: false,
             first_name,
             last_name,
             password_hash,
-            salt: salt.as_str().to_string(),
             totp_secret: None,
-            token: Self::generate_token(),
+            recovery_codes: Vec::new(),
+            last_totp_step: None,
+            mfa_required_since: None,
+            failed_login_attempts: 0,
+            locked_until: None,
             roles: Vec::from([*DEFAULT_ROLE_ID]),
             disabled: false,
             created_at: DateTime::now(),
-        })
+        }
     }
 
     pub fn new_system(
@@ -124,23 +207,23 @@ impl User {
         first_name: String,
         last_name: String,
         roles: Vec<String>,
+        hashing: &PasswordHashing,
     ) -> UserResult<Self> {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|_| UserError::PasswordHashingError)?
-            .to_string();
+        let password_hash = hashing.hash(&password)?;
 
         Ok(Self {
             id,
             email,
+            email_verified: true,
             first_name,
             last_name,
             password_hash,
-            salt: salt.as_str().to_string(),
             totp_secret: None,
-            token: Self::generate_token(),
+            recovery_codes: Vec::new(),
+            last_totp_step: None,
+            mfa_required_since: None,
+            failed_login_attempts: 0,
+            locked_until: None,
             roles: roles
                 .iter()
                 .map(|role| Uuid::parse_str(role).unwrap())
@@ -150,9 +233,9 @@ impl User {
         })
     }
 
-    #[allow(unused)]
-    pub fn is_admin(&self) -> bool {
-        self.id == *SYSTEM_USER_ID || self.roles.contains(&*ADMIN_ROLE_ID)
+    pub fn is_locked(&self) -> bool {
+        self.locked_until
+            .is_some_and(|locked_until| locked_until > DateTime::now())
     }
 
     #[allow(unused)]
@@ -178,16 +261,15 @@ impl User {
     }
 
     #[allow(unused)]
-    pub async fn get_full_by_token(token: String, mut db: &Database) -> UserResult<User> {
+    pub async fn get_full_by_session(session: &Session, db: &Database) -> UserResult<User> {
         let db = db.collection(Self::COLLECTION_NAME);
 
         let filter = doc! {
-            "token": token
+            "_id": session.user_id
         };
         match db.find_one(filter, None).await {
             Ok(Some(user)) => Ok(user),
-            //TODO: Not sure if we should return a placeholder UUID here
-            Ok(None) => Err(UserError::NotFound(Uuid::new())), // Using a placeholder UUID
+            Ok(None) => Err(UserError::NotFound(session.user_id)),
             Err(err) => Err(UserError::DatabaseError(err.to_string())),
         }
     }
@@ -223,6 +305,44 @@ impl User {
         }
     }
 
+    /// Returns which of the given (lowercase) emails already belong to a user
+    pub async fn get_existing_emails(
+        emails: &[String],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<HashSet<String>> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "email": { "$in": emails }
+        };
+        match db.find(filter, None).await {
+            Ok(cursor) => Ok(cursor
+                .filter_map(|user| async move { user.ok().map(|user| user.email) })
+                .collect::<HashSet<String>>()
+                .await),
+            Err(err) => Err(UserError::DatabaseError(err.to_string())),
+        }
+    }
+
+    /// Returns which of the given ids belong to a user
+    pub async fn get_existing_ids(
+        ids: &[Uuid],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<HashSet<Uuid>> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": { "$in": ids }
+        };
+        match db.find(filter, None).await {
+            Ok(cursor) => Ok(cursor
+                .filter_map(|user| async move { user.ok().map(|user| user.id) })
+                .collect::<HashSet<Uuid>>()
+                .await),
+            Err(err) => Err(UserError::DatabaseError(err.to_string())),
+        }
+    }
+
     #[allow(unused)]
     pub async fn get_all(connection: &Connection<AuthRsDatabase>) -> UserResult<Vec<User>> {
         let db = Self::get_collection(connection);
@@ -242,6 +362,61 @@ impl User {
         }
     }
 
+    /// Cursor over all users, for exports that shouldn't load every user into memory
+    pub async fn get_cursor(connection: &Connection<AuthRsDatabase>) -> UserResult<Cursor<User>> {
+        let db = Self::get_collection(connection);
+
+        db.find(None, None)
+            .await
+            .map_err(|err| UserError::DatabaseError(err.to_string()))
+    }
+
+    /// One page of the users matching the filter, see `PageQuery` for the parameters
+    pub async fn get_page(
+        filter: Document,
+        pagination: &Pagination,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<Page<User>> {
+        let db = Self::get_collection(connection);
+
+        pagination
+            .find(&db, filter)
+            .await
+            .map_err(|err| UserError::DatabaseError(err.to_string()))
+    }
+
+    pub async fn count(
+        filter: Document,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<u64> {
+        let db = Self::get_collection(connection);
+
+        db.count_documents(filter, None)
+            .await
+            .map_err(|err| UserError::DatabaseError(err.to_string()))
+    }
+
+    /// All users that have at least one of the given roles
+    pub async fn get_by_roles(
+        role_ids: &[Uuid],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<Vec<User>> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "roles": {
+                "$in": role_ids
+            }
+        };
+        match db.find(filter, None).await {
+            Ok(cursor) => Ok(cursor
+                .filter_map(|user| async { user.ok() })
+                .collect::<Vec<User>>()
+                .await),
+            Err(err) => Err(UserError::DatabaseError(err.to_string())),
+        }
+    }
+
     #[allow(unused)]
     pub async fn insert(&self, connection: &Connection<AuthRsDatabase>) -> UserResult<User> {
         let db = Self::get_collection(connection);
@@ -255,6 +430,24 @@ impl User {
         }
     }
 
+    /// Unordered, so a failed user doesn't stop the others from being inserted
+    #[allow(unused)]
+    pub async fn insert_many(
+        users: &[User],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<()> {
+        let db = Self::get_collection(connection);
+
+        let options = InsertManyOptions::builder().ordered(false).build();
+        match db.insert_many(users, options).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error inserting users: {}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     pub async fn update(&self, connection: &Connection<AuthRsDatabase>) -> UserResult<User> {
         let db = Self::get_collection(connection);
@@ -271,6 +464,192 @@ impl User {
         }
     }
 
+    /// Removes a used recovery code, returns false if it was already used in the meantime
+    #[allow(unused)]
+    pub async fn remove_recovery_code(
+        &self,
+        hash: &str,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<bool> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id,
+            "recoveryCodes": hash
+        };
+        let update = doc! {
+            "$pull": {
+                "recoveryCodes": hash
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(result) => Ok(result.modified_count == 1),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error removing recovery code: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Records `step` as the last accepted TOTP step.
+    /// Returns false if the same or a later step has already been accepted.
+    pub async fn claim_totp_step(
+        &self,
+        step: i64,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<bool> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id,
+            "$or": [
+                { "lastTotpStep": { "$lt": step } },
+                { "lastTotpStep": null }
+            ]
+        };
+        let update = doc! {
+            "$set": {
+                "lastTotpStep": step
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(result) => Ok(result.modified_count == 1),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error saving TOTP step: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Counts a failed login or MFA attempt and returns the new number of failed attempts
+    pub async fn register_failed_attempt(
+        &self,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<u32> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id
+        };
+        let update = doc! {
+            "$inc": {
+                "failedLoginAttempts": 1
+            }
+        };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        match db.find_one_and_update(filter, update, options).await {
+            Ok(Some(user)) => Ok(user.failed_login_attempts),
+            Ok(None) => Err(UserError::NotFound(self.id)),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error counting failed attempt: {}",
+                err
+            ))),
+        }
+    }
+
+    pub async fn lock(
+        &self,
+        locked_until: DateTime,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<()> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id
+        };
+        let update = doc! {
+            "$set": {
+                "lockedUntil": locked_until
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error locking user: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Clears the failed attempts and any lockout, returns false if there was nothing to clear
+    pub async fn unlock(&self, connection: &Connection<AuthRsDatabase>) -> UserResult<bool> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id,
+            "$or": [
+                { "failedLoginAttempts": { "$gt": 0 } },
+                { "lockedUntil": { "$ne": null } }
+            ]
+        };
+        let update = doc! {
+            "$set": {
+                "failedLoginAttempts": 0,
+                "lockedUntil": null
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(result) => Ok(result.modified_count == 1),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error unlocking user: {}",
+                err
+            ))),
+        }
+    }
+
+    #[allow(unused)]
+    /// Marks the email as verified, returns false if the user's email changed in the meantime
+    pub async fn verify_email(
+        &self,
+        email: &str,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<bool> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id,
+            "email": email
+        };
+        let update = doc! {
+            "$set": {
+                "emailVerified": true
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(result) => Ok(result.matched_count == 1),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error verifying email: {}",
+                err
+            ))),
+        }
+    }
+
+    pub async fn set_mfa_required_since(
+        &self,
+        since: Option<DateTime>,
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<()> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": self.id
+        };
+        let update = doc! {
+            "$set": {
+                "mfaRequiredSince": since
+            }
+        };
+        match db.update_one(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error updating MFA policy state: {}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     pub async fn remove_role_from_all(
         role_id: Uuid,
@@ -297,6 +676,58 @@ impl User {
         }
     }
 
+    pub async fn add_role_to_users(
+        role_id: Uuid,
+        user_ids: &[Uuid],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<()> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": {
+                "$in": user_ids
+            }
+        };
+        let update = doc! {
+            "$addToSet": {
+                "roles": role_id
+            }
+        };
+        match db.update_many(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error adding role to users: {}",
+                err
+            ))),
+        }
+    }
+
+    pub async fn remove_role_from_users(
+        role_id: Uuid,
+        user_ids: &[Uuid],
+        connection: &Connection<AuthRsDatabase>,
+    ) -> UserResult<()> {
+        let db = Self::get_collection(connection);
+
+        let filter = doc! {
+            "_id": {
+                "$in": user_ids
+            }
+        };
+        let update = doc! {
+            "$pull": {
+                "roles": role_id
+            }
+        };
+        match db.update_many(filter, update, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(UserError::DatabaseError(format!(
+                "Error removing role from users: {}",
+                err
+            ))),
+        }
+    }
+
     #[allow(unused)]
     pub async fn disable(
         &self,
@@ -366,6 +797,11 @@ impl User {
             .await
             .map_err(|err| UserError::DatabaseError(err.to_string()))?;
 
+        // Delete all sessions of this user
+        Session::delete_all_matching(doc! { "userId": self.id }, connection)
+            .await
+            .map_err(|err| UserError::DatabaseError(err.to_string()))?;
+
         let filter = doc! {
             "_id": self.id
         };
@@ -49,11 +49,17 @@ pub enum UserError {
     #[error("Password to short")]
     PasswordToShort,
 
+    #[error("Password does not meet the policy: {0}")]
+    PasswordPolicyViolation(String),
+
     #[error("Invalid email")]
     InvalidEmail,
 
     #[error("Registrations are closed")]
     RegistrationClosed,
+
+    #[error("Invalid import: {0}")]
+    InvalidImport(String),
 }
 
 // Implement conversion from UserError to HttpResponse
@@ -135,6 +141,11 @@ impl<T> From<UserError> for HttpResponse<T> {
                 message: "Password to short".to_string(),
                 data: None,
             },
+            UserError::PasswordPolicyViolation(msg) => HttpResponse {
+                status: 400,
+                message: msg,
+                data: None,
+            },
             UserError::InvalidEmail => HttpResponse {
                 status: 400,
                 message: "Invalid email".to_string(),
@@ -145,6 +156,11 @@ impl<T> From<UserError> for HttpResponse<T> {
                 message: "Registrations are closed".to_string(),
                 data: None,
             },
+            UserError::InvalidImport(msg) => HttpResponse {
+                status: 400,
+                message: msg,
+                data: None,
+            },
         }
     }
 }
@@ -1,7 +1,10 @@
+use mongodb::bson::doc;
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -10,16 +13,22 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/audit-logs", format = "json")]
+#[get("/audit-logs?<query..>", format = "json")]
 pub async fn get_all_audit_logs(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<AuditLog>>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<AuditLog>>>) {
+    if !req_entity.has_permission(Permission::AuditLogsRead) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
-    match AuditLog::get_by_user_id(None, &db).await {
+    let pagination = match query.pagination(&["createdAt"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    match AuditLog::get_page(doc! {}, &pagination, &db).await {
         Ok(audit_logs) => json_response(HttpResponse::success("All Audit Logs", audit_logs)),
         Err(err) => json_response(err.into()),
     }
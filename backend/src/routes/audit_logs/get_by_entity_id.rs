@@ -1,7 +1,10 @@
+use mongodb::bson::doc;
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -14,14 +17,15 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/audit-logs/<type>/entity/<id>", format = "json")]
+#[get("/audit-logs/<type>/entity/<id>?<query..>", format = "json")]
 pub async fn get_audit_log_by_entity_id(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
     r#type: &str,
     id: &str,
-) -> (Status, Json<HttpResponse<Vec<AuditLog>>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<AuditLog>>>) {
+    if !req_entity.has_permission(Permission::AuditLogsRead) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
@@ -35,7 +39,16 @@ pub async fn get_audit_log_by_entity_id(
         Err(err) => return json_response(err.into()),
     };
 
-    match AuditLog::get_by_entity_id(entity_uuid, entity_type, &db).await {
+    let pagination = match query.pagination(&["createdAt"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    // Entity ids are stored as strings
+    let filter = doc! {
+        "entityId": entity_uuid.to_string()
+    };
+    match AuditLog::get_page_from_type(entity_type, filter, &pagination, &db).await {
         Ok(audit_log) => json_response(HttpResponse::success(
             "Audit Logs found by entity id",
             audit_log,
@@ -1,7 +1,10 @@
+use mongodb::bson::doc;
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -13,13 +16,14 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/audit-logs/<type>", format = "json")]
+#[get("/audit-logs/<type>?<query..>", format = "json")]
 pub async fn get_audit_logs_by_type(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
     r#type: &str,
-) -> (Status, Json<HttpResponse<Vec<AuditLog>>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<AuditLog>>>) {
+    if !req_entity.has_permission(Permission::AuditLogsRead) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
@@ -28,7 +32,12 @@ pub async fn get_audit_logs_by_type(
         Err(err) => return json_response(err.into()),
     };
 
-    match AuditLog::get_all_from_type(entity_type.unwrap(), &db).await {
+    let pagination = match query.pagination(&["createdAt"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    match AuditLog::get_page_from_type(entity_type.unwrap(), doc! {}, &pagination, &db).await {
         Ok(audit_logs) => json_response(HttpResponse::success(
             "Successfully retrieved all audit logs by type",
             audit_logs,
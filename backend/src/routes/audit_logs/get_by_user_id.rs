@@ -1,7 +1,10 @@
+use mongodb::bson::doc;
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -15,12 +18,13 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/users/<id>/audit-logs", format = "json")]
+#[get("/users/<id>/audit-logs?<query..>", format = "json")]
 pub async fn get_audit_logs_by_user_id(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
     id: &str,
-) -> (Status, Json<HttpResponse<Vec<AuditLog>>>) {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<AuditLog>>>) {
     let user_uuid = match parse_uuid(id) {
         Ok(uuid) => uuid,
         Err(err) => return json_response(err.into()),
@@ -32,12 +36,20 @@ pub async fn get_audit_logs_by_user_id(
             .unwrap()
             .check_scope(OAuthScope::AuditLogs(ScopeActions::Read))
         || (req_entity.user.clone().unwrap().id != user_uuid
-            && !req_entity.user.unwrap().is_admin())
+            && !req_entity.has_permission(Permission::AuditLogsRead))
     {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
-    match AuditLog::get_by_user_id(Some(user_uuid), &db).await {
+    let pagination = match query.pagination(&["createdAt"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let filter = doc! {
+        "authorId": user_uuid
+    };
+    match AuditLog::get_page(filter, &pagination, &db).await {
         Ok(audit_logs) => json_response(HttpResponse::success(
             "Audit Logs found by user id",
             audit_logs,
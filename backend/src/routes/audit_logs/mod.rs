@@ -3,3 +3,4 @@ pub mod get_by_entity_id;
 pub mod get_by_id;
 pub mod get_by_type;
 pub mod get_by_user_id;
+pub mod search;
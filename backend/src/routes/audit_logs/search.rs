@@ -0,0 +1,88 @@
+use mongodb::bson::DateTime;
+use rocket::http::Status;
+use rocket::{get, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType, AuditLogSearch},
+        http_response::HttpResponse,
+    },
+    utils::parse_uuid::parse_uuid,
+};
+
+fn parse_time(name: &str, time: Option<&str>) -> Result<Option<DateTime>, String> {
+    time.map(|time| {
+        DateTime::parse_rfc3339_str(time)
+            .map_err(|_| format!("{} must be an RFC 3339 timestamp", name))
+    })
+    .transpose()
+}
+
+/// Searches the logs of all entity types, `from` is inclusive and `to` exclusive
+#[allow(unused)]
+#[get(
+    "/audit-logs/search?<author>&<entity>&<type>&<action>&<from>&<to>&<query..>",
+    format = "json"
+)]
+pub async fn search_audit_logs(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    author: Option<&str>,
+    entity: Option<&str>,
+    r#type: Option<&str>,
+    action: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<AuditLog>>>) {
+    if !req_entity.has_permission(Permission::AuditLogsRead) {
+        return json_response(HttpResponse::forbidden("Missing permissions!"));
+    }
+
+    let pagination = match query.pagination(&["createdAt"], "-createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let author_id = match author.map(parse_uuid).transpose() {
+        Ok(author_id) => author_id,
+        Err(err) => return json_response(err.into()),
+    };
+    let entity_type = match r#type.map(AuditLogEntityType::from_string).transpose() {
+        Ok(entity_type) => entity_type,
+        Err(err) => return json_response(err.into()),
+    };
+    let action = match action.map(AuditLogAction::from_string).transpose() {
+        Ok(action) => action,
+        Err(err) => return json_response(err.into()),
+    };
+    let (from, to) = match (parse_time("from", from), parse_time("to", to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(err), _) | (_, Err(err)) => return json_response(HttpResponse::bad_request(&err)),
+    };
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return json_response(HttpResponse::bad_request("from must be before to"));
+        }
+    }
+
+    let search = AuditLogSearch {
+        author_id,
+        entity_id: entity.map(str::to_string),
+        entity_type,
+        action,
+        from,
+        to,
+    };
+
+    match AuditLog::search(&search, &pagination, &db).await {
+        Ok(audit_logs) => json_response(HttpResponse::success("Audit Logs found", audit_logs)),
+        Err(err) => json_response(err.into()),
+    }
+}
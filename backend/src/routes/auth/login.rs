@@ -10,10 +10,15 @@ use crate::models::audit_log::{AuditLog, AuditLogAction, AuditLogEntityType};
 use crate::models::user::UserDTO;
 use crate::utils::response::json_response;
 use crate::{
-    auth::mfa::MfaHandler,
+    auth::{
+        client_info::ClientInfo,
+        email_verification, lockout,
+        mfa::{MfaEnrollment, MfaFactor, MfaHandler},
+    },
     db::AuthRsDatabase,
     errors::{ApiError, ApiResult},
-    models::{http_response::HttpResponse, user::User},
+    models::{http_response::HttpResponse, session::Session, user::User},
+    SETTINGS,
 };
 
 #[derive(Deserialize)]
@@ -32,23 +37,37 @@ pub struct LoginResponse {
     pub token: Option<String>,
     pub mfa_required: bool,
     pub mfa_flow_id: Option<Uuid>,
+    // Only set once, right after TOTP got enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+    // The factors that can be used to complete the MFA flow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mfa_factors: Option<Vec<MfaFactor>>,
+    // Set for users without MFA that fall under the MFA policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mfa_enrollment: Option<MfaEnrollment>,
 }
 
 // Process login and return a Result
 async fn process_login(
     db: &Connection<AuthRsDatabase>,
     login_data: LoginData,
+    client_info: &ClientInfo,
 ) -> ApiResult<LoginResponse> {
-    let user = User::get_by_email(&login_data.email, db)
-        .await
-        .map_err(|err| {
+    lockout::check(None, client_info).await?;
+
+    let mut user = match User::get_by_email(&login_data.email, db).await {
+        Ok(user) => user,
+        Err(err) => {
             tracing::warn!(
                 email = %login_data.email,
                 error = %err,
                 "Failed login attempt - user not found"
             );
-            ApiError::InternalError(err.to_string())
-        })?;
+            lockout::record_failure(None, client_info, db).await;
+            return Err(ApiError::InternalError(err.to_string()));
+        }
+    };
 
     if user.disabled {
         tracing::warn!(
@@ -59,19 +78,44 @@ async fn process_login(
         return Err(ApiError::Forbidden("User is disabled".to_string()));
     }
 
+    lockout::check(Some(&user), client_info).await?;
+
     if user.verify_password(&login_data.password).is_err() {
         tracing::warn!(
             user_id = %user.id,
             email = %user.email,
             "Failed login attempt - invalid password"
         );
+        lockout::record_failure(Some(&user), client_info, db).await;
         return Err(ApiError::Unauthorized(
             "Invalid email or password".to_string(),
         ));
     }
 
-    if MfaHandler::is_mfa_required(&user) {
-        let mfa_flow = MfaHandler::start_login_flow(&user)
+    // The plain password is only available here, so outdated or imported hashes are upgraded now
+    if let Err(err) = user.upgrade_password_hash(&login_data.password, db).await {
+        tracing::error!(
+            user_id = %user.id,
+            error = %err,
+            "Failed to upgrade password hash"
+        );
+    }
+
+    if email_verification::is_verification_required(&user).await {
+        tracing::warn!(
+            user_id = %user.id,
+            email = %user.email,
+            "Login attempt with unverified email"
+        );
+        return Err(ApiError::Forbidden("Email not verified".to_string()));
+    }
+
+    let mfa_factors = MfaHandler::get_factors(&user, db)
+        .await
+        .map_err(ApiError::InternalError)?;
+
+    if !mfa_factors.is_empty() {
+        let mfa_flow = MfaHandler::start_login_flow(&user, mfa_factors)
             .await
             .map_err(|err| {
                 tracing::error!(
@@ -94,20 +138,67 @@ async fn process_login(
             token: None,
             mfa_required: true,
             mfa_flow_id: Some(mfa_flow.flow_id),
+            recovery_codes: None,
+            mfa_factors: Some(mfa_flow.factors),
+            mfa_enrollment: None,
+        });
+    }
+
+    let settings = SETTINGS.lock().await.clone();
+    let mfa_enrollment = MfaHandler::get_policy_enrollment(&user, &settings, db)
+        .await
+        .map_err(ApiError::InternalError)?;
+
+    // Past the grace period the user has to set up TOTP before getting a session
+    if let Some(enrollment) = mfa_enrollment
+        .as_ref()
+        .filter(|enrollment| enrollment.required)
+    {
+        let enroll_flow = MfaHandler::start_enroll_flow(&user)
+            .await
+            .map_err(|err| ApiError::InternalError(format!("Failed to start MFA flow: {}", err)))?;
+
+        tracing::info!(
+            user_id = %user.id,
+            email = %user.email,
+            flow_id = %enroll_flow.flow_id,
+            "Mandatory MFA enrollment started for login"
+        );
+
+        return Ok(LoginResponse {
+            user: None,
+            token: Some(enroll_flow.totp.as_ref().unwrap().get_qr_base64().unwrap()),
+            mfa_required: true,
+            mfa_flow_id: Some(enroll_flow.flow_id),
+            recovery_codes: None,
+            mfa_factors: Some(enroll_flow.factors),
+            mfa_enrollment: Some(enrollment.clone()),
         });
     }
 
+    let (session, token) = Session::new(user.id, client_info);
+    session
+        .insert(db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to create session: {}", err)))?;
+
+    lockout::record_success(&user, db).await;
+
     tracing::info!(
         user_id = %user.id,
         email = %user.email,
+        session_id = %session.id,
         "User login successful"
     );
 
     Ok(LoginResponse {
         user: Some(user.to_dto()),
-        token: Some(user.token),
+        token: Some(token),
         mfa_required: false,
         mfa_flow_id: None,
+        recovery_codes: None,
+        mfa_factors: None,
+        mfa_enrollment,
     })
 }
 
@@ -115,11 +206,12 @@ async fn process_login(
 #[post("/auth/login", format = "json", data = "<data>")]
 pub async fn login(
     db: Connection<AuthRsDatabase>,
+    client_info: ClientInfo,
     data: Json<LoginData>,
 ) -> (Status, Json<HttpResponse<LoginResponse>>) {
     let login_data = data.into_inner();
 
-    match process_login(&db, login_data).await {
+    match process_login(&db, login_data, &client_info).await {
         Ok(response) => {
             if response.user.is_some() {
                 AuditLog::new(
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use mongodb::bson::doc;
+use rocket::http::Status;
+use rocket::{post, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    errors::{ApiError, ApiResult},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        oauth_token::OAuthToken,
+        session::Session,
+    },
+};
+
+// Invalidates only the credential the request was made with
+async fn process_logout(db: &Connection<AuthRsDatabase>, req_entity: AuthEntity) -> ApiResult<()> {
+    let old_values =
+        if let Some(session) = req_entity.session {
+            session.delete(db).await.map_err(|err| {
+                ApiError::InternalError(format!("Failed to revoke session: {}", err))
+            })?;
+
+            HashMap::from([("session_id".to_string(), session.id.to_string())])
+        } else if let Some(token) = req_entity.token {
+            token.delete(db).await.map_err(|err| {
+                ApiError::InternalError(format!("Failed to revoke token: {}", err))
+            })?;
+
+            HashMap::from([(
+                "application_id".to_string(),
+                token.application_id.to_string(),
+            )])
+        } else {
+            return Err(ApiError::Unauthorized("Not logged in".to_string()));
+        };
+
+    tracing::info!(user_id = %req_entity.user_id, "User logged out");
+
+    AuditLog::new(
+        req_entity.user_id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Logout,
+        "Logout successful.".to_string(),
+        req_entity.user_id,
+        Some(old_values),
+        None,
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    Ok(())
+}
+
+// Invalidates every session and OAuth token of the user, including the one used for this request
+async fn process_logout_everywhere(
+    db: &Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> ApiResult<()> {
+    // OAuth applications must not be able to sign the user out of everything
+    if !req_entity.is_user() {
+        return Err(ApiError::Forbidden("Forbidden".to_string()));
+    }
+
+    let revoked_sessions = Session::delete_all_matching(doc! { "userId": req_entity.user_id }, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to revoke sessions: {}", err)))?;
+
+    OAuthToken::delete_all_matching(doc! { "userId": req_entity.user_id }, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to revoke tokens: {}", err)))?;
+
+    tracing::info!(
+        user_id = %req_entity.user_id,
+        revoked_sessions,
+        "User signed out everywhere"
+    );
+
+    AuditLog::new(
+        req_entity.user_id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::LogoutEverywhere,
+        "Signed out everywhere.".to_string(),
+        req_entity.user_id,
+        Some(HashMap::from([(
+            "sessions".to_string(),
+            revoked_sessions.to_string(),
+        )])),
+        None,
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    Ok(())
+}
+
+#[allow(unused)]
+#[post("/auth/logout")]
+pub async fn logout(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> (Status, Json<HttpResponse<()>>) {
+    match process_logout(&db, req_entity).await {
+        Ok(_) => json_response(HttpResponse::success_no_data("Logout successful")),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+#[allow(unused)]
+#[post("/auth/logout/all")]
+pub async fn logout_everywhere(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> (Status, Json<HttpResponse<()>>) {
+    match process_logout_everywhere(&db, req_entity).await {
+        Ok(_) => json_response(HttpResponse::success_no_data(
+            "Successfully signed out everywhere",
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
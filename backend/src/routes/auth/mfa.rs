@@ -7,18 +7,26 @@ use rocket::{
 use rocket_db_pools::Connection;
 use std::collections::HashMap;
 use totp_rs::TOTP;
+use webauthn_rs::prelude::{PublicKeyCredential, RequestChallengeResponse};
 
 use super::login::LoginResponse;
+use super::passkey::get_webauthn;
 use crate::utils::response::json_response;
 use crate::{
-    auth::mfa::{MfaState, MfaType},
+    auth::{
+        client_info::ClientInfo,
+        lockout,
+        mfa::{MfaFactor, MfaHandler, MfaState, MfaType},
+    },
     db::AuthRsDatabase,
-    errors::{ApiError, ApiResult},
+    errors::{ApiError, ApiResult, AppError},
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
+        passkey::Passkey,
+        session::Session,
+        user::User,
     },
-    MFA_SESSIONS,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -29,45 +37,91 @@ pub struct MfaData {
     pub code: String,
 }
 
+// The flow only stores the user id, the user is loaded fresh for every step
+async fn get_flow_user(flow: &MfaHandler, db: &Connection<AuthRsDatabase>) -> ApiResult<User> {
+    User::get_by_id(flow.user_id, db)
+        .await
+        .map_err(|err| ApiError::NotFound(err.to_string()))
+}
+
 // Process MFA verification and return a Result
 async fn process_mfa(
     db: &Connection<AuthRsDatabase>,
     mfa_data: MfaData,
+    client_info: &ClientInfo,
 ) -> ApiResult<(String, LoginResponse)> {
-    let mfa_sessions = MFA_SESSIONS.lock().await;
-    let cloned_sessions = mfa_sessions.clone();
-
-    let flow = cloned_sessions
-        .get(&mfa_data.flow_id)
+    let flow = MfaHandler::get_flow(mfa_data.flow_id)
+        .await?
         .ok_or_else(|| ApiError::NotFound("Invalid or expired MFA flow".to_string()))?;
 
-    drop(mfa_sessions);
-
     if flow.state == MfaState::Complete {
         return Err(ApiError::BadRequest(
             "MFA flow already complete".to_string(),
         ));
     }
 
-    if flow.r#type != MfaType::Totp && flow.r#type != MfaType::EnableTotp {
+    if flow.r#type != MfaType::Totp
+        && flow.r#type != MfaType::EnableTotp
+        && flow.r#type != MfaType::EnrollTotp
+    {
         return Err(ApiError::BadRequest("Invalid MFA type".to_string()));
     }
 
-    if !flow.verify_current_totp(&mfa_data.code).await {
-        return Err(ApiError::Unauthorized("Invalid TOTP code".to_string()));
+    // Enabling TOTP happens in an existing session, only login flows count towards a lockout
+    let is_login_flow = flow.r#type != MfaType::EnableTotp;
+    let user = get_flow_user(&flow, db).await?;
+    if is_login_flow {
+        lockout::check(Some(&user), client_info).await?;
+    }
+
+    let (verified, totp_step) = if MfaHandler::is_recovery_code(&mfa_data.code) {
+        (flow.verify_recovery_code(&mfa_data.code, db).await, None)
+    } else {
+        let step = flow.verify_current_totp(&mfa_data.code, db).await;
+        (step.is_some(), step)
+    };
+    if !verified {
+        if is_login_flow {
+            lockout::record_failure(Some(&user), client_info, db).await;
+        }
+        return Err(ApiError::Unauthorized(
+            "Invalid TOTP or recovery code".to_string(),
+        ));
+    }
+
+    if is_login_flow {
+        lockout::record_success(&user, db).await;
     }
 
-    if flow.r#type == MfaType::EnableTotp && flow.totp.is_some() && flow.user.totp_secret.is_none()
+    if (flow.r#type == MfaType::EnableTotp || flow.r#type == MfaType::EnrollTotp)
+        && flow.totp.is_some()
+        && user.totp_secret.is_none()
     {
-        let mut user = flow.user.clone();
+        let (recovery_codes, recovery_code_hashes) = MfaHandler::generate_recovery_codes()
+            .map_err(|err| ApiError::InternalError(format!("Failed to enable TOTP: {}", err)))?;
+
+        let mut user = user;
+        if is_login_flow {
+            user.failed_login_attempts = 0;
+            user.locked_until = None;
+        }
         user.totp_secret = Some(flow.totp.as_ref().unwrap().get_secret_base32());
+        user.recovery_codes = recovery_code_hashes;
+        user.last_totp_step = totp_step;
+        user.mfa_required_since = None;
 
         user.update(db)
             .await
             .map_err(|err| ApiError::InternalError(format!("Failed to enable TOTP: {:?}", err)))?;
 
-        let new_values = HashMap::from([("totp_secret".to_string(), "*************".to_string())]);
-        let old_values = HashMap::from([("totp_secret".to_string(), "".to_string())]);
+        let new_values = HashMap::from([
+            ("totp_secret".to_string(), "*************".to_string()),
+            ("recovery_codes".to_string(), "*************".to_string()),
+        ]);
+        let old_values = HashMap::from([
+            ("totp_secret".to_string(), "".to_string()),
+            ("recovery_codes".to_string(), "".to_string()),
+        ]);
 
         AuditLog::new(
             user.id.to_string(),
@@ -82,6 +136,27 @@ async fn process_mfa(
         .await
         .ok();
 
+        // A mandatory enrollment replaces the MFA step of the login, so it ends with a session
+        if flow.r#type == MfaType::EnrollTotp {
+            let (session, token) = Session::new(user.id, client_info);
+            session.insert(db).await.map_err(|err| {
+                ApiError::InternalError(format!("Failed to create session: {}", err))
+            })?;
+
+            return Ok((
+                "MFA complete".to_string(),
+                LoginResponse {
+                    user: Some(user.to_dto()),
+                    token: Some(token),
+                    mfa_required: false,
+                    mfa_flow_id: None,
+                    recovery_codes: Some(recovery_codes),
+                    mfa_factors: None,
+                    mfa_enrollment: None,
+                },
+            ));
+        }
+
         Ok((
             "TOTP enabled".to_string(),
             LoginResponse {
@@ -89,16 +164,27 @@ async fn process_mfa(
                 token: Some(TOTP::get_qr_base64(flow.totp.as_ref().unwrap()).unwrap()),
                 mfa_required: false,
                 mfa_flow_id: None,
+                recovery_codes: Some(recovery_codes),
+                mfa_factors: None,
+                mfa_enrollment: None,
             },
         ))
     } else {
+        let (session, token) = Session::new(user.id, client_info);
+        session.insert(db).await.map_err(|err| {
+            ApiError::InternalError(format!("Failed to create session: {}", err))
+        })?;
+
         Ok((
             "MFA complete".to_string(),
             LoginResponse {
-                user: Some(flow.user.to_dto()),
-                token: Some(flow.user.token.to_string()),
+                user: Some(user.to_dto()),
+                token: Some(token),
                 mfa_required: false,
                 mfa_flow_id: None,
+                recovery_codes: None,
+                mfa_factors: None,
+                mfa_enrollment: None,
             },
         ))
     }
@@ -108,11 +194,12 @@ async fn process_mfa(
 #[post("/auth/mfa", format = "json", data = "<data>")]
 pub async fn mfa(
     db: Connection<AuthRsDatabase>,
+    client_info: ClientInfo,
     data: Json<MfaData>,
 ) -> (Status, Json<HttpResponse<LoginResponse>>) {
     let mfa_data = data.into_inner();
 
-    match process_mfa(&db, mfa_data).await {
+    match process_mfa(&db, mfa_data, &client_info).await {
         Ok((message, response)) => {
             if message == "MFA complete" {
                 AuditLog::new(
@@ -134,3 +221,161 @@ pub async fn mfa(
         Err(err) => json_response(err.into()),
     }
 }
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct MfaPasskeyStartData {
+    pub flow_id: Uuid,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct MfaPasskeyFinishData {
+    pub flow_id: Uuid,
+    pub credential: PublicKeyCredential,
+}
+
+// Get a pending MFA flow that can be completed with a passkey
+async fn get_passkey_flow(flow_id: Uuid) -> ApiResult<MfaHandler> {
+    let flow = MfaHandler::get_flow(flow_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Invalid or expired MFA flow".to_string()))?;
+
+    if flow.state == MfaState::Complete {
+        return Err(ApiError::BadRequest(
+            "MFA flow already complete".to_string(),
+        ));
+    }
+
+    if flow.r#type == MfaType::EnableTotp || !flow.factors.contains(&MfaFactor::Passkey) {
+        return Err(ApiError::BadRequest("Invalid MFA type".to_string()));
+    }
+
+    Ok(flow)
+}
+
+// Process passkey MFA start and return a Result
+async fn process_mfa_passkey_start(
+    db: &Connection<AuthRsDatabase>,
+    data: MfaPasskeyStartData,
+) -> ApiResult<RequestChallengeResponse> {
+    let mut flow = get_passkey_flow(data.flow_id).await?;
+
+    let passkeys = Passkey::get_by_owner(flow.user_id, db)
+        .await
+        .map_err(|_| ApiError::AppError(AppError::PasskeyNotFound(flow.user_id)))?
+        .into_iter()
+        .map(|passkey| passkey.credential)
+        .collect::<Vec<_>>();
+
+    let (challenge, auth_state) = get_webauthn()
+        .start_passkey_authentication(&passkeys)
+        .map_err(|_| ApiError::AppError(AppError::WebauthnError))?;
+
+    flow.start_passkey_authentication(auth_state)
+        .await
+        .map_err(ApiError::InternalError)?;
+
+    Ok(challenge)
+}
+
+#[allow(unused)]
+#[post("/auth/mfa/passkey/start", format = "json", data = "<data>")]
+pub async fn mfa_passkey_start(
+    db: Connection<AuthRsDatabase>,
+    data: Json<MfaPasskeyStartData>,
+) -> (Status, Json<HttpResponse<RequestChallengeResponse>>) {
+    match process_mfa_passkey_start(&db, data.into_inner()).await {
+        Ok(challenge) => json_response(HttpResponse::success(
+            "Passkey authentication initiated",
+            challenge,
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+// Process passkey MFA finish and return a Result
+async fn process_mfa_passkey_finish(
+    db: &Connection<AuthRsDatabase>,
+    data: MfaPasskeyFinishData,
+    client_info: &ClientInfo,
+) -> ApiResult<LoginResponse> {
+    let flow = get_passkey_flow(data.flow_id).await?;
+    let user = get_flow_user(&flow, db).await?;
+    lockout::check(Some(&user), client_info).await?;
+
+    let auth_state = flow
+        .passkey_authentication
+        .as_ref()
+        .ok_or(ApiError::InvalidState(
+            "Passkey authentication not started".to_string(),
+        ))?;
+
+    let passkey = match Passkey::get_by_id(&data.credential.id, db).await {
+        Ok(passkey) if passkey.owner == user.id => passkey,
+        _ => {
+            lockout::record_failure(Some(&user), client_info, db).await;
+            return Err(ApiError::Unauthorized("Invalid passkey".to_string()));
+        }
+    };
+
+    if get_webauthn()
+        .finish_passkey_authentication(&data.credential, auth_state)
+        .is_err()
+    {
+        lockout::record_failure(Some(&user), client_info, db).await;
+        return Err(ApiError::AppError(AppError::WebauthnError));
+    }
+
+    if !flow.complete_passkey_authentication().await {
+        return Err(ApiError::BadRequest(
+            "MFA flow already complete".to_string(),
+        ));
+    }
+
+    lockout::record_success(&user, db).await;
+
+    let (session, token) = Session::new(user.id, client_info);
+    session
+        .insert(db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to create session: {}", err)))?;
+
+    AuditLog::new(
+        user.id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Login,
+        format!("MFA passkey login successful.|{}", passkey.id),
+        user.id,
+        None,
+        None,
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    Ok(LoginResponse {
+        user: Some(user.to_dto()),
+        token: Some(token),
+        mfa_required: false,
+        mfa_flow_id: None,
+        recovery_codes: None,
+        mfa_factors: None,
+        mfa_enrollment: None,
+    })
+}
+
+#[allow(unused)]
+#[post("/auth/mfa/passkey/finish", format = "json", data = "<data>")]
+pub async fn mfa_passkey_finish(
+    db: Connection<AuthRsDatabase>,
+    client_info: ClientInfo,
+    data: Json<MfaPasskeyFinishData>,
+) -> (Status, Json<HttpResponse<LoginResponse>>) {
+    match process_mfa_passkey_finish(&db, data.into_inner(), &client_info).await {
+        Ok(response) => json_response(HttpResponse::success("MFA complete", response)),
+        Err(err) => json_response(err.into()),
+    }
+}
@@ -1,4 +1,7 @@
 pub mod login;
+pub mod logout;
 pub mod mfa;
 pub mod passkey;
+pub mod password_reset;
 pub mod register;
+pub mod verify_email;
@@ -1,5 +1,6 @@
-use crate::models::passkey::Passkey;
-use crate::AUTHENTICATIONS;
+use crate::auth::{client_info::ClientInfo, email_verification, lockout};
+use crate::models::{passkey::Passkey, session::Session};
+use crate::store;
 use crate::{
     db::AuthRsDatabase,
     errors::{ApiError, ApiResult, AppError},
@@ -22,9 +23,13 @@ use rocket_db_pools::Connection;
 use std::env;
 use std::sync::Arc;
 use url::Url;
-use webauthn_rs::prelude::{DiscoverableKey, PublicKeyCredential, RequestChallengeResponse};
+use webauthn_rs::prelude::{
+    DiscoverableAuthentication, DiscoverableKey, PublicKeyCredential, RequestChallengeResponse,
+};
 use webauthn_rs::{Webauthn, WebauthnBuilder};
 
+const AUTHENTICATIONS_NAMESPACE: &str = "passkey-authentications";
+
 // Static Webauthn instance with configurable values
 lazy_static! {
     static ref WEBAUTHN: Arc<Webauthn> = {
@@ -104,10 +109,13 @@ async fn process_authenticate_start() -> ApiResult<PasskeyAuthenticateStartRespo
 
     // Store authentication state
     let authentication_id = Uuid::new();
-    AUTHENTICATIONS
-        .lock()
-        .await
-        .insert(authentication_id, auth_state);
+    store::put(
+        AUTHENTICATIONS_NAMESPACE,
+        &authentication_id.to_string(),
+        &auth_state,
+        store::DEFAULT_TTL,
+    )
+    .await?;
 
     Ok(PasskeyAuthenticateStartResponse {
         challenge,
@@ -118,12 +126,13 @@ async fn process_authenticate_start() -> ApiResult<PasskeyAuthenticateStartRespo
 #[post("/auth/passkeys/authenticate/finish", format = "json", data = "<data>")]
 pub async fn authenticate_finish(
     db: Connection<AuthRsDatabase>,
+    client_info: ClientInfo,
     data: Json<PasskeyAuthenticateFinishRequest>,
 ) -> (
     Status,
     Json<HttpResponse<PasskeyAuthenticateFinishResponse>>,
 ) {
-    match process_authenticate_finish(db, data.into_inner()).await {
+    match process_authenticate_finish(db, data.into_inner(), &client_info).await {
         Ok(response) => json_response(HttpResponse {
             status: 200,
             message: "Authentication successful".to_string(),
@@ -136,15 +145,19 @@ pub async fn authenticate_finish(
 async fn process_authenticate_finish(
     db: Connection<AuthRsDatabase>,
     data: PasskeyAuthenticateFinishRequest,
+    client_info: &ClientInfo,
 ) -> ApiResult<PasskeyAuthenticateFinishResponse> {
+    lockout::check(None, client_info).await?;
+
     // Get the authentication state
-    let auth_state = AUTHENTICATIONS
-        .lock()
-        .await
-        .remove(&data.authentication_id)
-        .ok_or(ApiError::InvalidState(
-            "Authentication not found".to_string(),
-        ))?;
+    let auth_state = store::take::<DiscoverableAuthentication>(
+        AUTHENTICATIONS_NAMESPACE,
+        &data.authentication_id.to_string(),
+    )
+    .await?
+    .ok_or(ApiError::InvalidState(
+        "Authentication not found".to_string(),
+    ))?;
 
     // Initialize Webauthn
     let webauthn = get_webauthn();
@@ -165,10 +178,28 @@ async fn process_authenticate_finish(
         .map(|passkey| DiscoverableKey::from(passkey.credential.clone()))
         .collect::<Vec<_>>();
 
+    lockout::check(Some(&user), client_info).await?;
+
     // Verify authentication
-    let _ = webauthn
+    if webauthn
         .finish_discoverable_authentication(&data.credential, auth_state, all_passkeys.as_slice())
-        .map_err(|_| ApiError::AppError(AppError::WebauthnError))?;
+        .is_err()
+    {
+        lockout::record_failure(Some(&user), client_info, &db).await;
+        return Err(ApiError::AppError(AppError::WebauthnError));
+    }
+
+    lockout::record_success(&user, &db).await;
+
+    if email_verification::is_verification_required(&user).await {
+        return Err(ApiError::Forbidden("Email not verified".to_string()));
+    }
+
+    let (session, token) = Session::new(user.id, client_info);
+    session
+        .insert(&db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to create session: {}", err)))?;
 
     AuditLog::new(
         user.clone().id.to_string(),
@@ -186,6 +217,6 @@ async fn process_authenticate_finish(
     // Return success with user information and token
     Ok(PasskeyAuthenticateFinishResponse {
         user: user.to_dto(),
-        token: user.token,
+        token,
     })
 }
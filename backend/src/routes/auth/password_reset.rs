@@ -0,0 +1,217 @@
+use std::{collections::HashMap, env, time::Duration};
+
+use mongodb::bson::{doc, Uuid};
+use rocket::http::Status;
+use rocket::{
+    post,
+    serde::{json::Json, Deserialize, Serialize},
+    tokio,
+};
+use rocket_db_pools::Connection;
+
+use crate::utils::{
+    hash_token::hash_token, response::json_response, url_token::generate_url_token,
+};
+use crate::{
+    auth::oidc::get_frontend_url,
+    db::AuthRsDatabase,
+    errors::{ApiError, ApiResult},
+    mail::{self, Mail},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        oauth_token::OAuthToken,
+        session::Session,
+        user::User,
+    },
+    store,
+};
+
+const PASSWORD_RESETS_NAMESPACE: &str = "password-resets";
+
+/// A pending reset, stored under the hash of its token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct PasswordReset {
+    user_id: Uuid,
+    // Changes with every new password, so all pending resets become invalid once one is used
+    password_fingerprint: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetRequestData {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetConfirmData {
+    pub token: String,
+    pub password: String,
+}
+
+/// How long a reset link stays valid (`PASSWORD_RESET_TTL_MINUTES`)
+fn get_reset_ttl() -> Duration {
+    let minutes = env::var("PASSWORD_RESET_TTL_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .unwrap_or(30);
+    Duration::from_secs(minutes * 60)
+}
+
+fn get_password_fingerprint(user: &User) -> String {
+    hash_token(&user.password_hash)
+}
+
+// Process a reset request, unknown emails are ignored so the response doesn't reveal which accounts exist
+async fn process_password_reset_request(
+    db: &Connection<AuthRsDatabase>,
+    data: PasswordResetRequestData,
+) -> ApiResult<()> {
+    let user = match User::get_by_email(&data.email, db).await {
+        Ok(user) if !user.disabled => user,
+        _ => {
+            tracing::info!(email = %data.email, "Password reset requested for unknown or disabled user");
+            return Ok(());
+        }
+    };
+
+    let token = generate_url_token();
+    let ttl = get_reset_ttl();
+    let reset = PasswordReset {
+        user_id: user.id,
+        password_fingerprint: get_password_fingerprint(&user),
+    };
+    store::put(PASSWORD_RESETS_NAMESPACE, &hash_token(&token), &reset, ttl).await?;
+
+    let mail = Mail {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hi {},\n\nsomeone requested to reset the password of your account. Use the link below to choose a new password, it is valid for {} minutes:\n\n{}/reset-password?token={}\n\nIf this wasn't you, you can ignore this mail.",
+            user.first_name,
+            ttl.as_secs() / 60,
+            get_frontend_url(),
+            token
+        ),
+    };
+
+    // Sent in the background, so neither a failed delivery nor the time it takes tells the client
+    // that the account exists
+    let user_id = user.id;
+    tokio::spawn(async move {
+        match mail::send(mail).await {
+            Ok(_) => tracing::info!(user_id = %user_id, "Password reset mail sent"),
+            Err(err) => {
+                tracing::error!(user_id = %user_id, error = %err, "Failed to send password reset mail")
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[allow(unused)]
+#[post("/auth/password-reset", format = "json", data = "<data>")]
+pub async fn request_password_reset(
+    db: Connection<AuthRsDatabase>,
+    data: Json<PasswordResetRequestData>,
+) -> (Status, Json<HttpResponse<()>>) {
+    match process_password_reset_request(&db, data.into_inner()).await {
+        Ok(_) => json_response(HttpResponse::success_no_data(
+            "If the email belongs to an account, a reset link has been sent",
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+// Process a reset confirmation, the new password replaces all existing credentials of the user
+async fn process_password_reset_confirm(
+    db: &Connection<AuthRsDatabase>,
+    data: PasswordResetConfirmData,
+) -> ApiResult<()> {
+    let token_hash = hash_token(&data.token);
+    let invalid_token = || ApiError::BadRequest("Invalid or expired reset token".to_string());
+
+    let reset = store::get::<PasswordReset>(PASSWORD_RESETS_NAMESPACE, &token_hash)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    let mut user = User::get_full_by_id(reset.user_id, db)
+        .await
+        .map_err(|_| invalid_token())?;
+    if user.disabled {
+        return Err(ApiError::Forbidden("User is disabled".to_string()));
+    }
+    if reset.password_fingerprint != get_password_fingerprint(&user) {
+        return Err(invalid_token());
+    }
+
+    user.validate_new_password(&data.password)
+        .await
+        .map_err(|err| ApiError::AppError(err.into()))?;
+    user.set_password(&data.password)
+        .await
+        .map_err(|err| ApiError::AppError(err.into()))?;
+
+    // Validated before taking the token, so a rejected password doesn't use it up
+    if store::take::<PasswordReset>(PASSWORD_RESETS_NAMESPACE, &token_hash)
+        .await?
+        .is_none()
+    {
+        return Err(invalid_token());
+    }
+
+    // Proving access to the email also lifts a lockout
+    user.failed_login_attempts = 0;
+    user.locked_until = None;
+    user.update(db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to reset password: {}", err)))?;
+
+    let revoked_sessions = Session::delete_all_matching(doc! { "userId": user.id }, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to revoke sessions: {}", err)))?;
+    OAuthToken::delete_all_matching(doc! { "userId": user.id }, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to revoke tokens: {}", err)))?;
+
+    tracing::info!(user_id = %user.id, revoked_sessions, "Password reset completed");
+
+    AuditLog::new(
+        user.id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Update,
+        "Reset password.".to_string(),
+        user.id,
+        Some(HashMap::from([(
+            "password".to_string(),
+            "***********".to_string(),
+        )])),
+        Some(HashMap::from([(
+            "password".to_string(),
+            "***********".to_string(),
+        )])),
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    Ok(())
+}
+
+#[allow(unused)]
+#[post("/auth/password-reset/confirm", format = "json", data = "<data>")]
+pub async fn confirm_password_reset(
+    db: Connection<AuthRsDatabase>,
+    data: Json<PasswordResetConfirmData>,
+) -> (Status, Json<HttpResponse<()>>) {
+    match process_password_reset_confirm(&db, data.into_inner()).await {
+        Ok(_) => json_response(HttpResponse::success_no_data("Password reset")),
+        Err(err) => json_response(err.into()),
+    }
+}
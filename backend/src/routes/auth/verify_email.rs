@@ -0,0 +1,75 @@
+use rocket::http::Status;
+use rocket::{
+    post,
+    serde::{json::Json, Deserialize},
+};
+use rocket_db_pools::Connection;
+
+use crate::utils::response::json_response;
+use crate::{
+    auth::email_verification,
+    db::AuthRsDatabase,
+    errors::ApiResult,
+    models::{
+        http_response::HttpResponse,
+        user::{User, UserDTO},
+    },
+};
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEmailData {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ResendVerificationData {
+    pub email: String,
+}
+
+#[allow(unused)]
+#[post("/auth/verify-email", format = "json", data = "<data>")]
+pub async fn verify_email(
+    db: Connection<AuthRsDatabase>,
+    data: Json<VerifyEmailData>,
+) -> (Status, Json<HttpResponse<UserDTO>>) {
+    match email_verification::verify(&data.token, &db).await {
+        Ok(user) => json_response(HttpResponse::success("Email verified", user.to_dto())),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+// Process a resend request, like password resets the response doesn't reveal which accounts exist
+async fn process_resend_verification(
+    db: &Connection<AuthRsDatabase>,
+    data: ResendVerificationData,
+) -> ApiResult<()> {
+    match User::get_by_email(&data.email, db).await {
+        Ok(user) if !user.disabled && !user.email_verified => {
+            email_verification::try_send_verification_mail(&user).await
+        }
+        _ => tracing::info!(
+            email = %data.email,
+            "Email verification requested for unknown, disabled or verified user"
+        ),
+    }
+
+    Ok(())
+}
+
+#[allow(unused)]
+#[post("/auth/verify-email/resend", format = "json", data = "<data>")]
+pub async fn resend_verification_email(
+    db: Connection<AuthRsDatabase>,
+    data: Json<ResendVerificationData>,
+) -> (Status, Json<HttpResponse<()>>) {
+    match process_resend_verification(&db, data.into_inner()).await {
+        Ok(_) => json_response(HttpResponse::success_no_data(
+            "If the email belongs to an unverified account, a verification link has been sent",
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{delete, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -44,7 +45,7 @@ pub async fn disconnect(
 
     if (req_entity.is_user()
         && req_entity.user_id != uuid
-        && !req_entity.user.clone().unwrap().is_admin())
+        && !req_entity.has_permission(Permission::ConnectionsDelete))
     {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
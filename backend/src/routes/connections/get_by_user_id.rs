@@ -1,3 +1,4 @@
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -60,19 +61,42 @@ pub async fn get_by_user_id(
 
     if (req_entity.is_user()
         && req_entity.user_id != uuid
-        && !req_entity.user.clone().unwrap().is_admin())
+        && !req_entity.has_permission(Permission::ConnectionsRead))
         || req_entity.is_token() && req_entity.user_id != uuid
     {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
-    let connected_applications = match OAuthToken::get_by_user_id(uuid, &db).await {
+    let tokens = match OAuthToken::get_by_user_id(uuid, &db).await {
         Ok(tokens) => tokens,
         Err(err) => {
             return json_response(err.into());
         }
     };
 
+    // Every authorization creates its own token family, so merge them into one connection per application
+    let mut connected_applications: Vec<OAuthToken> = Vec::new();
+    for token in tokens {
+        match connected_applications
+            .iter_mut()
+            .find(|connected| connected.application_id == token.application_id)
+        {
+            Some(connected) => {
+                for scope in token.scope.iter() {
+                    if !connected.scope.contains(scope) {
+                        connected.scope.push(scope.clone());
+                    }
+                }
+                if token.created_at > connected.created_at {
+                    connected.id = token.id;
+                    connected.expires_in = token.expires_in;
+                    connected.created_at = token.created_at;
+                }
+            }
+            None => connected_applications.push(token),
+        }
+    }
+
     let filter = doc! {
         "_id": {
             "$in": connected_applications.iter().map(|token| token.clone().application_id).collect::<Vec<Uuid>>()
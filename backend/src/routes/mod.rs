@@ -4,8 +4,12 @@ pub mod base;
 pub mod connections;
 pub mod oauth;
 pub mod oauth_applications;
+pub mod oidc;
 pub mod passkeys;
 pub mod registration_tokens;
 pub mod roles;
+pub mod scim;
+pub mod sessions;
 pub mod settings;
+pub mod signing_keys;
 pub mod users;
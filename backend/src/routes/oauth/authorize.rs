@@ -1,22 +1,24 @@
 use mongodb::bson::Uuid;
-use rand::Rng;
 use rocket::http::Status;
 use rocket::{
     post,
     serde::{json::Json, Deserialize, Serialize},
-    tokio,
 };
 use rocket_db_pools::Connection;
 
 use crate::{
-    auth::AuthEntity,
+    auth::{
+        email_verification,
+        pkce::{self, CodeChallengeMethod},
+        AuthEntity,
+    },
     db::AuthRsDatabase,
-    models::{oauth_application::OAuthApplication, oauth_scope::OAuthScope},
-    OAUTH_CODES,
+    models::{
+        authorization_code::AuthorizationCode, oauth_application::OAuthApplication,
+        oauth_scope::OAuthScope,
+    },
 };
 
-use super::token::TokenOAuthData;
-
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[serde(rename_all = "camelCase")]
@@ -24,6 +26,9 @@ pub struct AuthorizeOAuthData {
     client_id: Uuid,
     redirect_uri: String,
     scope: Vec<OAuthScope>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -32,7 +37,7 @@ pub struct AuthorizeOAuthData {
 pub struct AuthorizeOAuthResponse {
     pub client_id: Uuid,
     pub redirect_uri: String,
-    pub code: u32,
+    pub code: String,
 }
 
 #[allow(unused)]
@@ -46,7 +51,7 @@ pub async fn authorize_oauth_application(
 
     if !req_entity.is_user()
         || req_entity.user.as_ref().unwrap().disabled
-        || req_entity.user.unwrap().is_system_admin()
+        || req_entity.user.as_ref().unwrap().is_system_admin()
     {
         tracing::warn!(
             user_id = %req_entity.user_id,
@@ -55,12 +60,18 @@ pub async fn authorize_oauth_application(
         return (Status::Unauthorized, None);
     }
 
+    if email_verification::is_verification_required(req_entity.user.as_ref().unwrap()).await {
+        tracing::warn!(
+            user_id = %req_entity.user_id,
+            "User with unverified email tried to authorize an application"
+        );
+        return (Status::Forbidden, None);
+    }
+
     if data.scope.is_empty() {
         return (Status::BadRequest, None);
     }
 
-    let code = rand::rng().random_range(10000000..99999999);
-
     let oauth_application = match OAuthApplication::get_by_id(data.client_id, &db).await {
         Ok(app) => app,
         Err(err) => {
@@ -82,40 +93,60 @@ pub async fn authorize_oauth_application(
         return (Status::Forbidden, None);
     }
 
-    let mut codes = OAUTH_CODES.lock().await;
+    let code_challenge_method = match &data.code_challenge {
+        Some(code_challenge) => {
+            let method =
+                match CodeChallengeMethod::parse(data.code_challenge_method.as_deref()) {
+                    Some(method) => method,
+                    None => return (Status::BadRequest, None),
+                };
+
+            if !pkce::is_valid_code(code_challenge) {
+                return (Status::BadRequest, None);
+            }
+
+            Some(method)
+        }
+        None => {
+            if oauth_application.public {
+                tracing::warn!(
+                    client_id = %data.client_id,
+                    "Public client tried to authorize without PKCE"
+                );
+                return (Status::BadRequest, None);
+            }
+
+            None
+        }
+    };
+
     let redirect_uri = data.redirect_uri.clone();
-    codes.insert(
-        code,
-        TokenOAuthData {
-            client_id: oauth_application.id,
-            client_secret: oauth_application.secret,
-            user_id: Some(req_entity.user_id),
-            code,
-            scope: Some(data.scope),
-            grant_type: "authorization_code".to_string(),
-            redirect_uri: data.redirect_uri,
-        },
+    let (authorization_code, code) = AuthorizationCode::new(
+        oauth_application.id,
+        req_entity.user_id,
+        data.scope,
+        data.redirect_uri,
+        data.nonce,
+        data.code_challenge,
+        code_challenge_method,
     );
-    drop(codes);
+
+    if let Err(err) = authorization_code.insert().await {
+        tracing::error!(
+            client_id = %oauth_application.id,
+            error = %err,
+            "Error storing authorization code"
+        );
+        return (Status::InternalServerError, None);
+    }
 
     tracing::info!(
         client_id = %oauth_application.id,
         user_id = %req_entity.user_id,
-        code = code,
+        code_id = %authorization_code.id,
         "OAuth authorization code issued"
     );
 
-    //TODO: Store this state in the db with a timestamp, and check for expiration
-    //  We can not and should not rely on the application not crashing or restarting
-    //  This application should be completely stateless
-    // delete code after 5 minutes
-    tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
-        let mut codes = OAUTH_CODES.lock().await;
-        codes.remove(&code);
-        drop(codes);
-    });
-
     (
         Status::Ok,
         Some(Json(AuthorizeOAuthResponse {
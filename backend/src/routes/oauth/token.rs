@@ -1,17 +1,32 @@
-use mongodb::bson::Uuid;
+use mongodb::bson::{doc, Uuid};
 use rocket::http::Status;
 use rocket::{
+    error,
     form::Form,
     post,
     serde::{json::Json, Deserialize, Serialize},
     FromForm,
 };
 use rocket_db_pools::Connection;
+use std::collections::HashMap;
 
 use crate::{
+    auth::{
+        oidc::{get_group_claims, IdTokenClaims},
+        pkce,
+    },
     db::AuthRsDatabase,
-    models::{oauth_scope::OAuthScope, oauth_token::OAuthToken},
-    OAUTH_CODES,
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        authorization_code::{AuthorizationCode, AuthorizationCodeError},
+        oauth_application::OAuthApplication,
+        oauth_scope::OAuthScope,
+        oauth_token::OAuthToken,
+        refresh_token::{RefreshToken, RefreshTokenError},
+        signing_key::SigningKey,
+        user::User,
+    },
+    SYSTEM_USER_ID,
 };
 
 #[derive(Debug, Deserialize, FromForm)]
@@ -20,36 +35,43 @@ pub struct TokenOAuthFieldData {
     #[form(field = "client_id")]
     pub client_id: String,
     #[form(field = "client_secret")]
-    pub client_secret: String,
+    pub client_secret: Option<String>,
     #[form(field = "grant_type")]
     pub grant_type: String,
     #[form(field = "code")]
-    pub code: u32,
+    pub code: Option<String>,
     #[form(field = "redirect_uri")]
-    pub redirect_uri: String,
+    pub redirect_uri: Option<String>,
+    #[form(field = "code_verifier")]
+    pub code_verifier: Option<String>,
+    #[form(field = "refresh_token")]
+    pub refresh_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct TokenOAuthJsonData {
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: Option<String>,
     pub grant_type: String,
-    pub code: u32,
-    pub redirect_uri: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(crate = "rocket::serde")]
-#[serde(rename_all = "camelCase")]
-pub struct TokenOAuthData {
-    pub client_id: Uuid,
-    pub client_secret: String,
-    pub grant_type: String,
-    pub user_id: Option<Uuid>,
-    pub code: u32,
-    pub scope: Option<Vec<OAuthScope>>,
-    pub redirect_uri: String,
+impl From<TokenOAuthFieldData> for TokenOAuthJsonData {
+    fn from(data: TokenOAuthFieldData) -> Self {
+        Self {
+            client_id: data.client_id,
+            client_secret: data.client_secret,
+            grant_type: data.grant_type,
+            code: data.code,
+            redirect_uri: data.redirect_uri,
+            code_verifier: data.code_verifier,
+            refresh_token: data.refresh_token,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -59,6 +81,9 @@ pub struct TokenOAuthResponse {
     pub token_type: String,
     pub expires_in: u64,
     pub scope: String,
+    pub refresh_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
 }
 
 #[allow(unused)]
@@ -71,16 +96,7 @@ pub async fn get_oauth_token(
     db: Connection<AuthRsDatabase>,
     data: Form<TokenOAuthFieldData>,
 ) -> (Status, Option<Json<TokenOAuthResponse>>) {
-    let form_data = data.into_inner();
-
-    match handle_token_request(
-        db,
-        form_data.client_id,
-        form_data.client_secret,
-        form_data.grant_type,
-        form_data.code,
-        form_data.redirect_uri,
-    ).await {
+    match handle_token_request(db, data.into_inner().into()).await {
         Ok(response) => (Status::Ok, Some(Json(response))),
         Err(status) => (status, None),
     }
@@ -96,16 +112,7 @@ pub async fn get_oauth_token_json(
     db: Connection<AuthRsDatabase>,
     data: Json<TokenOAuthJsonData>,
 ) -> (Status, Option<Json<Option<TokenOAuthResponse>>>) {
-    let data = data.into_inner();
-
-    match handle_token_request(
-        db,
-        data.client_id,
-        data.client_secret,
-        data.grant_type,
-        data.code,
-        data.redirect_uri,
-    ).await {
+    match handle_token_request(db, data.into_inner()).await {
         Ok(response) => (Status::Ok, Some(Json(Some(response)))),
         Err(status) => (status, Some(Json(None))),
     }
@@ -114,88 +121,315 @@ pub async fn get_oauth_token_json(
 
 async fn handle_token_request(
     db: Connection<AuthRsDatabase>,
-    client_id: String,
-    client_secret: String,
-    grant_type: String,
-    code: u32,
-    redirect_uri: String,
+    data: TokenOAuthJsonData,
 ) -> Result<TokenOAuthResponse, Status> {
-    let client_id = match Uuid::parse_str(&client_id) {
+    let client_id = match Uuid::parse_str(&data.client_id) {
         Ok(client_id) => client_id,
         Err(_) => return Err(Status::BadRequest),
     };
 
-    let data = TokenOAuthData {
-        client_id,
-        client_secret: client_secret,
-        grant_type: grant_type,
-        user_id: None,
-        code: code,
-        scope: None,
-        redirect_uri: redirect_uri,
+    match data.grant_type.trim() {
+        "authorization_code" => handle_authorization_code_grant(db, client_id, data).await,
+        "refresh_token" => handle_refresh_token_grant(db, client_id, data).await,
+        _ => Err(Status::BadRequest),
+    }
+}
+
+async fn handle_authorization_code_grant(
+    db: Connection<AuthRsDatabase>,
+    client_id: Uuid,
+    data: TokenOAuthJsonData,
+) -> Result<TokenOAuthResponse, Status> {
+    let (code, redirect_uri) = match (data.code, data.redirect_uri) {
+        (Some(code), Some(redirect_uri)) => (code, redirect_uri),
+        _ => return Err(Status::BadRequest),
     };
 
-    let mut codes = OAUTH_CODES.lock().await;
-    let code_data = match codes.get(&data.code) {
-        Some(code_data) => code_data.clone(),
-        None => return Err(Status::Unauthorized),
+    let code_data = match AuthorizationCode::redeem(&code).await {
+        Ok(code_data) => code_data,
+        Err(AuthorizationCodeError::NotFound) => return Err(Status::Unauthorized),
+        Err(_) => return Err(Status::InternalServerError),
     };
-    codes.remove(&data.code);
-    drop(codes);
 
-    if code_data.client_id != data.client_id
-        || code_data.grant_type.trim() != data.grant_type.trim()
-        || code_data.client_secret.trim() != data.client_secret.trim()
-        || code_data.redirect_uri.trim() != data.redirect_uri.trim()
-    {
+    if code_data.client_id != client_id || code_data.redirect_uri.trim() != redirect_uri.trim() {
         return Err(Status::Unauthorized);
     }
 
-    let mut existing_tokens = match OAuthToken::get_by_user_and_application_id(
-        code_data.user_id.unwrap(),
+    let oauth_application = match OAuthApplication::get_by_id(client_id, &db).await {
+        Ok(app) => app,
+        Err(_) => return Err(Status::Unauthorized),
+    };
+
+    // Public clients can only leave out the secret if the code is bound to a PKCE challenge
+    match data.client_secret {
+        Some(client_secret) => {
+            if oauth_application.secret.trim() != client_secret.trim() {
+                return Err(Status::Unauthorized);
+            }
+        }
+        None => {
+            if !oauth_application.public || code_data.code_challenge.is_none() {
+                return Err(Status::Unauthorized);
+            }
+        }
+    }
+
+    match (
+        &code_data.code_challenge,
+        &code_data.code_challenge_method,
+        data.code_verifier,
+    ) {
+        (Some(code_challenge), Some(method), Some(code_verifier)) => {
+            if !pkce::verify_code_challenge(&code_verifier, code_challenge, method) {
+                tracing::warn!(
+                    client_id = %client_id,
+                    "PKCE code verifier does not match the code challenge"
+                );
+                return Err(Status::Unauthorized);
+            }
+        }
+        (None, _, None) => {}
+        _ => return Err(Status::Unauthorized),
+    }
+
+    let user_id = code_data.user_id;
+    let granted_scope = code_data.scope.clone();
+
+    // Every authorization starts a new token family
+    let token = match OAuthToken::new(
         code_data.client_id,
-        &db,
+        user_id,
+        granted_scope.clone(),
+        OAuthToken::ACCESS_TOKEN_LIFETIME,
     )
+    .unwrap()
+    .insert(&db)
     .await
     {
-        Ok(tokens) => tokens,
-        Err(_) => return Err(Status::BadRequest),
+        Ok(token) => token,
+        Err(_) => return Err(Status::InternalServerError),
     };
 
-    let token = if !existing_tokens.is_empty() {
-        // TODO: implement a proper scope check here
-        if existing_tokens[0].scope.len() > code_data.scope.as_ref().unwrap().len() {
-            existing_tokens[0].clone()
-        } else {
-            existing_tokens[0]
-                .reauthenticate(code_data.scope.as_ref().unwrap().clone(), &db)
-                .await
-                .unwrap()
-        }
-    } else {
-        match OAuthToken::new(
-            code_data.client_id,
-            code_data.user_id.unwrap(),
-            code_data.scope.clone().unwrap(),
-            30 * 24 * 60 * 60 * 1000,
+    let (refresh_token, refresh_token_value) =
+        RefreshToken::new(token.id, code_data.client_id, user_id, granted_scope.clone());
+    if refresh_token.insert(&db).await.is_err() {
+        return Err(Status::InternalServerError);
+    }
+
+    let id_token = if granted_scope.contains(&OAuthScope::OpenId) {
+        Some(
+            issue_id_token(
+                &db,
+                user_id,
+                code_data.client_id,
+                &granted_scope,
+                code_data.nonce.clone(),
+            )
+            .await?,
         )
-        .unwrap()
-        .insert(&db)
-        .await
-        {
-            Ok(token) => token,
-            Err(_) => return Err(Status::InternalServerError),
-        }
+    } else {
+        None
     };
 
     tracing::info!(
-        user_id = %code_data.user_id.unwrap(),
+        user_id = %user_id,
         client_id = %code_data.client_id,
         token_id = %token.id,
         "OAuth access token issued"
     );
 
-    Ok(TokenOAuthResponse {
+    Ok(build_token_response(&token, refresh_token_value, id_token))
+}
+
+async fn handle_refresh_token_grant(
+    db: Connection<AuthRsDatabase>,
+    client_id: Uuid,
+    data: TokenOAuthJsonData,
+) -> Result<TokenOAuthResponse, Status> {
+    let refresh_token_value = match data.refresh_token {
+        Some(refresh_token) => refresh_token,
+        None => return Err(Status::BadRequest),
+    };
+
+    let oauth_application = match OAuthApplication::get_by_id(client_id, &db).await {
+        Ok(app) => app,
+        Err(_) => return Err(Status::Unauthorized),
+    };
+
+    match data.client_secret {
+        Some(client_secret) => {
+            if oauth_application.secret.trim() != client_secret.trim() {
+                return Err(Status::Unauthorized);
+            }
+        }
+        None => {
+            if !oauth_application.public {
+                return Err(Status::Unauthorized);
+            }
+        }
+    }
+
+    let refresh_token = match RefreshToken::get_by_token(&refresh_token_value, &db).await {
+        Ok(refresh_token) => refresh_token,
+        Err(RefreshTokenError::NotFound) | Err(RefreshTokenError::Expired) => {
+            return Err(Status::Unauthorized)
+        }
+        Err(_) => return Err(Status::InternalServerError),
+    };
+
+    if refresh_token.application_id != client_id {
+        return Err(Status::Unauthorized);
+    }
+
+    // A used token showing up again means it leaked, so the whole family gets revoked
+    let first_use = match refresh_token.mark_used(&db).await {
+        Ok(first_use) => first_use,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    if refresh_token.used || !first_use {
+        revoke_token_family(&db, &refresh_token).await;
+        return Err(Status::Unauthorized);
+    }
+
+    let user = match User::get_by_id(refresh_token.user_id, &db).await {
+        Ok(user) => user,
+        Err(_) => return Err(Status::Unauthorized),
+    };
+    if user.disabled {
+        return Err(Status::Unauthorized);
+    }
+
+    let mut token = match OAuthToken::get_by_id(refresh_token.family_id, &db).await {
+        Ok(token) => token,
+        Err(_) => return Err(Status::Unauthorized),
+    };
+    let token = match token.rotate(&db).await {
+        Ok(token) => token,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+
+    let (next_refresh_token, next_refresh_token_value) = RefreshToken::new(
+        refresh_token.family_id,
+        refresh_token.application_id,
+        refresh_token.user_id,
+        refresh_token.scope.clone(),
+    );
+    if next_refresh_token.insert(&db).await.is_err() {
+        return Err(Status::InternalServerError);
+    }
+
+    let id_token = if token.scope.contains(&OAuthScope::OpenId) {
+        Some(issue_id_token(&db, token.user_id, client_id, &token.scope, None).await?)
+    } else {
+        None
+    };
+
+    tracing::info!(
+        user_id = %token.user_id,
+        client_id = %client_id,
+        token_id = %token.id,
+        "OAuth access token refreshed"
+    );
+
+    Ok(build_token_response(&token, next_refresh_token_value, id_token))
+}
+
+async fn revoke_token_family(db: &Connection<AuthRsDatabase>, refresh_token: &RefreshToken) {
+    tracing::warn!(
+        user_id = %refresh_token.user_id,
+        client_id = %refresh_token.application_id,
+        family_id = %refresh_token.family_id,
+        "Refresh token reuse detected, revoking token family"
+    );
+
+    if let Err(err) =
+        OAuthToken::delete_all_matching(doc! { "_id": refresh_token.family_id }, db).await
+    {
+        error!("Failed to revoke access token: {}", err);
+    }
+    if let Err(err) =
+        RefreshToken::delete_all_matching(doc! { "familyId": refresh_token.family_id }, db).await
+    {
+        error!("Failed to revoke refresh tokens: {}", err);
+    }
+
+    let old_values = HashMap::from([
+        (
+            "application_id".to_string(),
+            refresh_token.application_id.to_string(),
+        ),
+        (
+            "token_family".to_string(),
+            refresh_token.family_id.to_string(),
+        ),
+    ]);
+
+    if let Err(err) = AuditLog::new(
+        refresh_token.user_id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Delete,
+        "Refresh token reuse detected, token family revoked.".to_string(),
+        *SYSTEM_USER_ID,
+        Some(old_values),
+        None,
+    )
+    .insert(db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+}
+
+async fn issue_id_token(
+    db: &Connection<AuthRsDatabase>,
+    user_id: Uuid,
+    client_id: Uuid,
+    scope: &[OAuthScope],
+    nonce: Option<String>,
+) -> Result<String, Status> {
+    let user = match User::get_by_id(user_id, db).await {
+        Ok(user) => user,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+
+    let user = user.to_dto();
+    let groups = match get_group_claims(&user, client_id, scope, db).await {
+        Ok(groups) => groups,
+        Err(err) => {
+            tracing::error!(client_id = %client_id, error = %err, "Failed to resolve group claims");
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let claims = IdTokenClaims::new(&user, client_id, scope, groups, nonce);
+
+    let signing_key = match SigningKey::get_active(db).await {
+        Ok(signing_key) => signing_key,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to load active signing key");
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    match claims.sign(&signing_key) {
+        Ok(id_token) => Ok(id_token),
+        Err(err) => {
+            tracing::error!(
+                client_id = %client_id,
+                error = %err,
+                "Failed to issue id token"
+            );
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+fn build_token_response(
+    token: &OAuthToken,
+    refresh_token: String,
+    id_token: Option<String>,
+) -> TokenOAuthResponse {
+    TokenOAuthResponse {
         access_token: token.token.to_string(),
         token_type: "Bearer".to_string(),
         expires_in: token.expires_in,
@@ -205,5 +439,7 @@ async fn handle_token_request(
             .map(|s| s.to_string())
             .collect::<Vec<String>>()
             .join(","),
-    })
+        refresh_token,
+        id_token,
+    }
 }
@@ -5,6 +5,7 @@ use rocket::{
 };
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::SETTINGS;
 use crate::{
@@ -24,6 +25,8 @@ pub struct CreateOAuthApplicationData {
     name: String,
     description: Option<String>,
     redirect_uris: Vec<String>,
+    #[serde(default)]
+    public: bool,
 }
 
 #[allow(unused)]
@@ -43,7 +46,7 @@ pub async fn create_oauth_application(
 
     // Handle only admins can create OAuth Applications check
     let settings = (*SETTINGS).lock().await;
-    if !settings.allow_oauth_apps_for_users && !req_entity.user.unwrap().is_admin() {
+    if !settings.allow_oauth_apps_for_users && !req_entity.has_permission(Permission::OAuthApplicationsCreate) {
         return json_response(HttpResponse::forbidden(
             "Only admins can create OAuth Applications",
         ));
@@ -53,6 +56,7 @@ pub async fn create_oauth_application(
         data.name,
         data.description,
         data.redirect_uris,
+        data.public,
         req_entity.user_id,
     ) {
         Ok(oauth_application) => oauth_application,
@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{delete, error, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -35,7 +36,7 @@ pub async fn delete_oauth_application(
         Err(err) => return json_response(err.into()),
     };
 
-    if req_entity.user_id != oauth_application.owner && !req_entity.user.unwrap().is_admin() {
+    if req_entity.user_id != oauth_application.owner && !req_entity.has_permission(Permission::OAuthApplicationsDelete) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
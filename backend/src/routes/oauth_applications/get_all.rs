@@ -1,8 +1,10 @@
-use mongodb::bson::doc;
+use mongodb::bson::{doc, Document};
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
+use crate::utils::pagination::{starts_with, Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -15,11 +17,12 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/oauth-applications", format = "json")]
+#[get("/oauth-applications?<query..>", format = "json")]
 pub async fn get_all_oauth_applications(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<OAuthApplicationDTO>>>) {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<OAuthApplicationDTO>>>) {
     if req_entity.is_token()
         && (!req_entity
             .token
@@ -34,20 +37,32 @@ pub async fn get_all_oauth_applications(
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    let filter = match req_entity.user.unwrap().is_admin() {
-        true => None,
-        false => Some(doc! {
+    let pagination = match query.pagination(&["createdAt", "name"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let mut filters = Vec::<Document>::new();
+    if !req_entity.has_permission(Permission::OAuthApplicationsRead) {
+        filters.push(doc! {
             "owner": req_entity.user_id
-        }),
+        });
+    }
+    if let Some(name) = &query.name {
+        filters.push(starts_with("name", name.trim()));
+    }
+    let filter = match filters.is_empty() {
+        true => doc! {},
+        false => doc! { "$and": filters },
     };
 
-    let applications = match OAuthApplication::get_all(&db, filter).await {
+    let applications = match OAuthApplication::get_page(filter, &pagination, &db).await {
         Ok(oauth_applications) => oauth_applications,
         Err(err) => return json_response(err.into()),
     };
 
     json_response(HttpResponse::success(
         "Successfully retrieved your oauth applications",
-        applications.into_iter().map(|app| app.to_dto()).collect(),
+        applications.map(|app| app.to_dto()),
     ))
 }
@@ -1,3 +1,4 @@
+use crate::models::permission::Permission;
 use crate::utils::parse_uuid::parse_uuid;
 use crate::utils::response::json_response;
 use crate::{
@@ -7,17 +8,20 @@ use crate::{
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
-        oauth_application::{OAuthApplication, OAuthApplicationDTO, OAuthApplicationResult},
+        oauth_application::{
+            OAuthApplication, OAuthApplicationDTO, OAuthApplicationError, OAuthApplicationResult,
+        },
+        role::Role,
     },
 };
-use mongodb::bson::Uuid;
+use mongodb::bson::{doc, Uuid};
 use rocket::http::Status;
 use rocket::{
     error, patch,
     serde::{json::Json, Deserialize},
 };
 use rocket_db_pools::Connection;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -26,6 +30,8 @@ pub struct UpdateOAuthApplicationData {
     name: Option<String>,
     description: Option<String>,
     redirect_uris: Option<Vec<String>>,
+    public: Option<bool>,
+    disclosed_roles: Option<Vec<Uuid>>,
 }
 
 #[allow(unused)]
@@ -111,6 +117,55 @@ impl OAuthApplicationUpdate {
         }
     }
 
+    fn update_public(&mut self, new_public: bool) {
+        if self.app.public != new_public {
+            let old_public = self.app.public;
+            self.update_field("public", old_public.to_string(), new_public.to_string());
+            self.app.public = new_public;
+        }
+    }
+
+    async fn update_disclosed_roles(
+        &mut self,
+        new_roles: Vec<Uuid>,
+        db: &Connection<AuthRsDatabase>,
+    ) -> OAuthApplicationResult<()> {
+        if self.app.disclosed_roles == new_roles {
+            return Ok(());
+        }
+
+        let role_ids = new_roles.iter().collect::<HashSet<_>>();
+        let filter = doc! {
+            "_id": {
+                "$in": &new_roles
+            }
+        };
+        let existing_roles = Role::count(filter, db)
+            .await
+            .map_err(|e| OAuthApplicationError::InternalServerError(e.message()))?;
+        if existing_roles != role_ids.len() as u64 {
+            return Err(OAuthApplicationError::InvalidData(
+                "One or more roles do not exist".to_string(),
+            ));
+        }
+
+        let old_roles = self
+            .app
+            .disclosed_roles
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let new_roles_str = new_roles
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.update_field("disclosed_roles", old_roles, new_roles_str);
+        self.app.disclosed_roles = new_roles;
+        Ok(())
+    }
+
     async fn save(
         self,
         db: &Connection<AuthRsDatabase>,
@@ -156,10 +211,7 @@ async fn update_oauth_application_internal(
     let uuid = parse_uuid(id).map_err(|e| ApiError::BadRequest(e.to_string()))?;
     let app = OAuthApplication::get_by_id(uuid, &db).await?;
 
-    let req_user = req_entity
-        .user()
-        .map_err(|_| ApiError::Forbidden("Forbidden".to_string()))?;
-    if req_entity.user_id != app.owner && !req_user.is_admin() {
+    if req_entity.user_id != app.owner && !req_entity.has_permission(Permission::OAuthApplicationsUpdate) {
         return Err(ApiError::Forbidden("Missing permissions!".to_string()).into());
     }
 
@@ -178,6 +230,14 @@ async fn update_oauth_application_internal(
         update.update_redirect_uris(redirect_uris);
     }
 
+    if let Some(public) = data.public {
+        update.update_public(public);
+    }
+
+    if let Some(disclosed_roles) = data.disclosed_roles {
+        update.update_disclosed_roles(disclosed_roles, &db).await?;
+    }
+
     // Save changes
     update.save(&db, req_entity.user_id).await
 }
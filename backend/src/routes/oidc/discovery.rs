@@ -0,0 +1,62 @@
+use rocket::{
+    get,
+    serde::{json::Json, Serialize},
+};
+
+use crate::auth::oidc::{get_frontend_url, get_issuer};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[allow(unused)]
+#[get("/.well-known/openid-configuration")]
+pub async fn openid_configuration() -> Json<OpenIdConfiguration> {
+    let issuer = get_issuer();
+
+    Json(OpenIdConfiguration {
+        authorization_endpoint: format!("{}/oauth/authorize", get_frontend_url()),
+        token_endpoint: format!("{}/oauth/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        response_types_supported: to_strings(&["code"]),
+        subject_types_supported: to_strings(&["public"]),
+        id_token_signing_alg_values_supported: to_strings(&["RS256"]),
+        scopes_supported: to_strings(&["openid", "profile", "email", "roles:read"]),
+        token_endpoint_auth_methods_supported: to_strings(&["client_secret_post", "none"]),
+        grant_types_supported: to_strings(&["authorization_code", "refresh_token"]),
+        code_challenge_methods_supported: to_strings(&["S256", "plain"]),
+        claims_supported: to_strings(&[
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "nonce",
+            "name",
+            "given_name",
+            "family_name",
+            "email",
+            "groups",
+        ]),
+        issuer,
+    })
+}
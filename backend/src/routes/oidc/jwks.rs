@@ -0,0 +1,34 @@
+use rocket::{
+    get,
+    http::Status,
+    serde::{json::Json, Serialize},
+};
+use rocket_db_pools::Connection;
+
+use crate::{
+    db::AuthRsDatabase,
+    models::signing_key::{Jwk, SigningKey},
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[allow(unused)]
+#[get("/.well-known/jwks.json")]
+pub async fn get_jwks(db: Connection<AuthRsDatabase>) -> (Status, Option<Json<JwkSet>>) {
+    match SigningKey::get_published(&db).await {
+        Ok(signing_keys) => (
+            Status::Ok,
+            Some(Json(JwkSet {
+                keys: signing_keys.iter().map(|key| key.to_jwk()).collect(),
+            })),
+        ),
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to load published signing keys");
+            (Status::InternalServerError, None)
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use rocket::http::Status;
+use rocket::{get, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::{
+    auth::{
+        oidc::{get_group_claims, UserInfoClaims},
+        AuthEntity,
+    },
+    db::AuthRsDatabase,
+    models::{oauth_scope::OAuthScope, user::User},
+};
+
+#[allow(unused)]
+#[get("/userinfo")]
+pub async fn get_userinfo(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> (Status, Option<Json<UserInfoClaims>>) {
+    let token = match req_entity.token {
+        Some(token) if token.check_scope(OAuthScope::OpenId) => token,
+        _ => return (Status::Unauthorized, None),
+    };
+
+    match User::get_by_id(token.user_id, &db).await {
+        Ok(user) => {
+            if user.disabled {
+                return (Status::Forbidden, None);
+            }
+
+            let user = user.to_dto();
+            let groups =
+                match get_group_claims(&user, token.application_id, &token.scope, &db).await {
+                    Ok(groups) => groups,
+                    Err(_) => return (Status::InternalServerError, None),
+                };
+
+            (
+                Status::Ok,
+                Some(Json(UserInfoClaims::from_user(&user, &token.scope, groups))),
+            )
+        }
+        Err(_) => (Status::NotFound, None),
+    }
+}
@@ -1,7 +1,10 @@
+use mongodb::bson::doc;
 use rocket::{get, http::Status, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::passkey::Passkey;
+use crate::utils::pagination::{Page, PageQuery, Pagination};
 use crate::{
     auth::AuthEntity,
     db::AuthRsDatabase,
@@ -10,16 +13,22 @@ use crate::{
     utils::response::json_response,
 };
 
-#[get("/passkeys")]
+#[get("/passkeys?<query..>")]
 pub async fn list_passkeys(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<PasskeyDTO>>>) {
-    if !req_entity.is_user() || !req_entity.user.as_ref().unwrap().is_admin() {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<PasskeyDTO>>>) {
+    if !req_entity.has_permission(Permission::PasskeysRead) {
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    match process_list_passkeys(db).await {
+    let pagination = match query.pagination(&["createdAt", "name"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    match process_list_passkeys(db, &pagination).await {
         Ok(passkeys) => json_response(HttpResponse {
             status: 200,
             message: "Passkeys retrieved successfully".to_string(),
@@ -29,14 +38,15 @@ pub async fn list_passkeys(
     }
 }
 
-async fn process_list_passkeys(db: Connection<AuthRsDatabase>) -> ApiResult<Vec<PasskeyDTO>> {
+async fn process_list_passkeys(
+    db: Connection<AuthRsDatabase>,
+    pagination: &Pagination,
+) -> ApiResult<Page<PasskeyDTO>> {
     // Get the authenticated user
-    let passkeys = Passkey::get_all(&db, None)
+    let passkeys = Passkey::get_page(doc! {}, pagination, &db)
         .await
         .map_err(|e| ApiError::NotFound(format!("User not found: {}", e)))?
-        .iter()
-        .map(|passkey| passkey.to_dto())
-        .collect();
+        .map(|passkey| passkey.to_dto());
 
     // Get all passkey DTOs
     Ok(passkeys)
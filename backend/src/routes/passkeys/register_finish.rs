@@ -1,5 +1,6 @@
 use crate::models::passkey::Passkey;
 use crate::routes::auth::passkey::get_webauthn;
+use crate::routes::passkeys::register_start::REGISTRATIONS_NAMESPACE;
 use crate::{
     auth::AuthEntity,
     db::AuthRsDatabase,
@@ -8,8 +9,8 @@ use crate::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
     },
+    store,
     utils::response::json_response,
-    REGISTRATIONS,
 };
 use mongodb::bson::{DateTime, Uuid};
 use rocket::{
@@ -18,7 +19,7 @@ use rocket::{
     serde::{json::Json, Deserialize, Serialize},
 };
 use rocket_db_pools::Connection;
-use webauthn_rs::prelude::RegisterPublicKeyCredential;
+use webauthn_rs::prelude::{PasskeyRegistration, RegisterPublicKeyCredential};
 
 // DTO for passkey registration finish request
 #[derive(Deserialize)]
@@ -69,11 +70,12 @@ async fn process_register_finish(
     };
 
     // Get the registration state
-    let (user_id, reg_state) = REGISTRATIONS
-        .lock()
-        .await
-        .remove(&data.registration_id)
-        .ok_or(ApiError::InvalidState("Registration not found".to_string()))?;
+    let (user_id, reg_state) = store::take::<(Uuid, PasskeyRegistration)>(
+        REGISTRATIONS_NAMESPACE,
+        &data.registration_id.to_string(),
+    )
+    .await?
+    .ok_or(ApiError::InvalidState("Registration not found".to_string()))?;
 
     if user.id != user_id {
         return Err(ApiError::Unauthorized(
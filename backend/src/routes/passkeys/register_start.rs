@@ -6,7 +6,7 @@ use crate::{
     auth::AuthEntity,
     db::AuthRsDatabase,
     errors::{ApiError, ApiResult, AppError},
-    REGISTRATIONS,
+    store,
 };
 use mongodb::bson::Uuid;
 use rocket::{
@@ -17,6 +17,8 @@ use rocket::{
 use rocket_db_pools::Connection;
 use webauthn_rs::prelude::CreationChallengeResponse;
 
+pub const REGISTRATIONS_NAMESPACE: &str = "passkey-registrations";
+
 // Response for passkey registration start
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -78,10 +80,13 @@ async fn process_register_start(
 
     // Store registration state
     let registration_id = Uuid::new();
-    REGISTRATIONS
-        .lock()
-        .await
-        .insert(registration_id, (user.id, reg_state));
+    store::put(
+        REGISTRATIONS_NAMESPACE,
+        &registration_id.to_string(),
+        &(user.id, reg_state),
+        store::DEFAULT_TTL,
+    )
+    .await?;
 
     Ok(PasskeyRegisterStartResponse {
         registration_id,
@@ -6,6 +6,7 @@ use rocket::{
 };
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::role::Role;
 use crate::utils::response::json_response;
 use crate::DEFAULT_ROLE_ID;
@@ -37,7 +38,7 @@ pub async fn create_registration_token(
 ) -> (Status, Json<HttpResponse<RegistrationToken>>) {
     let data = data.into_inner();
 
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RegistrationTokensCreate) {
         return json_response(HttpResponse::forbidden(
             "Only admins can create registration tokens",
         ));
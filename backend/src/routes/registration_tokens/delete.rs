@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{delete, error, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::registration_token::RegistrationToken;
 use crate::utils::response::json_response;
 use crate::{
@@ -21,7 +22,7 @@ pub async fn delete_registration_token(
     req_entity: AuthEntity,
     id: &str,
 ) -> (Status, Json<HttpResponse<()>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RegistrationTokensDelete) {
         return json_response(HttpResponse::forbidden(
             "Only admins can delete registration tokens",
         ));
@@ -1,3 +1,5 @@
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -10,18 +12,24 @@ use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
 #[allow(unused)]
-#[get("/registration-tokens", format = "json")]
+#[get("/registration-tokens?<query..>", format = "json")]
 pub async fn get_all_registration_tokens(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<RegistrationToken>>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<RegistrationToken>>>) {
+    if !req_entity.has_permission(Permission::RegistrationTokensRead) {
         return json_response(HttpResponse::forbidden(
             "Only admins can view registration tokens",
         ));
     }
 
-    let registration_tokens = match RegistrationToken::get_all(&db, None).await {
+    let pagination = match query.pagination(&["createdAt", "maxUses"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let registration_tokens = match RegistrationToken::get_page(doc! {}, &pagination, &db).await {
         Ok(registration_tokens) => registration_tokens,
         Err(err) => return json_response(err.into()),
     };
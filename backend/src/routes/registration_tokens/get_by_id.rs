@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -17,7 +18,7 @@ pub async fn get_registration_token_by_id(
     req_entity: AuthEntity,
     id: &str,
 ) -> (Status, Json<HttpResponse<RegistrationToken>>) {
-    if !req_entity.is_user() || !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RegistrationTokensRead) {
         return json_response(HttpResponse::forbidden(
             "Only admins can get registration tokens",
         ));
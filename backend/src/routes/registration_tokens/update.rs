@@ -1,3 +1,4 @@
+use crate::models::permission::Permission;
 use crate::models::registration_token::{
     RegistrationToken, RegistrationTokenError, RegistrationTokenResult,
 };
@@ -187,7 +188,7 @@ async fn update_registration_token_internal(
     data: UpdateRegistrationTokenData,
 ) -> RegistrationTokenResult<RegistrationToken> {
     // Basic permission checks
-    if !req_entity.is_user() || !req_entity.user.clone().unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RegistrationTokensUpdate) {
         return Err(
             ApiError::Forbidden("Only admins can update registration tokens".to_string()).into(),
         );
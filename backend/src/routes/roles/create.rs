@@ -1,3 +1,4 @@
+use mongodb::bson::Uuid;
 use rocket::http::Status;
 use rocket::{
     error, post,
@@ -5,10 +6,11 @@ use rocket::{
 };
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
-    db::AuthRsDatabase,
+    db::{get_main_db, AuthRsDatabase},
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
@@ -20,6 +22,10 @@ use crate::{
 #[serde(crate = "rocket::serde")]
 pub struct CreateRoleData {
     name: String,
+    #[serde(default)]
+    permissions: Vec<Permission>,
+    #[serde(default)]
+    parents: Vec<Uuid>,
 }
 
 #[allow(unused)]
@@ -35,21 +41,50 @@ pub async fn create_role(
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    if !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RolesCreate) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
+    if data
+        .permissions
+        .iter()
+        .any(|permission| !req_entity.has_permission(*permission))
+    {
+        return json_response(HttpResponse::forbidden(
+            "Cannot grant permissions you don't have",
+        ));
+    }
+
     if Role::get_by_name(&data.name, &db).await.is_ok() {
         return json_response(HttpResponse::bad_request(
             "Role with that name already exists",
         ));
     }
 
-    let role = match Role::new(data.name) {
+    let role = match Role::new(data.name, data.permissions, data.parents) {
         Ok(role) => role,
         Err(err) => return json_response(err.into()),
     };
 
+    let main_db = get_main_db(&db);
+    if let Err(err) = role.validate_parents(&role.parents, &main_db).await {
+        return json_response(err.into());
+    }
+
+    match Role::get_permissions(&role.parents, &main_db).await {
+        Ok(inherited_permissions) => {
+            if inherited_permissions
+                .iter()
+                .any(|permission| !req_entity.has_permission(*permission))
+            {
+                return json_response(HttpResponse::forbidden(
+                    "Cannot inherit permissions you don't have",
+                ));
+            }
+        }
+        Err(err) => return json_response(err.into()),
+    }
+
     match role.insert(&db).await {
         Ok(role) => {
             match AuditLog::new(
@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{delete, error, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::parse_uuid::parse_uuid;
 use crate::utils::response::json_response;
 use crate::{
@@ -25,7 +26,7 @@ pub async fn delete_role(
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    if !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::RolesDelete) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
@@ -1,7 +1,9 @@
+use mongodb::bson::doc;
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::utils::pagination::{starts_with, Page, PageQuery};
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -10,16 +12,27 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/roles", format = "json")]
+#[get("/roles?<query..>", format = "json")]
 pub async fn get_all_roles(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<Role>>>) {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<Role>>>) {
     if !req_entity.is_user() {
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    match Role::get_all(&db, None).await {
+    let pagination = match query.pagination(&["createdAt", "name"], "createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let filter = match &query.name {
+        Some(name) => starts_with("name", name.trim()),
+        None => doc! {},
+    };
+
+    match Role::get_page(filter, &pagination, &db).await {
         Ok(roles) => json_response(HttpResponse {
             status: 200,
             message: "Successfully retrieved all roles".to_string(),
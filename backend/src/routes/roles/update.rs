@@ -1,28 +1,31 @@
+use crate::models::permission::Permission;
 use crate::utils::parse_uuid::parse_uuid;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
-    db::AuthRsDatabase,
+    db::{get_main_db, AuthRsDatabase},
     errors::ApiError,
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
-        role::Role,
-        role::RoleResult,
+        role::{Role, RoleError, RoleResult},
     },
 };
+use mongodb::bson::Uuid;
 use rocket::http::Status;
 use rocket::{
     error, patch,
     serde::{json::Json, Deserialize},
 };
 use rocket_db_pools::Connection;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct UpdateRoleData {
     name: Option<String>,
+    permissions: Option<Vec<Permission>>,
+    parents: Option<Vec<Uuid>>,
 }
 
 #[allow(unused)]
@@ -74,11 +77,90 @@ impl RoleUpdate {
         }
     }
 
-    async fn save(
-        self,
+    fn update_permissions(
+        &mut self,
+        new_permissions: Vec<Permission>,
+        req_entity: &AuthEntity,
+    ) -> RoleResult<()> {
+        let mut new_permissions = new_permissions;
+        let mut seen = HashSet::new();
+        new_permissions.retain(|permission| seen.insert(*permission));
+        if self.role.permissions == new_permissions {
+            return Ok(());
+        }
+
+        // Only permissions the requester holds can be added or removed
+        let changed = new_permissions
+            .iter()
+            .filter(|permission| !self.role.permissions.contains(permission))
+            .chain(
+                self.role
+                    .permissions
+                    .iter()
+                    .filter(|permission| !new_permissions.contains(permission)),
+            )
+            .any(|permission| !req_entity.has_permission(*permission));
+        if changed {
+            return Err(RoleError::MissingPermissions);
+        }
+
+        let join = |permissions: &[Permission]| {
+            permissions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let old_permissions = join(&self.role.permissions);
+        self.update_field("permissions", old_permissions, join(&new_permissions));
+        self.role.permissions = new_permissions;
+        Ok(())
+    }
+
+    async fn update_parents(
+        &mut self,
+        new_parents: Vec<Uuid>,
         db: &Connection<AuthRsDatabase>,
-        req_user_id: mongodb::bson::Uuid,
-    ) -> RoleResult<Role> {
+        req_entity: &AuthEntity,
+    ) -> RoleResult<()> {
+        let mut new_parents = new_parents;
+        let mut seen = HashSet::new();
+        new_parents.retain(|parent| seen.insert(*parent));
+        if self.role.parents == new_parents {
+            return Ok(());
+        }
+
+        let db = get_main_db(db);
+        self.role.validate_parents(&new_parents, &db).await?;
+
+        // Inheriting from a role grants its permissions, so the same rule as for permissions applies
+        let added_parents = new_parents
+            .iter()
+            .filter(|parent| !self.role.parents.contains(parent))
+            .copied()
+            .collect::<Vec<_>>();
+        let inherited_permissions = Role::get_permissions(&added_parents, &db).await?;
+        if inherited_permissions
+            .iter()
+            .any(|permission| !req_entity.has_permission(*permission))
+        {
+            return Err(RoleError::MissingPermissions);
+        }
+
+        let join = |parents: &[Uuid]| {
+            parents
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let old_parents = join(&self.role.parents);
+        self.update_field("parents", old_parents, join(&new_parents));
+        self.role.parents = new_parents;
+        Ok(())
+    }
+
+    async fn save(self, db: &Connection<AuthRsDatabase>, req_user_id: Uuid) -> RoleResult<Role> {
         if !self.modified {
             return Ok(self.role);
         }
@@ -116,10 +198,7 @@ async fn update_role_internal(
         return Err(ApiError::Forbidden("Forbidden".to_string()).into());
     }
 
-    let req_user = req_entity
-        .user()
-        .map_err(|_| ApiError::Forbidden("Forbidden".to_string()))?;
-    if !req_user.is_admin() {
+    if !req_entity.has_permission(Permission::RolesUpdate) {
         return Err(ApiError::Forbidden("Missing permissions!".to_string()).into());
     }
 
@@ -140,6 +219,14 @@ async fn update_role_internal(
         update.update_name(name);
     }
 
+    if let Some(permissions) = data.permissions {
+        update.update_permissions(permissions, &req_entity)?;
+    }
+
+    if let Some(parents) = data.parents {
+        update.update_parents(parents, &db, &req_entity).await?;
+    }
+
     // Save changes
     update.save(&db, req_entity.user_id).await
 }
@@ -0,0 +1,431 @@
+use mongodb::bson::{doc, Document, Uuid};
+use rocket::{
+    delete, error, get,
+    http::Status,
+    patch, post, put,
+    serde::json::{self, Json},
+};
+use rocket_db_pools::Connection;
+use std::collections::{HashMap, HashSet};
+
+use super::{parse_body, parse_id};
+use crate::{
+    auth::scim::ScimClient,
+    db::{get_main_db, AuthRsDatabase},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        permission::Permission,
+        role::{Role, RoleError},
+        user::User,
+    },
+    scim::{
+        group::ScimGroup, is_excluded, patch::PatchRequest, EqFilter, ListQuery, ListResponse,
+        Page, ResourceQuery, ScimError, ScimJson, ScimResult,
+    },
+    utils::pagination::Pagination,
+    SYSTEM_USER_ID,
+};
+
+/// Supports `displayName eq` and `id eq` filters
+fn parse_filter(filter: Option<&str>) -> Result<Document, ScimError> {
+    let Some(filter) = filter else {
+        return Ok(doc! {});
+    };
+
+    let filter = EqFilter::parse(filter)?;
+    match filter.attribute.as_str() {
+        "displayname" => Ok(doc! {
+            "name": filter.value
+        }),
+        // An id that isn't a UUID matches no group
+        "id" => Ok(doc! {
+            "_id": Uuid::parse_str(&filter.value).ok()
+        }),
+        attribute => Err(ScimError::bad_request(
+            "invalidFilter",
+            format!("Filtering by {} is not supported", attribute),
+        )),
+    }
+}
+
+async fn get_role(id: &str, db: &Connection<AuthRsDatabase>) -> Result<Role, ScimError> {
+    let id = parse_id("Group", id)?;
+    Ok(Role::get_by_id(id, db).await?)
+}
+
+/// Users that have one of the roles, without the system user
+async fn get_members(
+    role_ids: &[Uuid],
+    db: &Connection<AuthRsDatabase>,
+) -> Result<Vec<User>, ScimError> {
+    let mut users = User::get_by_roles(role_ids, db).await?;
+    users.retain(|user| user.id != *SYSTEM_USER_ID);
+    Ok(users)
+}
+
+fn to_group(role: &Role, users: &[User]) -> ScimGroup {
+    let members = users
+        .iter()
+        .filter(|user| user.roles.contains(&role.id))
+        .collect::<Vec<_>>();
+    ScimGroup::from_role(role, &members)
+}
+
+/// The user ids of the members, the system user can't be a member
+fn parse_members(data: &ScimGroup) -> Result<HashSet<Uuid>, ScimError> {
+    data.members
+        .iter()
+        .map(|member| match Uuid::parse_str(&member.value) {
+            Ok(id) if id != *SYSTEM_USER_ID => Ok(id),
+            _ => Err(ScimError::bad_request(
+                "invalidValue",
+                format!("User {} not found", member.value),
+            )),
+        })
+        .collect()
+}
+
+fn join_ids(ids: &HashSet<Uuid>) -> String {
+    let mut ids = ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+    ids.sort();
+    ids.join(",")
+}
+
+fn audit_log(
+    role_id: Uuid,
+    action: AuditLogAction,
+    reason: &str,
+    old_values: Option<HashMap<String, String>>,
+    new_values: Option<HashMap<String, String>>,
+) -> AuditLog {
+    AuditLog::new(
+        role_id.to_string(),
+        AuditLogEntityType::Role,
+        action,
+        reason.to_string(),
+        *SYSTEM_USER_ID,
+        old_values,
+        new_values,
+    )
+}
+
+#[allow(unused)]
+#[get("/Groups?<query..>")]
+pub async fn get_groups(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    query: ListQuery<'_>,
+) -> ScimResult<ListResponse<ScimGroup>> {
+    client?;
+
+    let page = Page::new(&query);
+    let filter = parse_filter(query.filter)?;
+
+    let (roles, total) = if page.count == 0 {
+        (Vec::new(), Role::count(filter, &db).await?)
+    } else {
+        let pagination = Pagination::with_offset(page.skip(), page.count);
+        let roles = Role::get_page(filter, &pagination, &db).await?;
+        (roles.items, roles.total)
+    };
+
+    // The default role has every user as member, clients can skip the members
+    let users = if is_excluded(query.excluded_attributes, "members") {
+        Vec::new()
+    } else {
+        let role_ids = roles.iter().map(|role| role.id).collect::<Vec<_>>();
+        get_members(&role_ids, &db).await?
+    };
+
+    let resources = roles.iter().map(|role| to_group(role, &users)).collect();
+    Ok(ScimJson(
+        Status::Ok,
+        ListResponse::new(resources, total, &page),
+    ))
+}
+
+#[allow(unused)]
+#[get("/Groups/<id>?<query..>")]
+pub async fn get_group_by_id(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+    query: ResourceQuery<'_>,
+) -> ScimResult<ScimGroup> {
+    client?;
+
+    let role = get_role(id, &db).await?;
+    let users = if is_excluded(query.excluded_attributes, "members") {
+        Vec::new()
+    } else {
+        get_members(&[role.id], &db).await?
+    };
+
+    Ok(ScimJson(Status::Ok, to_group(&role, &users)))
+}
+
+#[allow(unused)]
+#[post("/Groups", data = "<data>")]
+pub async fn create_group(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    data: Result<Json<ScimGroup>, json::Error<'_>>,
+) -> ScimResult<ScimGroup> {
+    client?;
+
+    let data = parse_body(data)?;
+    let name = data.display_name.trim().to_string();
+    if name.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "displayName is required",
+        ));
+    }
+    if Role::get_by_name(&name, &db).await.is_ok() {
+        return Err(RoleError::NameAlreadyExists(name).into());
+    }
+
+    let members = parse_members(&data)?;
+    validate_new_members(&members, &db).await?;
+
+    // Permissions are granted to the role in the dashboard, provisioning only manages membership
+    let role = Role::new(name, Vec::new(), Vec::new())?.insert(&db).await?;
+    let member_ids = members.iter().copied().collect::<Vec<_>>();
+    User::add_role_to_users(role.id, &member_ids, &db).await?;
+
+    if let Err(err) = audit_log(
+        role.id,
+        AuditLogAction::Create,
+        "Role created via SCIM.",
+        None,
+        Some(HashMap::from([("members".to_string(), join_ids(&members))])),
+    )
+    .insert(&db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+
+    let users = get_members(&[role.id], &db).await?;
+    Ok(ScimJson(Status::Created, to_group(&role, &users)))
+}
+
+#[allow(unused)]
+#[put("/Groups/<id>", data = "<data>")]
+pub async fn replace_group(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+    data: Result<Json<ScimGroup>, json::Error<'_>>,
+) -> ScimResult<ScimGroup> {
+    client?;
+
+    let role = get_role(id, &db).await?;
+    let data = parse_body(data)?;
+    let users = get_members(&[role.id], &db).await?;
+
+    let role = save_group(role, &users, data, &db).await?;
+    let users = get_members(&[role.id], &db).await?;
+    Ok(ScimJson(Status::Ok, to_group(&role, &users)))
+}
+
+#[allow(unused)]
+#[patch("/Groups/<id>", data = "<data>")]
+pub async fn patch_group(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+    data: Result<Json<PatchRequest>, json::Error<'_>>,
+) -> ScimResult<ScimGroup> {
+    client?;
+
+    let role = get_role(id, &db).await?;
+    let patch = parse_body(data)?;
+    let users = get_members(&[role.id], &db).await?;
+
+    // The operations are applied to the SCIM representation, which is then saved like a PUT
+    let mut resource = serde_json::to_value(to_group(&role, &users))
+        .map_err(|err| ScimError::internal(err.to_string()))?;
+    patch.apply(&mut resource)?;
+    let data = serde_json::from_value::<ScimGroup>(resource)
+        .map_err(|err| ScimError::bad_request("invalidValue", err.to_string()))?;
+
+    let role = save_group(role, &users, data, &db).await?;
+    let users = get_members(&[role.id], &db).await?;
+    Ok(ScimJson(Status::Ok, to_group(&role, &users)))
+}
+
+#[allow(unused)]
+#[delete("/Groups/<id>")]
+pub async fn delete_group(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+) -> Result<Status, ScimError> {
+    client?;
+
+    let role = get_role(id, &db).await?;
+    let role = role.delete(&db).await?;
+
+    if let Err(err) = audit_log(
+        role.id,
+        AuditLogAction::Delete,
+        "Role deleted via SCIM.",
+        None,
+        None,
+    )
+    .insert(&db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+
+    Ok(Status::NoContent)
+}
+
+async fn validate_new_members(
+    members: &HashSet<Uuid>,
+    db: &Connection<AuthRsDatabase>,
+) -> Result<(), ScimError> {
+    if members.is_empty() {
+        return Ok(());
+    }
+
+    let member_ids = members.iter().copied().collect::<Vec<_>>();
+    let filter = doc! {
+        "_id": {
+            "$in": &member_ids
+        }
+    };
+    if User::count(filter, db).await? != members.len() as u64 {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "One or more members are not existing users",
+        ));
+    }
+    Ok(())
+}
+
+/// Membership of the system roles and of roles granting permissions, also inherited ones,
+/// stays under the control of the dashboard
+fn ensure_provisionable(role: &Role, permissions: &HashSet<Permission>) -> Result<(), ScimError> {
+    if role.system {
+        return Err(ScimError::bad_request(
+            "mutability",
+            "Membership of system groups can't be provisioned",
+        ));
+    }
+    if !permissions.is_empty() {
+        return Err(ScimError::bad_request(
+            "mutability",
+            "Membership of groups with permissions can't be provisioned",
+        ));
+    }
+    Ok(())
+}
+
+/// Renames the role and applies the membership of `data` to the roles of the users
+async fn save_group(
+    mut role: Role,
+    users: &[User],
+    data: ScimGroup,
+    db: &Connection<AuthRsDatabase>,
+) -> Result<Role, ScimError> {
+    let mut old_values = HashMap::new();
+    let mut new_values = HashMap::new();
+
+    let current = users.iter().map(|user| user.id).collect::<HashSet<_>>();
+    let members = parse_members(&data)?;
+    let added = members.difference(&current).copied().collect::<Vec<_>>();
+    let removed = current.difference(&members).copied().collect::<Vec<_>>();
+    let membership_changed = !added.is_empty() || !removed.is_empty();
+
+    // Everything is validated before the first write
+    if membership_changed {
+        let permissions = Role::get_permissions(&[role.id], &get_main_db(db)).await?;
+        ensure_provisionable(&role, &permissions)?;
+        validate_new_members(&added.iter().copied().collect(), db).await?;
+    }
+
+    let name = data.display_name.trim().to_string();
+    if role.name != name {
+        if name.is_empty() {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                "displayName is required",
+            ));
+        }
+        if Role::get_by_name(&name, db).await.is_ok() {
+            return Err(RoleError::NameAlreadyExists(name).into());
+        }
+        old_values.insert("name".to_string(), role.name.clone());
+        new_values.insert("name".to_string(), name.clone());
+        role.name = name;
+        role.update(db).await?;
+    }
+
+    if membership_changed {
+        User::add_role_to_users(role.id, &added, db).await?;
+        User::remove_role_from_users(role.id, &removed, db).await?;
+
+        old_values.insert("members".to_string(), join_ids(&current));
+        new_values.insert("members".to_string(), join_ids(&members));
+    }
+
+    if !new_values.is_empty() {
+        if let Err(err) = audit_log(
+            role.id,
+            AuditLogAction::Update,
+            "Role updated via SCIM.",
+            Some(old_values),
+            Some(new_values),
+        )
+        .insert(db)
+        .await
+        {
+            error!("Failed to create audit log: {}", err);
+        }
+    }
+
+    Ok(role)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ADMIN_ROLE_ID, DEFAULT_ROLE_ID};
+
+    #[test]
+    fn refuses_system_groups() {
+        for (id, name) in [(*ADMIN_ROLE_ID, "Admin"), (*DEFAULT_ROLE_ID, "Default")] {
+            let role = Role::new_system(id, name.to_string()).unwrap();
+            assert!(ensure_provisionable(&role, &HashSet::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn refuses_groups_with_permissions() {
+        let role = Role::new(
+            "Support".to_string(),
+            vec![Permission::UsersUpdate],
+            Vec::new(),
+        )
+        .unwrap();
+        let permissions = HashSet::from([Permission::UsersUpdate]);
+        assert!(ensure_provisionable(&role, &permissions).is_err());
+    }
+
+    #[test]
+    fn refuses_groups_inheriting_permissions() {
+        // The role grants nothing itself, its parent does
+        let role = Role::new("Support".to_string(), Vec::new(), vec![Uuid::new()]).unwrap();
+        let permissions = HashSet::from([Permission::RolesAssign]);
+        assert!(ensure_provisionable(&role, &permissions).is_err());
+    }
+
+    #[test]
+    fn allows_groups_without_permissions() {
+        let role = Role::new("Engineering".to_string(), Vec::new(), Vec::new()).unwrap();
+        assert!(ensure_provisionable(&role, &HashSet::new()).is_ok());
+    }
+}
@@ -0,0 +1,31 @@
+use mongodb::bson::Uuid;
+use rocket::serde::json::{self, Json};
+use rocket_db_pools::Connection;
+use std::collections::HashMap;
+
+use crate::{db::AuthRsDatabase, models::role::Role, scim::ScimError};
+
+pub mod groups;
+pub mod service_provider_config;
+pub mod users;
+
+/// Unwraps a JSON body, malformed bodies are SCIM `invalidSyntax` errors
+fn parse_body<T>(data: Result<Json<T>, json::Error<'_>>) -> Result<T, ScimError> {
+    data.map(Json::into_inner)
+        .map_err(|err| ScimError::bad_request("invalidSyntax", err.to_string()))
+}
+
+/// Resource ids are UUIDs, anything else can't exist
+fn parse_id(resource: &str, id: &str) -> Result<Uuid, ScimError> {
+    Uuid::parse_str(id).map_err(|_| ScimError::not_found(format!("{} {} not found", resource, id)))
+}
+
+async fn get_role_names(
+    db: &Connection<AuthRsDatabase>,
+) -> Result<HashMap<Uuid, String>, ScimError> {
+    Ok(Role::get_all(db, None)
+        .await?
+        .into_iter()
+        .map(|role| (role.id, role.name))
+        .collect())
+}
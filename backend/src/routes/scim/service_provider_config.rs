@@ -0,0 +1,39 @@
+use rocket::{get, http::Status};
+use serde_json::{json, Value};
+
+use crate::{
+    auth::scim::ScimClient,
+    scim::{ScimError, ScimJson, ScimResult, MAX_PAGE_SIZE, SERVICE_PROVIDER_CONFIG_SCHEMA},
+};
+
+#[allow(unused)]
+#[get("/ServiceProviderConfig")]
+pub async fn get_service_provider_config(
+    client: Result<ScimClient, ScimError>,
+) -> ScimResult<Value> {
+    client?;
+
+    Ok(ScimJson(
+        Status::Ok,
+        json!({
+            "schemas": [SERVICE_PROVIDER_CONFIG_SCHEMA],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": MAX_PAGE_SIZE },
+            "changePassword": { "supported": true },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Bearer Token",
+                "description": "Authentication with the provisioning token from SCIM_TOKEN",
+                "primary": true
+            }],
+            "meta": {
+                "resourceType": "ServiceProviderConfig",
+                "location": crate::scim::get_location("ServiceProviderConfig", "")
+                    .trim_end_matches('/')
+            }
+        }),
+    ))
+}
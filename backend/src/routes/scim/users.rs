@@ -0,0 +1,460 @@
+use mongodb::bson::{doc, Document, Uuid};
+use rocket::{
+    delete, error, get,
+    http::Status,
+    patch, post, put,
+    serde::json::{self, Json},
+};
+use rocket_db_pools::Connection;
+use std::collections::{HashMap, HashSet};
+
+use super::{get_role_names, parse_body, parse_id};
+use crate::{
+    auth::scim::ScimClient,
+    db::{get_main_db, AuthRsDatabase},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        permission::Permission,
+        role::Role,
+        session::Session,
+        user::User,
+        user_error::UserError,
+    },
+    scim::{
+        patch::PatchRequest, user::ScimUser, EqFilter, ListQuery, ListResponse, Page, ScimError,
+        ScimJson, ScimResult,
+    },
+    utils::pagination::Pagination,
+    SYSTEM_USER_ID,
+};
+
+/// Supports `userName eq`, `emails.value eq` and `id eq` filters
+fn parse_filter(filter: Option<&str>) -> Result<Document, ScimError> {
+    let Some(filter) = filter else {
+        return Ok(doc! {});
+    };
+
+    let filter = EqFilter::parse(filter)?;
+    match filter.attribute.as_str() {
+        "username" | "emails" | "emails.value" => Ok(doc! {
+            "email": filter.value.to_lowercase()
+        }),
+        // An id that isn't a UUID matches no user
+        "id" => Ok(doc! {
+            "_id": Uuid::parse_str(&filter.value).ok()
+        }),
+        attribute => Err(ScimError::bad_request(
+            "invalidFilter",
+            format!("Filtering by {} is not supported", attribute),
+        )),
+    }
+}
+
+/// The system user is not visible to the provisioning client
+async fn get_user(id: &str, db: &Connection<AuthRsDatabase>) -> Result<User, ScimError> {
+    let id = parse_id("User", id)?;
+    if id == *SYSTEM_USER_ID {
+        return Err(ScimError::not_found(format!("User {} not found", id)));
+    }
+
+    Ok(User::get_full_by_id(id, db).await?)
+}
+
+/// Accounts holding any permission stay under the control of the dashboard, so the provisioning
+/// client can't take them over or lock them out
+fn ensure_unprivileged(permissions: &HashSet<Permission>, change: &str) -> Result<(), ScimError> {
+    if !permissions.is_empty() {
+        return Err(ScimError::bad_request(
+            "mutability",
+            format!("Users with permissions can't be {} via SCIM", change),
+        ));
+    }
+    Ok(())
+}
+
+/// The first change of `data` that is only allowed for users without permissions
+fn get_privileged_change(user: &User, email: &str, data: &ScimUser) -> Option<&'static str> {
+    if user.email != email {
+        Some("given a new email")
+    } else if data.password.is_some() {
+        Some("given a new password")
+    } else if !data.active && !user.disabled {
+        Some("deactivated")
+    } else {
+        None
+    }
+}
+
+async fn get_permissions(
+    user: &User,
+    db: &Connection<AuthRsDatabase>,
+) -> Result<HashSet<Permission>, ScimError> {
+    Ok(Role::get_permissions(&user.roles, &get_main_db(db)).await?)
+}
+
+fn validate_user(data: &ScimUser) -> Result<(String, String, String), ScimError> {
+    let email = data.email();
+    if !email.contains('@') || !email.contains('.') || email.len() < 5 {
+        return Err(UserError::InvalidEmail.into());
+    }
+
+    let first_name = data.first_name();
+    if first_name.is_empty() {
+        return Err(UserError::FirstNameRequired.into());
+    }
+
+    Ok((email, first_name, data.last_name()))
+}
+
+fn audit_log(
+    user_id: Uuid,
+    action: AuditLogAction,
+    reason: &str,
+    old_values: Option<HashMap<String, String>>,
+    new_values: Option<HashMap<String, String>>,
+) -> AuditLog {
+    AuditLog::new(
+        user_id.to_string(),
+        AuditLogEntityType::User,
+        action,
+        reason.to_string(),
+        *SYSTEM_USER_ID,
+        old_values,
+        new_values,
+    )
+}
+
+async fn to_response(
+    user: &User,
+    status: Status,
+    db: &Connection<AuthRsDatabase>,
+) -> ScimResult<ScimUser> {
+    let role_names = get_role_names(db).await?;
+    Ok(ScimJson(status, ScimUser::from_user(user, &role_names)))
+}
+
+#[allow(unused)]
+#[get("/Users?<query..>")]
+pub async fn get_users(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    query: ListQuery<'_>,
+) -> ScimResult<ListResponse<ScimUser>> {
+    client?;
+
+    let page = Page::new(&query);
+    let filter = doc! {
+        "$and": [
+            parse_filter(query.filter)?,
+            { "_id": { "$ne": *SYSTEM_USER_ID } }
+        ]
+    };
+
+    let (users, total) = if page.count == 0 {
+        (Vec::new(), User::count(filter, &db).await?)
+    } else {
+        let pagination = Pagination::with_offset(page.skip(), page.count);
+        let users = User::get_page(filter, &pagination, &db).await?;
+        (users.items, users.total)
+    };
+
+    let role_names = get_role_names(&db).await?;
+    let resources = users
+        .iter()
+        .map(|user| ScimUser::from_user(user, &role_names))
+        .collect();
+    Ok(ScimJson(
+        Status::Ok,
+        ListResponse::new(resources, total, &page),
+    ))
+}
+
+#[allow(unused)]
+#[get("/Users/<id>")]
+pub async fn get_user_by_id(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+) -> ScimResult<ScimUser> {
+    client?;
+
+    let user = get_user(id, &db).await?;
+    to_response(&user, Status::Ok, &db).await
+}
+
+#[allow(unused)]
+#[post("/Users", data = "<data>")]
+pub async fn create_user(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    data: Result<Json<ScimUser>, json::Error<'_>>,
+) -> ScimResult<ScimUser> {
+    client?;
+
+    let data = parse_body(data)?;
+    let (email, first_name, last_name) = validate_user(&data)?;
+
+    if User::get_by_email(&email, &db).await.is_ok() {
+        return Err(UserError::EmailAlreadyExists(email).into());
+    }
+
+    // Without a password the user can't log in until the password is reset
+    let mut user = User::with_password_hash(email, String::new(), first_name, last_name);
+    // The identity provider owns the email address
+    user.email_verified = true;
+    user.disabled = !data.active;
+    if let Some(password) = &data.password {
+        user.validate_new_password(password).await?;
+        user.set_password(password).await?;
+    }
+
+    let user = user.insert(&db).await?;
+
+    if let Err(err) = audit_log(
+        user.id,
+        AuditLogAction::Create,
+        "User created via SCIM.",
+        None,
+        None,
+    )
+    .insert(&db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+
+    to_response(&user, Status::Created, &db).await
+}
+
+#[allow(unused)]
+#[put("/Users/<id>", data = "<data>")]
+pub async fn replace_user(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+    data: Result<Json<ScimUser>, json::Error<'_>>,
+) -> ScimResult<ScimUser> {
+    client?;
+
+    let user = get_user(id, &db).await?;
+    let data = parse_body(data)?;
+
+    let user = save_user(user, data, &db).await?;
+    to_response(&user, Status::Ok, &db).await
+}
+
+#[allow(unused)]
+#[patch("/Users/<id>", data = "<data>")]
+pub async fn patch_user(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+    data: Result<Json<PatchRequest>, json::Error<'_>>,
+) -> ScimResult<ScimUser> {
+    client?;
+
+    let user = get_user(id, &db).await?;
+    let patch = parse_body(data)?;
+
+    // The operations are applied to the SCIM representation, which is then saved like a PUT
+    let role_names = get_role_names(&db).await?;
+    let mut resource = serde_json::to_value(ScimUser::from_user(&user, &role_names))
+        .map_err(|err| ScimError::internal(err.to_string()))?;
+    patch.apply(&mut resource)?;
+    let data = serde_json::from_value::<ScimUser>(resource)
+        .map_err(|err| ScimError::bad_request("invalidValue", err.to_string()))?;
+
+    let user = save_user(user, data, &db).await?;
+    to_response(&user, Status::Ok, &db).await
+}
+
+#[allow(unused)]
+#[delete("/Users/<id>")]
+pub async fn delete_user(
+    db: Connection<AuthRsDatabase>,
+    client: Result<ScimClient, ScimError>,
+    id: &str,
+) -> Result<Status, ScimError> {
+    client?;
+
+    let user = get_user(id, &db).await?;
+    ensure_unprivileged(&get_permissions(&user, &db).await?, "deleted")?;
+    let user = user.delete(&db).await?;
+
+    if let Err(err) = audit_log(
+        user.id,
+        AuditLogAction::Delete,
+        "User deleted via SCIM.",
+        None,
+        None,
+    )
+    .insert(&db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// Applies the attributes of `data` to the user, `active` maps to `User::disable` and `User::enable`
+async fn save_user(
+    mut user: User,
+    data: ScimUser,
+    db: &Connection<AuthRsDatabase>,
+) -> Result<User, ScimError> {
+    let (email, first_name, last_name) = validate_user(&data)?;
+    if let Some(change) = get_privileged_change(&user, &email, &data) {
+        ensure_unprivileged(&get_permissions(&user, db).await?, change)?;
+    }
+
+    let mut old_values = HashMap::new();
+    let mut new_values = HashMap::new();
+
+    if user.email != email {
+        if User::get_by_email(&email, db).await.is_ok() {
+            return Err(UserError::EmailAlreadyExists(email).into());
+        }
+        old_values.insert("email".to_string(), user.email.clone());
+        new_values.insert("email".to_string(), email.clone());
+        user.email = email;
+        user.email_verified = true;
+    }
+
+    if user.first_name != first_name {
+        old_values.insert("first_name".to_string(), user.first_name.clone());
+        new_values.insert("first_name".to_string(), first_name.clone());
+        user.first_name = first_name;
+    }
+
+    if user.last_name != last_name {
+        old_values.insert("last_name".to_string(), user.last_name.clone());
+        new_values.insert("last_name".to_string(), last_name.clone());
+        user.last_name = last_name;
+    }
+
+    if let Some(password) = &data.password {
+        user.validate_new_password(password).await?;
+        user.set_password(password).await?;
+        old_values.insert("password".to_string(), "***********".to_string());
+        new_values.insert("password".to_string(), "***********".to_string());
+    }
+
+    if !new_values.is_empty() {
+        user.update(db).await?;
+    }
+
+    if user.disabled == data.active {
+        if data.active {
+            user.enable(db).await?;
+        } else {
+            user.disable(db).await?;
+            // A disabled user is logged out of all devices
+            Session::delete_all_matching(doc! { "userId": user.id }, db)
+                .await
+                .map_err(|err| {
+                    ScimError::internal(format!("Failed to revoke sessions: {}", err))
+                })?;
+        }
+        old_values.insert("disabled".to_string(), user.disabled.to_string());
+        new_values.insert("disabled".to_string(), (!data.active).to_string());
+        user.disabled = !data.active;
+    }
+
+    if !new_values.is_empty() {
+        if let Err(err) = audit_log(
+            user.id,
+            AuditLogAction::Update,
+            "User updated via SCIM.",
+            Some(old_values),
+            Some(new_values),
+        )
+        .insert(db)
+        .await
+        {
+            error!("Failed to create audit log: {}", err);
+        }
+    }
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User::with_password_hash(
+            "jane@example.com".to_string(),
+            String::new(),
+            "Jane".to_string(),
+            "Doe".to_string(),
+        )
+    }
+
+    fn scim_user(value: serde_json::Value) -> ScimUser {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn credential_changes_and_deactivation_are_privileged() {
+        let user = user();
+
+        let data = scim_user(serde_json::json!({ "userName": "jane@example.com" }));
+        assert_eq!(
+            get_privileged_change(&user, "jane@example.com", &data),
+            None
+        );
+        assert_eq!(
+            get_privileged_change(&user, "mallory@example.com", &data),
+            Some("given a new email")
+        );
+
+        let data = scim_user(serde_json::json!({
+            "userName": "jane@example.com",
+            "password": "correct horse battery staple"
+        }));
+        assert_eq!(
+            get_privileged_change(&user, "jane@example.com", &data),
+            Some("given a new password")
+        );
+
+        let data = scim_user(serde_json::json!({
+            "userName": "jane@example.com",
+            "active": false
+        }));
+        assert_eq!(
+            get_privileged_change(&user, "jane@example.com", &data),
+            Some("deactivated")
+        );
+    }
+
+    #[test]
+    fn reactivation_is_not_privileged() {
+        let mut user = user();
+        user.disabled = true;
+
+        let data = scim_user(serde_json::json!({
+            "userName": "jane@example.com",
+            "active": true
+        }));
+        assert_eq!(
+            get_privileged_change(&user, "jane@example.com", &data),
+            None
+        );
+    }
+
+    #[test]
+    fn refuses_users_with_any_permission() {
+        assert!(ensure_unprivileged(&HashSet::new(), "deleted").is_ok());
+        // Not only admins, a single permission of a custom role is enough
+        for permission in [
+            Permission::UsersUpdate,
+            Permission::RolesAssign,
+            Permission::SettingsUpdate,
+        ] {
+            assert!(ensure_unprivileged(&HashSet::from([permission]), "deleted").is_err());
+        }
+        assert!(ensure_unprivileged(&HashSet::from(Permission::ALL), "deleted").is_err());
+    }
+}
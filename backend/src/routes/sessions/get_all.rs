@@ -0,0 +1,42 @@
+use rocket::http::Status;
+use rocket::{get, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::utils::pagination::{Page, PageQuery};
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        http_response::HttpResponse,
+        session::{Session, SessionDTO},
+    },
+};
+
+#[allow(unused)]
+#[get("/users/@me/sessions?<query..>", format = "json")]
+pub async fn get_all_sessions(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<SessionDTO>>>) {
+    // OAuth applications are not allowed to see the login sessions of a user
+    if !req_entity.is_user() {
+        return json_response(HttpResponse::forbidden("Forbidden"));
+    }
+
+    let pagination = match query.pagination(&["lastSeenAt", "createdAt"], "-lastSeenAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let current_session_id = req_entity.session.as_ref().map(|session| session.id);
+
+    match Session::get_page_by_user_id(req_entity.user_id, &pagination, &db).await {
+        Ok(sessions) => json_response(HttpResponse::success(
+            "Successfully retrieved sessions",
+            sessions.map(|session| session.to_dto(current_session_id)),
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use rocket::http::Status;
+use rocket::{delete, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        session::Session,
+    },
+    utils::parse_uuid::parse_uuid,
+};
+
+#[allow(unused)]
+#[delete("/users/@me/sessions/<id>")]
+pub async fn revoke_session(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    id: &str,
+) -> (Status, Json<HttpResponse<()>>) {
+    if !req_entity.is_user() {
+        return json_response(HttpResponse::forbidden("Forbidden"));
+    }
+
+    let uuid = match parse_uuid(id) {
+        Ok(uuid) => uuid,
+        Err(err) => return json_response(err.into()),
+    };
+
+    let session = match Session::get_by_id(uuid, &db).await {
+        Ok(session) => session,
+        Err(err) => return json_response(err.into()),
+    };
+
+    // Do not reveal sessions of other users
+    if session.user_id != req_entity.user_id {
+        return json_response(HttpResponse::not_found("Session not found"));
+    }
+
+    if let Err(err) = session.delete(&db).await {
+        return json_response(err.into());
+    }
+
+    AuditLog::new(
+        req_entity.user_id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Logout,
+        "Session revoked.".to_string(),
+        req_entity.user_id,
+        Some(HashMap::from([(
+            "session_id".to_string(),
+            session.id.to_string(),
+        )])),
+        None,
+    )
+    .insert(&db)
+    .await
+    .ok();
+
+    json_response(HttpResponse::success_no_data(
+        "Successfully revoked session",
+    ))
+}
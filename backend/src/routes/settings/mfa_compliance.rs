@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use mongodb::bson::DateTime;
+use rocket::http::Status;
+use rocket::{
+    get,
+    serde::{json::Json, Serialize},
+};
+use rocket_db_pools::Connection;
+
+use crate::models::permission::Permission;
+use crate::utils::response::json_response;
+use crate::SETTINGS;
+use crate::{
+    auth::{mfa::MfaHandler, AuthEntity},
+    db::AuthRsDatabase,
+    models::{
+        http_response::HttpResponse,
+        passkey::Passkey,
+        user::{User, UserDTO},
+    },
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct MfaComplianceEntry {
+    pub user: UserDTO,
+    // Not set if the user hasn't logged in since the policy applies to them
+    pub deadline: Option<DateTime>,
+    pub overdue: bool,
+}
+
+#[allow(unused)]
+#[get("/admin/mfa-compliance", format = "json")]
+pub async fn get_mfa_compliance(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> (Status, Json<HttpResponse<Vec<MfaComplianceEntry>>>) {
+    if !req_entity.is_user() || !req_entity.has_permission(Permission::UsersRead) {
+        return json_response(HttpResponse::forbidden(
+            "Missing permissions to view the MFA compliance report",
+        ));
+    }
+
+    let settings = SETTINGS.lock().await.clone();
+    let enforced_roles = match MfaHandler::get_enforced_roles(&settings, &db).await {
+        Ok(enforced_roles) => enforced_roles,
+        Err(err) => return json_response(HttpResponse::internal_error(&err)),
+    };
+
+    let users = match User::get_all(&db).await {
+        Ok(users) => users,
+        Err(err) => return json_response(err.into()),
+    };
+
+    let passkey_owners = match Passkey::get_all(&db, None).await {
+        Ok(passkeys) => passkeys
+            .into_iter()
+            .map(|passkey| passkey.owner)
+            .collect::<HashSet<_>>(),
+        Err(err) => return json_response(err.into()),
+    };
+
+    let now = DateTime::now();
+    let report = users
+        .into_iter()
+        .filter(|user| user.roles.iter().any(|role| enforced_roles.contains(role)))
+        .filter(|user| user.totp_secret.is_none() && !passkey_owners.contains(&user.id))
+        .map(|user| {
+            let deadline = user
+                .mfa_required_since
+                .map(|since| MfaHandler::get_enrollment_deadline(since, &settings));
+            MfaComplianceEntry {
+                user: user.to_dto(),
+                deadline,
+                overdue: deadline.is_some_and(|deadline| deadline <= now),
+            }
+        })
+        .collect();
+
+    json_response(HttpResponse::success(
+        "Successfully retrieved MFA compliance report",
+        report,
+    ))
+}
@@ -1,2 +1,3 @@
 pub mod get;
+pub mod mfa_compliance;
 pub mod update;
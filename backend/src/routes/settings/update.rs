@@ -1,3 +1,7 @@
+use crate::auth::breached_passwords;
+use crate::models::password_hashing::PasswordHashing;
+use crate::models::password_policy::PasswordPolicy;
+use crate::models::permission::Permission;
 use crate::models::settings::{Settings, SettingsError, SettingsResult};
 use crate::utils::response::json_response;
 use crate::SETTINGS;
@@ -7,8 +11,10 @@ use crate::{
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
+        role::Role,
     },
 };
+use mongodb::bson::Uuid;
 use rocket::http::Status;
 use rocket::{
     error, patch,
@@ -23,6 +29,11 @@ use std::collections::HashMap;
 pub struct UpdateSettingsData {
     open_registration: Option<bool>,
     allow_oauth_apps_for_users: Option<bool>,
+    mfa_required_roles: Option<Vec<Uuid>>,
+    mfa_grace_period_days: Option<u32>,
+    require_email_verification: Option<bool>,
+    password_policy: Option<PasswordPolicy>,
+    password_hashing: Option<PasswordHashing>,
 }
 
 #[allow(unused)]
@@ -92,6 +103,106 @@ impl SettingsUpdate {
         }
     }
 
+    async fn update_mfa_required_roles(
+        &mut self,
+        new_roles: Vec<Uuid>,
+        db: &Connection<AuthRsDatabase>,
+    ) -> SettingsResult<()> {
+        if self.settings.mfa_required_roles == new_roles {
+            return Ok(());
+        }
+
+        let available_roles = Role::get_all(db, None)
+            .await
+            .map_err(|e| SettingsError::InternalServerError(e.message()))?;
+        for role_id in &new_roles {
+            if !available_roles.iter().any(|role| role.id == *role_id) {
+                return Err(SettingsError::InvalidData(format!(
+                    "Role {} does not exist",
+                    role_id
+                )));
+            }
+        }
+
+        let old_roles = self
+            .settings
+            .mfa_required_roles
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let new_roles_str = new_roles
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.update_field("mfa_required_roles", old_roles, new_roles_str);
+        self.settings.mfa_required_roles = new_roles;
+        Ok(())
+    }
+
+    fn update_mfa_grace_period_days(&mut self, new_grace_period_days: u32) {
+        if self.settings.mfa_grace_period_days != new_grace_period_days {
+            let old_grace_period_days = self.settings.mfa_grace_period_days;
+            self.update_field(
+                "mfa_grace_period_days",
+                old_grace_period_days.to_string(),
+                new_grace_period_days.to_string(),
+            );
+            self.settings.mfa_grace_period_days = new_grace_period_days;
+        }
+    }
+
+    fn update_require_email_verification(&mut self, new_require_email_verification: bool) {
+        if self.settings.require_email_verification != new_require_email_verification {
+            let old_require_email_verification = self.settings.require_email_verification;
+            self.update_field(
+                "require_email_verification",
+                old_require_email_verification.to_string(),
+                new_require_email_verification.to_string(),
+            );
+            self.settings.require_email_verification = new_require_email_verification;
+        }
+    }
+
+    fn update_password_policy(&mut self, new_policy: PasswordPolicy) -> SettingsResult<()> {
+        if self.settings.password_policy == new_policy {
+            return Ok(());
+        }
+
+        if !(1..=256).contains(&new_policy.min_length) {
+            return Err(SettingsError::InvalidData(
+                "Minimum password length must be between 1 and 256".to_string(),
+            ));
+        }
+        if new_policy.deny_breached && !breached_passwords::is_configured() {
+            return Err(SettingsError::InvalidData(
+                "No breached password list configured (BREACHED_PASSWORDS_PATH)".to_string(),
+            ));
+        }
+
+        let old_policy = serde_json::to_string(&self.settings.password_policy).unwrap_or_default();
+        let new_policy_str = serde_json::to_string(&new_policy).unwrap_or_default();
+        self.update_field("password_policy", old_policy, new_policy_str);
+        self.settings.password_policy = new_policy;
+        Ok(())
+    }
+
+    fn update_password_hashing(&mut self, new_hashing: PasswordHashing) -> SettingsResult<()> {
+        if self.settings.password_hashing == new_hashing {
+            return Ok(());
+        }
+
+        new_hashing.validate().map_err(SettingsError::InvalidData)?;
+
+        let old_hashing =
+            serde_json::to_string(&self.settings.password_hashing).unwrap_or_default();
+        let new_hashing_str = serde_json::to_string(&new_hashing).unwrap_or_default();
+        self.update_field("password_hashing", old_hashing, new_hashing_str);
+        self.settings.password_hashing = new_hashing;
+        Ok(())
+    }
+
     async fn save(
         self,
         db: &Connection<AuthRsDatabase>,
@@ -130,17 +241,12 @@ async fn update_settings_internal(
 ) -> SettingsResult<Settings> {
     // Basic permission checks
     if !req_entity.is_user() {
-        return Err(SettingsError::Forbidden(
-            "Only the system user can change settings!".to_string(),
-        ));
+        return Err(SettingsError::Forbidden("Forbidden".to_string()));
     }
 
-    let req_user = req_entity.user().map_err(|_| {
-        SettingsError::Forbidden("Only the system user can change settings!".to_string())
-    })?;
-    if !req_user.is_system_admin() {
+    if !req_entity.has_permission(Permission::SettingsUpdate) {
         return Err(SettingsError::Forbidden(
-            "Only the system user can change settings!".to_string(),
+            "Missing permissions to change settings!".to_string(),
         ));
     }
 
@@ -158,6 +264,28 @@ async fn update_settings_internal(
         update.update_allow_oauth_apps_for_users(allow_oauth_apps_for_users);
     }
 
+    if let Some(mfa_required_roles) = data.mfa_required_roles {
+        update
+            .update_mfa_required_roles(mfa_required_roles, &db)
+            .await?;
+    }
+
+    if let Some(mfa_grace_period_days) = data.mfa_grace_period_days {
+        update.update_mfa_grace_period_days(mfa_grace_period_days);
+    }
+
+    if let Some(require_email_verification) = data.require_email_verification {
+        update.update_require_email_verification(require_email_verification);
+    }
+
+    if let Some(password_policy) = data.password_policy {
+        update.update_password_policy(password_policy)?;
+    }
+
+    if let Some(password_hashing) = data.password_hashing {
+        update.update_password_hashing(password_hashing)?;
+    }
+
     // Save changes
     update.save(&db, req_entity.user_id).await
 }
@@ -0,0 +1,42 @@
+use crate::models::permission::Permission;
+use crate::utils::pagination::{Page, PageQuery};
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        http_response::HttpResponse,
+        signing_key::{SigningKey, SigningKeyDTO},
+    },
+};
+use mongodb::bson::doc;
+use rocket::http::Status;
+use rocket::{get, serde::json::Json};
+use rocket_db_pools::Connection;
+
+#[allow(unused)]
+#[get("/admin/signing-keys?<query..>", format = "json")]
+pub async fn get_all_signing_keys(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<SigningKeyDTO>>>) {
+    if !req_entity.has_permission(Permission::SigningKeysRead) {
+        return json_response(HttpResponse::forbidden("Only admins can view signing keys"));
+    }
+
+    let pagination = match query.pagination(&["createdAt"], "-createdAt") {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let signing_keys = match SigningKey::get_page(doc! {}, &pagination, &db).await {
+        Ok(signing_keys) => signing_keys,
+        Err(err) => return json_response(err.into()),
+    };
+
+    json_response(HttpResponse::success(
+        "Successfully retrieved all signing keys",
+        signing_keys.map(|key| key.to_dto()),
+    ))
+}
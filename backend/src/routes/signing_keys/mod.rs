@@ -0,0 +1,2 @@
+pub mod get_all;
+pub mod rotate;
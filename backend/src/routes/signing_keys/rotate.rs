@@ -0,0 +1,65 @@
+use crate::models::permission::Permission;
+use crate::models::signing_key::{SigningKey, SigningKeyDTO, SigningKeyError, SigningKeyResult};
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+    },
+};
+use rocket::http::Status;
+use rocket::{error, post, serde::json::Json};
+use rocket_db_pools::Connection;
+use std::collections::HashMap;
+
+#[allow(unused)]
+#[post("/admin/signing-keys/rotate")]
+pub async fn rotate_signing_key(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> (Status, Json<HttpResponse<SigningKeyDTO>>) {
+    match rotate_signing_key_internal(db, req_entity).await {
+        Ok(signing_key) => json_response(HttpResponse::success("Signing key rotated", signing_key)),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+async fn rotate_signing_key_internal(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+) -> SigningKeyResult<SigningKeyDTO> {
+    // Key rotation affects every client, so it needs its own permission
+    if !req_entity.has_permission(Permission::SigningKeysRotate) {
+        return Err(SigningKeyError::Forbidden(
+            "Missing permissions to rotate signing keys!".to_string(),
+        ));
+    }
+
+    let previous_key = SigningKey::get_active(&db).await.ok();
+    let signing_key = SigningKey::rotate(&db).await?;
+
+    let old_values =
+        previous_key.map(|key| HashMap::from([("active_key_id".to_string(), key.id.to_string())]));
+    let new_values = HashMap::from([("active_key_id".to_string(), signing_key.id.to_string())]);
+
+    if let Err(err) = AuditLog::new(
+        signing_key.id.to_string(),
+        AuditLogEntityType::SigningKey,
+        AuditLogAction::Update,
+        "Signing key rotated.".to_string(),
+        req_entity.user_id,
+        old_values,
+        Some(new_values),
+    )
+    .insert(&db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+
+    tracing::info!(kid = %signing_key.id, "Signing key rotated");
+
+    Ok(signing_key.to_dto())
+}
@@ -7,8 +7,9 @@ use rocket::{
 use rocket_db_pools::Connection;
 
 use crate::{
-    auth::OptionalAuthEntity,
+    auth::{email_verification, OptionalAuthEntity},
     db::AuthRsDatabase,
+    models::permission::Permission,
     models::registration_token::RegistrationToken,
     models::user::UserDTO,
     models::{
@@ -56,6 +57,9 @@ async fn create_user_internal(
     auth_entity: Option<OptionalAuthEntity>,
     data: CreateUserData,
 ) -> UserResult<User> {
+    let can_create_users = auth_entity
+        .as_ref()
+        .is_some_and(|entity| entity.has_permission(Permission::UsersCreate));
     let req_user = if auth_entity.as_ref().is_some() && auth_entity.as_ref().unwrap().user.is_some()
     {
         Some(auth_entity.unwrap().user.unwrap())
@@ -66,9 +70,16 @@ async fn create_user_internal(
     let mut registration_token: Option<RegistrationToken> = None;
 
     // Handle closed registration
-    let settings = (*SETTINGS).lock().await;
-    if !settings.open_registration && (req_user.is_none() || !req_user.as_ref().unwrap().is_admin())
-    {
+    // Copy what we need so the lock isn't held across hashing, database writes or mail
+    let (open_registration, password_policy, password_hashing) = {
+        let settings = (*SETTINGS).lock().await;
+        (
+            settings.open_registration,
+            settings.password_policy.clone(),
+            settings.password_hashing.clone(),
+        )
+    };
+    if !open_registration && !can_create_users {
         if data.registration_code.is_none() || data.registration_code.len() < 1 {
             return Err(UserError::RegistrationClosed);
         }
@@ -93,9 +104,12 @@ async fn create_user_internal(
     if data.first_name.is_empty() {
         return Err(UserError::FirstNameRequired);
     }
-    if data.password.len() < 8 {
-        return Err(UserError::PasswordToShort);
-    }
+    password_policy
+        .validate(
+            &data.password,
+            &[&data.email, &data.first_name, &data.last_name],
+        )
+        .await?;
 
     // Create new user
     let mut user = User::new(
@@ -103,6 +117,7 @@ async fn create_user_internal(
         data.password,
         data.first_name,
         data.last_name,
+        &password_hashing,
     )?;
 
     // Handle registration token
@@ -155,5 +170,7 @@ async fn create_user_internal(
         error!("Failed to create audit log: {}", err);
     }
 
+    email_verification::try_send_verification_mail(&inserted_user).await;
+
     Ok(inserted_user)
 }
@@ -2,6 +2,7 @@ use rocket::http::Status;
 use rocket::{delete, error, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -30,7 +31,7 @@ pub async fn delete_user(
         Err(err) => return json_response(err.into()),
     };
 
-    if req_entity.user_id != uuid && !req_entity.user.unwrap().is_admin() {
+    if req_entity.user_id != uuid && !req_entity.has_permission(Permission::UsersDelete) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
@@ -0,0 +1,250 @@
+use mongodb::bson::Uuid;
+use rocket::futures::StreamExt;
+use rocket::http::{ContentType, Status};
+use rocket::response::stream::ReaderStream;
+use rocket::tokio::io::{AsyncRead, ReadBuf};
+use rocket::{
+    error, get,
+    serde::{json::Json, Serialize},
+};
+use rocket_db_pools::Connection;
+use std::collections::HashMap;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        http_response::HttpResponse,
+        permission::Permission,
+        role::Role,
+        user::{User, UserDTO},
+    },
+    utils::response::json_response,
+};
+
+// Same column names as the import, so an export can be imported again
+const CSV_HEADER: [&str; 8] = [
+    "id",
+    "email",
+    "emailVerified",
+    "firstName",
+    "lastName",
+    "roles",
+    "disabled",
+    "createdAt",
+];
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct ExportedUser {
+    #[serde(flatten)]
+    user: UserDTO,
+    role_names: Vec<String>,
+}
+
+// A piece of the export, `Failed` errors the read so the client sees an aborted download
+// instead of a file that looks complete
+pub enum ExportChunk {
+    Data(Cursor<String>),
+    Failed,
+}
+
+impl From<String> for ExportChunk {
+    fn from(data: String) -> Self {
+        ExportChunk::Data(Cursor::new(data))
+    }
+}
+
+impl AsyncRead for ExportChunk {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ExportChunk::Data(data) => Pin::new(data).poll_read(cx, buf),
+            ExportChunk::Failed => Poll::Ready(Err(io::Error::other("Failed to read users"))),
+        }
+    }
+}
+
+/// Streams all users as a JSON array or CSV file, `format` is `json` (default) or `csv`
+#[allow(unused)]
+#[get("/users/export?<format>")]
+pub async fn export_users(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    format: Option<&str>,
+) -> Result<(ContentType, ReaderStream![ExportChunk]), (Status, Json<HttpResponse<()>>)> {
+    if !req_entity.is_user() {
+        return Err(json_response(HttpResponse::forbidden("Forbidden")));
+    }
+
+    if !req_entity.has_permission(Permission::UsersRead) {
+        return Err(json_response(HttpResponse::forbidden(
+            "Missing permissions!",
+        )));
+    }
+
+    let as_csv = match format.unwrap_or("json") {
+        "json" => false,
+        "csv" => true,
+        _ => {
+            return Err(json_response(HttpResponse::bad_request(
+                "Format must be json or csv",
+            )))
+        }
+    };
+
+    let role_names = Role::get_all(&db, None)
+        .await
+        .map_err(|err| json_response(err.into()))?
+        .into_iter()
+        .map(|role| (role.id, role.name))
+        .collect::<HashMap<Uuid, String>>();
+    let mut users = User::get_cursor(&db)
+        .await
+        .map_err(|err| json_response(err.into()))?;
+
+    let stream = ReaderStream! {
+        if as_csv {
+            yield ExportChunk::from(to_csv_line(&CSV_HEADER));
+        } else {
+            yield ExportChunk::from("[".to_string());
+        }
+
+        let mut first = true;
+        while let Some(user) = users.next().await {
+            let user = match user {
+                Ok(user) => user,
+                Err(err) => {
+                    error!("Failed to read user during export: {}", err);
+                    yield ExportChunk::Failed;
+                    return;
+                }
+            };
+            let names = user
+                .roles
+                .iter()
+                .filter_map(|role_id| role_names.get(role_id).cloned())
+                .collect::<Vec<_>>();
+
+            if as_csv {
+                yield ExportChunk::from(to_csv_row(&user, &names));
+            } else {
+                let exported = ExportedUser {
+                    user: user.to_dto(),
+                    role_names: names,
+                };
+                let json = serde_json::to_string(&exported).unwrap_or_default();
+                yield ExportChunk::from(if first { json } else { format!(",{}", json) });
+            }
+            first = false;
+        }
+
+        if !as_csv {
+            yield ExportChunk::from("]".to_string());
+        }
+    };
+
+    let content_type = if as_csv {
+        ContentType::CSV
+    } else {
+        ContentType::JSON
+    };
+    Ok((content_type, stream))
+}
+
+/// Every cell goes through `escape_csv_formula`, the names and the email are user controlled
+fn to_csv_row(user: &User, role_names: &[String]) -> String {
+    let fields = [
+        user.id.to_string(),
+        user.email.clone(),
+        user.email_verified.to_string(),
+        user.first_name.clone(),
+        user.last_name.clone(),
+        role_names
+            .iter()
+            .map(|name| escape_csv_formula(name))
+            .collect::<Vec<_>>()
+            .join(";"),
+        user.disabled.to_string(),
+        user.created_at.try_to_rfc3339_string().unwrap_or_default(),
+    ];
+    to_csv_line(&fields.map(|field| escape_csv_formula(&field)))
+}
+
+fn to_csv_line<T: AsRef<[u8]>>(fields: &[T]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if let Err(err) = writer.write_record(fields) {
+        error!("Failed to write CSV line: {}", err);
+    }
+    writer
+        .into_inner()
+        .ok()
+        .and_then(|line| String::from_utf8(line).ok())
+        .unwrap_or_default()
+}
+
+// Spreadsheets run cells starting with these as formulas, a leading quote keeps them text
+fn escape_csv_formula(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email: &str, first_name: &str, last_name: &str) -> User {
+        User::with_password_hash(
+            email.to_string(),
+            String::new(),
+            first_name.to_string(),
+            last_name.to_string(),
+        )
+    }
+
+    #[test]
+    fn escapes_formula_prefixes() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\t=1", "\r=1"] {
+            assert_eq!(escape_csv_formula(value), format!("'{}", value));
+        }
+        assert_eq!(escape_csv_formula("Jane"), "Jane");
+        assert_eq!(escape_csv_formula(""), "");
+    }
+
+    #[test]
+    fn escapes_the_email_column() {
+        let user = user("=HYPERLINK(\"x\")@x.y", "Jane", "Doe");
+        let row = to_csv_row(&user, &[]);
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(row.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(&record[1], "'=HYPERLINK(\"x\")@x.y");
+    }
+
+    #[test]
+    fn escapes_names_and_role_names() {
+        let user = user("jane@example.com", "@Jane", "-Doe");
+        let row = to_csv_row(&user, &["=Admins".to_string(), "Support".to_string()]);
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(row.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(&record[1], "jane@example.com");
+        assert_eq!(&record[3], "'@Jane");
+        assert_eq!(&record[4], "'-Doe");
+        assert_eq!(&record[5], "'=Admins;Support");
+    }
+}
@@ -1,8 +1,12 @@
+use mongodb::bson::{doc, Document};
 use rocket::http::Status;
 use rocket::{get, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::user::UserDTO;
+use crate::utils::pagination::{starts_with, Page, PageQuery};
+use crate::utils::parse_uuid::parse_uuid;
 use crate::utils::response::json_response;
 use crate::{
     auth::AuthEntity,
@@ -11,24 +15,51 @@ use crate::{
 };
 
 #[allow(unused)]
-#[get("/users", format = "json")]
+#[get("/users?<query..>", format = "json")]
 pub async fn get_all_users(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Json<HttpResponse<Vec<UserDTO>>>) {
+    query: PageQuery,
+) -> (Status, Json<HttpResponse<Page<UserDTO>>>) {
     if !req_entity.is_user() {
         return json_response(HttpResponse::forbidden("Forbidden"));
     }
 
-    if !req_entity.user.unwrap().is_admin() {
+    if !req_entity.has_permission(Permission::UsersRead) {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
     }
 
-    //TODO: Add pagination
-    match User::get_all(&db).await {
+    let pagination = match query.pagination(
+        &["createdAt", "email", "firstName", "lastName"],
+        "createdAt",
+    ) {
+        Ok(pagination) => pagination,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let mut filters = Vec::<Document>::new();
+    if let Some(email) = &query.email {
+        filters.push(starts_with("email", &email.trim().to_lowercase()));
+    }
+    if let Some(disabled) = query.disabled {
+        filters.push(doc! { "disabled": disabled });
+    }
+    if let Some(role) = &query.role {
+        let role_id = match parse_uuid(role) {
+            Ok(role_id) => role_id,
+            Err(err) => return json_response(err.into()),
+        };
+        filters.push(doc! { "roles": role_id });
+    }
+    let filter = match filters.is_empty() {
+        true => doc! {},
+        false => doc! { "$and": filters },
+    };
+
+    match User::get_page(filter, &pagination, &db).await {
         Ok(users) => json_response(HttpResponse::success(
             "Successfully retrieved all users",
-            users.into_iter().map(|user| user.to_dto()).collect(),
+            users.map(|user| user.to_dto()),
         )),
         Err(err) => json_response(err.into()),
     }
@@ -1,6 +1,7 @@
 use rocket::{get, http::Status, serde::json::Json};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::user::UserDTO;
 use crate::{
     auth::AuthEntity,
@@ -42,7 +43,7 @@ pub async fn get_user_by_id(
 
     if (req_entity.is_user()
         && req_entity.user_id != uuid
-        && !req_entity.user.as_ref().unwrap().is_admin())
+        && !req_entity.has_permission(Permission::UsersRead))
         || req_entity.is_token() && req_entity.user_id != uuid
     {
         return json_response(HttpResponse::forbidden("Missing permissions!"));
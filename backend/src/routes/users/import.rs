@@ -0,0 +1,439 @@
+use mongodb::bson::Uuid;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::{
+    error, post,
+    serde::{json::Json, Deserialize, Serialize},
+    tokio,
+};
+use rocket_db_pools::Connection;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    auth::{email_verification, AuthEntity},
+    db::{get_main_db, AuthRsDatabase},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        password_hashing::PasswordHashing,
+        password_policy::PasswordPolicy,
+        permission::Permission,
+        role::Role,
+        user::{User, UserDTO},
+        user_error::{UserError, UserResult},
+    },
+    utils::response::json_response,
+    ADMIN_ROLE_ID, DEFAULT_ROLE_ID, SETTINGS,
+};
+
+const MAX_IMPORT_ROWS: usize = 5000;
+// Rows hashed per blocking task during an import
+const HASH_BATCH_SIZE: usize = 100;
+// Separates role names inside the CSV roles column
+const CSV_ROLE_SEPARATOR: char = ';';
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ImportUserData {
+    email: String,
+    first_name: String,
+    #[serde(default)]
+    last_name: String,
+    // Either a plain password or a hash, users with neither have to reset their password
+    password: Option<String>,
+    password_hash: Option<String>,
+    // Role names, the default role is always assigned
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    email_verified: bool,
+    #[serde(default)]
+    disabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRowError {
+    // 1-based, for CSV imports the header is row 0
+    row: usize,
+    email: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ImportUsersResult {
+    dry_run: bool,
+    total: usize,
+    imported: usize,
+    errors: Vec<ImportRowError>,
+    users: Vec<UserDTO>,
+    // Entity id of the audit log entry, only set when users were imported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    import_id: Option<Uuid>,
+}
+
+#[allow(unused)]
+#[post("/users/import?<dry_run>", format = "json", data = "<data>")]
+pub async fn import_users_json(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    dry_run: Option<bool>,
+    data: Json<Vec<ImportUserData>>,
+) -> (Status, Json<HttpResponse<ImportUsersResult>>) {
+    let result = import_users_internal(
+        &db,
+        &req_entity,
+        dry_run.unwrap_or(false),
+        data.into_inner(),
+    )
+    .await;
+
+    import_response(result)
+}
+
+#[allow(unused)]
+#[post("/users/import?<dry_run>", format = "text/csv", data = "<data>")]
+pub async fn import_users_csv(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> (Status, Json<HttpResponse<ImportUsersResult>>) {
+    let content = match data.open(10.mebibytes()).into_string().await {
+        Ok(content) if content.is_complete() => content.into_inner(),
+        Ok(_) => return json_response(HttpResponse::bad_request("CSV file is too large")),
+        Err(err) => return json_response(HttpResponse::bad_request(&err.to_string())),
+    };
+    let rows = match parse_csv(&content) {
+        Ok(rows) => rows,
+        Err(err) => return json_response(HttpResponse::bad_request(&err)),
+    };
+
+    let result = import_users_internal(&db, &req_entity, dry_run.unwrap_or(false), rows).await;
+
+    import_response(result)
+}
+
+fn import_response(
+    result: UserResult<ImportUsersResult>,
+) -> (Status, Json<HttpResponse<ImportUsersResult>>) {
+    match result {
+        Ok(result) if !result.errors.is_empty() => json_response(HttpResponse {
+            status: 400,
+            message: "Import contains invalid rows, no users were imported".to_string(),
+            data: Some(result),
+        }),
+        Ok(result) if result.dry_run => {
+            json_response(HttpResponse::success("Import is valid", result))
+        }
+        Ok(result) => json_response(HttpResponse {
+            status: 201,
+            message: "Users imported".to_string(),
+            data: Some(result),
+        }),
+        Err(err) => json_response(err.into()),
+    }
+}
+
+/// Parses a CSV file with a header row, see `ImportUserData` for the columns
+fn parse_csv(content: &str) -> Result<Vec<ImportUserData>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|err| format!("Invalid CSV header: {}", err))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (Some(email), Some(first_name)) = (column("email"), column("firstName")) else {
+        return Err("CSV header must contain the email and firstName columns".to_string());
+    };
+    let last_name = column("lastName");
+    let password = column("password");
+    let password_hash = column("passwordHash");
+    let roles = column("roles");
+    let email_verified = column("emailVerified");
+    let disabled = column("disabled");
+
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| format!("Invalid CSV row {}: {}", index + 1, err))?;
+        let get = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let get_bool = |column: Option<usize>| {
+            get(column).is_some_and(|value| matches!(value.to_lowercase().as_str(), "true" | "1"))
+        };
+
+        rows.push(ImportUserData {
+            email: get(Some(email)).unwrap_or_default(),
+            first_name: get(Some(first_name)).unwrap_or_default(),
+            last_name: get(last_name).unwrap_or_default(),
+            password: get(password),
+            password_hash: get(password_hash),
+            roles: get(roles)
+                .map(|roles| {
+                    roles
+                        .split(CSV_ROLE_SEPARATOR)
+                        .map(|role| role.trim().to_string())
+                        .filter(|role| !role.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            email_verified: get_bool(email_verified),
+            disabled: get_bool(disabled),
+        });
+    }
+
+    Ok(rows)
+}
+
+async fn import_users_internal(
+    db: &Connection<AuthRsDatabase>,
+    req_entity: &AuthEntity,
+    dry_run: bool,
+    rows: Vec<ImportUserData>,
+) -> UserResult<ImportUsersResult> {
+    if !req_entity.is_user() || !req_entity.has_permission(Permission::UsersCreate) {
+        return Err(UserError::MissingPermissions);
+    }
+    if rows.is_empty() {
+        return Err(UserError::InvalidImport(
+            "Import contains no users".to_string(),
+        ));
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(UserError::InvalidImport(format!(
+            "Imports are limited to {} users",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let settings = SETTINGS.lock().await.clone();
+    let roles_by_name = Role::get_all(db, None)
+        .await
+        .map_err(|e| UserError::InternalServerError(e.message()))?
+        .into_iter()
+        .map(|role| (role.name.to_lowercase(), role.id))
+        .collect::<HashMap<_, _>>();
+    let emails = rows
+        .iter()
+        .map(|row| row.email.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    let existing_emails = User::get_existing_emails(&emails, db).await?;
+    let is_system_admin = req_entity.user()?.is_system_admin();
+
+    let mut errors = Vec::new();
+    let mut valid_rows = Vec::new();
+    let mut seen_emails = HashSet::new();
+    for (index, mut row) in rows.into_iter().enumerate() {
+        row.email = row.email.trim().to_lowercase();
+        let row_error = |message: String| ImportRowError {
+            row: index + 1,
+            email: row.email.clone(),
+            message,
+        };
+
+        let result = validate_row(
+            &row,
+            &settings.password_policy,
+            &roles_by_name,
+            is_system_admin,
+        )
+        .await;
+        match result {
+            Ok(_) if existing_emails.contains(&row.email) => errors.push(row_error(
+                UserError::EmailAlreadyExists(row.email.clone()).to_string(),
+            )),
+            Ok(_) if !seen_emails.insert(row.email.clone()) => errors.push(row_error(
+                "Email appears more than once in the import".to_string(),
+            )),
+            Ok(role_ids) => valid_rows.push((row, role_ids)),
+            Err(message) => errors.push(row_error(message)),
+        }
+    }
+
+    // Nobody can hand out permissions they don't hold themselves
+    let assigned_roles = valid_rows
+        .iter()
+        .flat_map(|(_, role_ids)| role_ids.iter().copied())
+        .filter(|role_id| *role_id != *DEFAULT_ROLE_ID)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    if !assigned_roles.is_empty() {
+        if !req_entity.has_permission(Permission::RolesAssign) {
+            return Err(UserError::MissingPermissions);
+        }
+        let granted_permissions = Role::get_permissions(&assigned_roles, &get_main_db(db))
+            .await
+            .map_err(|e| UserError::InternalServerError(e.message()))?;
+        if granted_permissions
+            .iter()
+            .any(|permission| !req_entity.has_permission(*permission))
+        {
+            return Err(UserError::MissingPermissions);
+        }
+    }
+
+    let total = errors.len() + valid_rows.len();
+    if dry_run || !errors.is_empty() {
+        return Ok(ImportUsersResult {
+            dry_run,
+            total,
+            imported: 0,
+            errors,
+            users: Vec::new(),
+            import_id: None,
+        });
+    }
+
+    // Hashing is CPU bound, so it runs off the async executor in batches
+    let mut users = Vec::with_capacity(valid_rows.len());
+    while !valid_rows.is_empty() {
+        let batch = valid_rows
+            .drain(..HASH_BATCH_SIZE.min(valid_rows.len()))
+            .collect::<Vec<_>>();
+        let password_hashing = settings.password_hashing.clone();
+        let batch_users = tokio::task::spawn_blocking(move || {
+            batch
+                .into_iter()
+                .map(|(row, role_ids)| build_user(row, role_ids, &password_hashing))
+                .collect::<UserResult<Vec<_>>>()
+        })
+        .await
+        .map_err(|e| UserError::InternalServerError(e.to_string()))??;
+        users.extend(batch_users);
+    }
+
+    // Whatever was inserted before a failure is audited before the error is returned
+    let insert_result = User::insert_many(&users, db).await;
+    if insert_result.is_err() {
+        let ids = users.iter().map(|user| user.id).collect::<Vec<_>>();
+        let inserted_ids = User::get_existing_ids(&ids, db).await?;
+        users.retain(|user| inserted_ids.contains(&user.id));
+        if users.is_empty() {
+            insert_result?;
+        }
+    }
+
+    // One audit log for the whole import, listing the created users
+    let import_id = Uuid::new();
+    let new_values = users
+        .iter()
+        .map(|user| (user.id.to_string(), user.email.clone()))
+        .collect::<HashMap<_, _>>();
+    if let Err(err) = AuditLog::new(
+        import_id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Import,
+        format!("Imported {} users.", users.len()),
+        req_entity.user_id,
+        None,
+        Some(new_values),
+    )
+    .insert(db)
+    .await
+    {
+        error!("Failed to create audit log: {}", err);
+    }
+    insert_result?;
+
+    // Sending thousands of mails would hold the request open, so they go out in the background
+    let unverified_users = users
+        .iter()
+        .filter(|user| !user.email_verified)
+        .cloned()
+        .collect::<Vec<_>>();
+    tokio::spawn(async move {
+        for user in unverified_users {
+            email_verification::try_send_verification_mail(&user).await;
+        }
+    });
+
+    Ok(ImportUsersResult {
+        dry_run,
+        total,
+        imported: users.len(),
+        errors: Vec::new(),
+        users: users.iter().map(User::to_dto).collect(),
+        import_id: Some(import_id),
+    })
+}
+
+fn build_user(
+    row: ImportUserData,
+    role_ids: Vec<Uuid>,
+    password_hashing: &PasswordHashing,
+) -> UserResult<User> {
+    let password_hash = match (row.password, row.password_hash) {
+        (Some(password), _) => password_hashing.hash(&password)?,
+        (None, Some(password_hash)) => password_hash,
+        // Not a valid hash, so nobody can log in until the password is reset
+        (None, None) => String::new(),
+    };
+
+    let mut user =
+        User::with_password_hash(row.email, password_hash, row.first_name, row.last_name);
+    user.roles = role_ids;
+    user.email_verified = row.email_verified;
+    user.disabled = row.disabled;
+    Ok(user)
+}
+
+// Checks a single row and resolves its role names, the error is shown to the admin
+async fn validate_row(
+    row: &ImportUserData,
+    password_policy: &PasswordPolicy,
+    roles_by_name: &HashMap<String, Uuid>,
+    is_system_admin: bool,
+) -> Result<Vec<Uuid>, String> {
+    if !row.email.contains('@') || !row.email.contains('.') || row.email.len() < 5 {
+        return Err(UserError::InvalidEmail.to_string());
+    }
+    if row.first_name.trim().is_empty() {
+        return Err(UserError::FirstNameRequired.to_string());
+    }
+
+    match (&row.password, &row.password_hash) {
+        (Some(_), Some(_)) => {
+            return Err("Only one of password and passwordHash can be set".to_string())
+        }
+        (Some(password), None) => password_policy
+            .validate(password, &[&row.email, &row.first_name, &row.last_name])
+            .await
+            .map_err(|err| err.to_string())?,
+        (None, Some(password_hash)) if !PasswordHashing::is_supported(password_hash) => {
+            return Err(
+                "Unsupported password hash, use Argon2, bcrypt, scrypt or PBKDF2".to_string(),
+            )
+        }
+        (None, Some(password_hash)) if !PasswordHashing::is_within_cost_limits(password_hash) => {
+            return Err("Password hash parameters are too expensive".to_string())
+        }
+        _ => {}
+    }
+
+    let mut role_ids = Vec::from([*DEFAULT_ROLE_ID]);
+    for name in &row.roles {
+        let Some(role_id) = roles_by_name.get(&name.to_lowercase()) else {
+            return Err(format!("Role {} does not exist", name));
+        };
+        if *role_id == *ADMIN_ROLE_ID && !is_system_admin {
+            return Err(UserError::AdminRoleAssignment.to_string());
+        }
+        if !role_ids.contains(role_id) {
+            role_ids.push(*role_id);
+        }
+    }
+
+    Ok(role_ids)
+}
@@ -1,11 +1,18 @@
 use rocket::http::Status;
-use rocket::{get, serde::json::Json};
+use rocket::{
+    get,
+    serde::{json::Json, Serialize},
+};
 use rocket_db_pools::Connection;
 
+use crate::models::permission::Permission;
 use crate::models::user::UserDTO;
 use crate::utils::response::json_response;
 use crate::{
-    auth::AuthEntity,
+    auth::{
+        oidc::{get_group_claims, GroupClaim},
+        AuthEntity,
+    },
     db::AuthRsDatabase,
     models::{
         http_response::HttpResponse,
@@ -41,12 +48,21 @@ pub async fn get_current_user(
     }
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PlainUserResponse {
+    #[serde(flatten)]
+    pub user: UserDTO,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<GroupClaim>>,
+}
+
 #[allow(unused)]
 #[get("/users/@me/plain", format = "json")]
 pub async fn get_current_user_plain(
     db: Connection<AuthRsDatabase>,
     req_entity: AuthEntity,
-) -> (Status, Option<Json<UserDTO>>) {
+) -> (Status, Option<Json<PlainUserResponse>>) {
     if req_entity.is_token()
         && (!req_entity
             .token
@@ -62,8 +78,46 @@ pub async fn get_current_user_plain(
         return (Status::Unauthorized, None);
     }
 
-    match User::get_by_id(req_entity.user_id, &db).await {
-        Ok(user) => (Status::Ok, Some(Json(user.to_dto()))),
-        Err(err) => (Status::NotFound, None),
+    let mut user = match User::get_by_id(req_entity.user_id, &db).await {
+        Ok(user) => user.to_dto(),
+        Err(err) => return (Status::NotFound, None),
+    };
+
+    // Applications only get to see the roles they are allowed to through the roles scope
+    let groups = match &req_entity.token {
+        Some(token) => {
+            match get_group_claims(&user, token.application_id, &token.scope, &db).await {
+                Ok(groups) => {
+                    let disclosed = groups.clone().unwrap_or_default();
+                    user.roles
+                        .retain(|role| disclosed.iter().any(|group| group.id == role.to_string()));
+                    groups
+                }
+                Err(err) => return (Status::InternalServerError, None),
+            }
+        }
+        None => None,
+    };
+
+    (Status::Ok, Some(Json(PlainUserResponse { user, groups })))
+}
+
+#[allow(unused)]
+#[get("/users/@me/permissions", format = "json")]
+pub async fn get_current_user_permissions(
+    req_entity: AuthEntity,
+) -> (Status, Json<HttpResponse<Vec<Permission>>>) {
+    if !req_entity.is_user() {
+        return json_response(HttpResponse::forbidden("Forbidden"));
     }
+
+    let permissions = Permission::ALL
+        .into_iter()
+        .filter(|permission| req_entity.has_permission(*permission))
+        .collect();
+
+    json_response(HttpResponse::success(
+        "Successfully retrieved permissions",
+        permissions,
+    ))
 }
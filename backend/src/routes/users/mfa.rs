@@ -1,17 +1,23 @@
 use rocket::http::Status;
 use rocket::{
     post,
-    serde::{json::Json, Deserialize},
+    serde::{json::Json, Deserialize, Serialize},
 };
 use rocket_db_pools::Connection;
+use std::collections::HashMap;
 
+use crate::models::permission::Permission;
 use crate::models::user::UserDTO;
 use crate::utils::response::json_response;
 use crate::{
     auth::{mfa::MfaHandler, AuthEntity},
     db::AuthRsDatabase,
     errors::{ApiError, ApiResult},
-    models::{http_response::HttpResponse, user::User},
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        user::User,
+    },
     routes::auth::login::LoginResponse,
     utils::parse_uuid::parse_uuid,
 };
@@ -38,7 +44,7 @@ async fn process_enable_totp_mfa(
 
     if req_entity.is_user()
         && req_entity.user_id != uuid
-        && !req_entity.user.as_ref().unwrap().is_system_admin()
+        && !req_entity.has_permission(Permission::UsersMfa)
     {
         return Err(ApiError::Forbidden("Missing permissions!".to_string()));
     }
@@ -68,6 +74,9 @@ async fn process_enable_totp_mfa(
             token: Some(flow.totp.unwrap().get_qr_base64().unwrap()),
             mfa_required: true,
             mfa_flow_id: Some(flow.flow_id),
+            recovery_codes: None,
+            mfa_factors: Some(flow.factors),
+            mfa_enrollment: None,
         },
     ))
 }
@@ -111,7 +120,7 @@ async fn process_disable_totp_mfa(
 
     if req_entity.is_user()
         && req_entity.user_id != uuid
-        && !req_entity.user.as_ref().unwrap().is_system_admin()
+        && !req_entity.has_permission(Permission::UsersMfa)
     {
         return Err(ApiError::Forbidden("Missing permissions!".to_string()));
     }
@@ -131,9 +140,8 @@ async fn process_disable_totp_mfa(
     }
 
     if let Some(code) = mfa_data.code {
-        let is_valid =
-            MfaHandler::verify_totp(&user, user.totp_secret.as_ref().unwrap().to_string(), &code)
-                .await;
+        // Claims the step like a login, so a code that was already used can't disable TOTP
+        let is_valid = MfaHandler::verify_user_totp(&user, &code, db).await.is_some();
 
         if !is_valid {
             return Err(ApiError::Unauthorized("Invalid TOTP code!".to_string()));
@@ -166,3 +174,94 @@ pub async fn disable_totp_mfa(
         Err(err) => Json(err.into()),
     }
 }
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RegenerateRecoveryCodesData {
+    pub password: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+// Process regenerate recovery codes and return a Result
+async fn process_regenerate_recovery_codes(
+    db: &Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    id: &str,
+    data: RegenerateRecoveryCodesData,
+) -> ApiResult<RecoveryCodesResponse> {
+    if req_entity.is_token() {
+        return Err(ApiError::Forbidden("Forbidden!".to_string()));
+    }
+
+    let uuid = parse_uuid(id)?;
+
+    // The codes are shown in the response, so only the user themself can regenerate them
+    if req_entity.user_id != uuid {
+        return Err(ApiError::Forbidden("Missing permissions!".to_string()));
+    }
+
+    let mut user = User::get_by_id(uuid, db)
+        .await
+        .map_err(|err| ApiError::InternalError(format!("Failed to get user: {:?}", err)))?;
+
+    if user.verify_password(&data.password).is_err() {
+        return Err(ApiError::Unauthorized("Incorrect password!".to_string()));
+    }
+
+    if user.totp_secret.is_none() {
+        return Err(ApiError::BadRequest("TOTP MFA is not enabled!".to_string()));
+    }
+
+    let (recovery_codes, recovery_code_hashes) = MfaHandler::generate_recovery_codes()
+        .map_err(|err| ApiError::InternalError(format!("Failed to generate codes: {}", err)))?;
+    user.recovery_codes = recovery_code_hashes;
+
+    user.update(db).await.map_err(|err| {
+        ApiError::InternalError(format!("Failed to save recovery codes: {:?}", err))
+    })?;
+
+    AuditLog::new(
+        user.id.to_string(),
+        AuditLogEntityType::User,
+        AuditLogAction::Update,
+        "Regenerate recovery codes.".to_string(),
+        req_entity.user_id,
+        Some(HashMap::from([(
+            "recovery_codes".to_string(),
+            "*************".to_string(),
+        )])),
+        Some(HashMap::from([(
+            "recovery_codes".to_string(),
+            "*************".to_string(),
+        )])),
+    )
+    .insert(db)
+    .await
+    .ok();
+
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+#[allow(unused)]
+#[post("/users/<id>/mfa/recovery-codes", format = "json", data = "<data>")]
+pub async fn regenerate_recovery_codes(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    id: &str,
+    data: Json<RegenerateRecoveryCodesData>,
+) -> (Status, Json<HttpResponse<RecoveryCodesResponse>>) {
+    match process_regenerate_recovery_codes(&db, req_entity, id, data.into_inner()).await {
+        Ok(response) => json_response(HttpResponse::success(
+            "Recovery codes regenerated.",
+            response,
+        )),
+        Err(err) => json_response(err.into()),
+    }
+}
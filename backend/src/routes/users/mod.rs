@@ -1,8 +1,11 @@
 pub mod create;
 pub mod delete;
+pub mod export;
 pub mod get_all;
 pub mod get_by_id;
+pub mod import;
 pub mod me;
 pub mod mfa;
 pub mod passkeys;
+pub mod unlock;
 pub mod update;
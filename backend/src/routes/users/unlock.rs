@@ -0,0 +1,73 @@
+use rocket::http::Status;
+use rocket::{error, post, serde::json::Json};
+use rocket_db_pools::Connection;
+
+use crate::models::permission::Permission;
+use crate::utils::response::json_response;
+use crate::{
+    auth::AuthEntity,
+    db::AuthRsDatabase,
+    models::{
+        audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
+        http_response::HttpResponse,
+        user::{User, UserDTO},
+    },
+    utils::parse_uuid::parse_uuid,
+};
+
+#[allow(unused)]
+#[post("/users/<id>/unlock", format = "json")]
+pub async fn unlock_user(
+    db: Connection<AuthRsDatabase>,
+    req_entity: AuthEntity,
+    id: &str,
+) -> (Status, Json<HttpResponse<UserDTO>>) {
+    if !req_entity.is_user() {
+        return json_response(HttpResponse::forbidden("Forbidden"));
+    }
+
+    let uuid = match parse_uuid(id) {
+        Ok(uuid) => uuid,
+        Err(err) => return json_response(err.into()),
+    };
+
+    if !req_entity.has_permission(Permission::UsersUpdate) {
+        return json_response(HttpResponse::forbidden("Missing permissions!"));
+    }
+
+    let mut user = match User::get_full_by_id(uuid, &db).await {
+        Ok(user) => user,
+        Err(err) => {
+            return json_response(HttpResponse::not_found(&format!(
+                "User does not exist: {:?}",
+                err
+            )))
+        }
+    };
+
+    match user.unlock(&db).await {
+        Ok(true) => {
+            match AuditLog::new(
+                user.id.to_string(),
+                AuditLogEntityType::User,
+                AuditLogAction::Unlock,
+                "Unlock account.".to_string(),
+                req_entity.user_id,
+                None,
+                None,
+            )
+            .insert(&db)
+            .await
+            {
+                Ok(_) => (),
+                Err(err) => error!("{}", err),
+            }
+
+            user.failed_login_attempts = 0;
+            user.locked_until = None;
+            json_response(HttpResponse::success("User unlocked", user.to_dto()))
+        }
+        Ok(false) => json_response(HttpResponse::bad_request("User is not locked")),
+        Err(err) => json_response(err.into()),
+    }
+}
@@ -1,20 +1,20 @@
 use crate::models::user::UserDTO;
 use crate::utils::response::json_response;
 use crate::{
-    auth::AuthEntity,
-    db::AuthRsDatabase,
+    auth::{email_verification, AuthEntity},
+    db::{get_main_db, AuthRsDatabase},
     models::{
         audit_log::{AuditLog, AuditLogAction, AuditLogEntityType},
         http_response::HttpResponse,
+        permission::Permission,
         role::Role,
+        session::Session,
         user::User,
         user_error::{UserError, UserResult},
     },
     ADMIN_ROLE_ID, DEFAULT_ROLE_ID, SYSTEM_USER_ID,
 };
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHasher};
-use mongodb::bson::Uuid;
+use mongodb::bson::{doc, Uuid};
 use rocket::{
     error,
     http::Status,
@@ -91,23 +91,20 @@ impl UserUpdate {
             let old_email = self.user.email.clone();
             self.update_field("email", old_email, new_email.clone());
             self.user.email = new_email;
+
+            // The new address has to be verified again
+            if self.user.email_verified {
+                self.update_field("email_verified", "true", "false");
+                self.user.email_verified = false;
+            }
         }
         Ok(())
     }
 
-    fn update_password(&mut self, password: String) -> UserResult<()> {
-        if password.len() < 8 {
-            return Err(UserError::PasswordToShort);
-        }
-        let salt =
-            SaltString::from_b64(&self.user.salt).map_err(|_| UserError::PasswordHashingError)?;
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|_| UserError::PasswordHashingError)?
-            .to_string();
+    async fn update_password(&mut self, password: String) -> UserResult<()> {
+        self.user.validate_new_password(&password).await?;
+        self.user.set_password(&password).await?;
         self.update_field("password", "***********", "***********");
-        self.user.password_hash = password_hash;
         Ok(())
     }
 
@@ -141,7 +138,7 @@ impl UserUpdate {
         &mut self,
         new_roles: Vec<Uuid>,
         db: &Connection<AuthRsDatabase>,
-        req_user: &User,
+        req_entity: &AuthEntity,
     ) -> UserResult<()> {
         if self.user.roles == new_roles {
             return Ok(());
@@ -152,8 +149,7 @@ impl UserUpdate {
             return Err(UserError::SystemUserModification);
         }
 
-        // Admin permission check
-        if !req_user.is_admin() {
+        if !req_entity.has_permission(Permission::RolesAssign) {
             return Err(UserError::MissingPermissions);
         }
 
@@ -174,10 +170,26 @@ impl UserUpdate {
         }
 
         // Admin role assignment check
-        if final_roles.contains(&ADMIN_ROLE_ID) && !self.user.roles.contains(&ADMIN_ROLE_ID) && !req_user.is_system_admin() {
+        if final_roles.contains(&ADMIN_ROLE_ID) && !self.user.roles.contains(&ADMIN_ROLE_ID) && !req_entity.user()?.is_system_admin() {
             return Err(UserError::AdminRoleAssignment);
         }
 
+        // Nobody can hand out permissions they don't hold themselves
+        let added_roles = final_roles
+            .iter()
+            .filter(|role_id| !self.user.roles.contains(role_id))
+            .copied()
+            .collect::<Vec<_>>();
+        let granted_permissions = Role::get_permissions(&added_roles, &get_main_db(db))
+            .await
+            .map_err(|e| UserError::InternalServerError(e.message()))?;
+        if granted_permissions
+            .iter()
+            .any(|permission| !req_entity.has_permission(*permission))
+        {
+            return Err(UserError::MissingPermissions);
+        }
+
         let old_roles = self
             .user
             .roles
@@ -195,12 +207,12 @@ impl UserUpdate {
         Ok(())
     }
 
-    fn update_disabled(&mut self, disabled: bool, req_user: &User) -> UserResult<()> {
+    fn update_disabled(&mut self, disabled: bool, req_entity: &AuthEntity) -> UserResult<()> {
         if self.user.disabled == disabled {
             return Ok(());
         }
 
-        if !req_user.is_admin() || req_user.id == self.user.id {
+        if !req_entity.has_permission(Permission::UsersDisable) || req_entity.user_id == self.user.id {
             return Err(UserError::MissingPermissions);
         }
 
@@ -225,6 +237,19 @@ impl UserUpdate {
             .await
             .map_err(|e| UserError::DatabaseError(format!("Failed to update user: {}", e)))?;
 
+        // A disabled user is logged out of all devices
+        if self.new_values.contains_key("disabled") && updated_user.disabled {
+            Session::delete_all_matching(doc! { "userId": updated_user.id }, db)
+                .await
+                .map_err(|e| {
+                    UserError::DatabaseError(format!("Failed to revoke sessions: {}", e))
+                })?;
+        }
+
+        if self.new_values.contains_key("email") {
+            email_verification::try_send_verification_mail(&updated_user).await;
+        }
+
         // Create audit log
         if let Err(err) = AuditLog::new(
             updated_user.id.to_string(),
@@ -257,11 +282,7 @@ async fn update_user_internal(
     }
 
     let uuid = Uuid::parse_str(id).map_err(|e| UserError::InvalidUuid(e.to_string()))?;
-    let req_user = req_entity
-        .user()
-        .map_err(|_| UserError::MissingPermissions)?;
-
-    if req_entity.user_id != uuid && !req_user.is_admin() {
+    if req_entity.user_id != uuid && !req_entity.has_permission(Permission::UsersUpdate) {
         return Err(UserError::MissingPermissions);
     }
 
@@ -269,6 +290,25 @@ async fn update_user_internal(
     let user = User::get_by_id(uuid, &db)
         .await
         .map_err(|_| UserError::NotFound(uuid))?;
+
+    if req_entity.user_id != uuid {
+        // Only the system admin can change its own credentials
+        if uuid == *SYSTEM_USER_ID && (data.email.is_some() || data.password.is_some()) {
+            return Err(UserError::SystemUserModification);
+        }
+
+        // Like for role grants, nobody can take over a user with permissions they don't hold
+        let target_permissions = Role::get_permissions(&user.roles, &get_main_db(&db))
+            .await
+            .map_err(|e| UserError::InternalServerError(e.message()))?;
+        if target_permissions
+            .iter()
+            .any(|permission| !req_entity.has_permission(*permission))
+        {
+            return Err(UserError::MissingPermissions);
+        }
+    }
+
     let mut update = UserUpdate::new(user);
 
     // Apply updates
@@ -276,18 +316,19 @@ async fn update_user_internal(
         update.update_email(email, &db).await?;
     }
 
+    update.update_name(data.first_name, data.last_name)?;
+
+    // Applied after the name and email, so the policy checks against the new values
     if let Some(password) = data.password {
-        update.update_password(password)?;
+        update.update_password(password).await?;
     }
 
-    update.update_name(data.first_name, data.last_name)?;
-
     if let Some(roles) = data.roles {
-        update.update_roles(roles, &db, req_user).await?;
+        update.update_roles(roles, &db, &req_entity).await?;
     }
 
     if let Some(disabled) = data.disabled {
-        update.update_disabled(disabled, req_user)?;
+        update.update_disabled(disabled, &req_entity)?;
     }
 
     // Save changes
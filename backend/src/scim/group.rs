@@ -0,0 +1,40 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{get_location, user::ScimMember, ScimMeta, GROUP_SCHEMA};
+use crate::models::{role::Role, user::User};
+
+/// A role, its members are the users that have the role
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMember>,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl ScimGroup {
+    pub fn from_role(role: &Role, members: &[&User]) -> Self {
+        let id = role.id.to_string();
+        Self {
+            schemas: vec![GROUP_SCHEMA.to_string()],
+            id: Some(id.clone()),
+            display_name: role.name.clone(),
+            members: members
+                .iter()
+                .map(|user| ScimMember::from_user(user))
+                .collect(),
+            meta: Some(ScimMeta {
+                resource_type: "Group",
+                created: role.created_at.try_to_rfc3339_string().unwrap_or_default(),
+                location: get_location("Groups", &id),
+            }),
+        }
+    }
+}
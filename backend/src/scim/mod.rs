@@ -0,0 +1,274 @@
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::Serialize;
+use rocket::{FromForm, Request, Response};
+use std::io::Cursor;
+use thiserror::Error;
+
+use crate::models::{http_response::HttpResponse, role::RoleError, user_error::UserError};
+
+pub mod group;
+pub mod patch;
+pub mod user;
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const PATCH_OP_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const SERVICE_PROVIDER_CONFIG_SCHEMA: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+pub const DEFAULT_PAGE_SIZE: u64 = 100;
+pub const MAX_PAGE_SIZE: u64 = 500;
+
+/// Error in the SCIM format (RFC 7644, section 3.12)
+#[derive(Debug, Error)]
+#[error("{detail}")]
+pub struct ScimError {
+    pub status: Status,
+    pub scim_type: Option<&'static str>,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct ScimErrorBody {
+    schemas: [&'static str; 1],
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl ScimError {
+    pub fn new(status: Status, scim_type: Option<&'static str>, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            scim_type,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(Status::BadRequest, Some(scim_type), detail)
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(Status::Unauthorized, None, detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(Status::NotFound, None, detail)
+    }
+
+    pub fn uniqueness(detail: impl Into<String>) -> Self {
+        Self::new(Status::Conflict, Some("uniqueness"), detail)
+    }
+
+    pub fn internal(detail: impl Into<String>) -> Self {
+        Self::new(Status::InternalServerError, None, detail)
+    }
+}
+
+impl<T> From<HttpResponse<T>> for ScimError {
+    fn from(response: HttpResponse<T>) -> Self {
+        let status = Status::from_code(response.status).unwrap_or(Status::InternalServerError);
+        let scim_type = (status == Status::BadRequest).then_some("invalidValue");
+        Self::new(status, scim_type, response.message)
+    }
+}
+
+impl From<UserError> for ScimError {
+    fn from(error: UserError) -> Self {
+        match error {
+            UserError::EmailAlreadyExists(email) => {
+                Self::uniqueness(format!("User with userName {} already exists", email))
+            }
+            UserError::SystemUserModification => {
+                Self::bad_request("mutability", "The system user can't be provisioned")
+            }
+            error => HttpResponse::<()>::from(error).into(),
+        }
+    }
+}
+
+impl From<RoleError> for ScimError {
+    fn from(error: RoleError) -> Self {
+        match error {
+            RoleError::NameAlreadyExists(name) => {
+                Self::uniqueness(format!("Group with displayName {} already exists", name))
+            }
+            RoleError::SystemRoleModification => {
+                Self::bad_request("mutability", "System groups can't be modified")
+            }
+            error => HttpResponse::<()>::from(error).into(),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ScimError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = ScimErrorBody {
+            schemas: [ERROR_SCHEMA],
+            status: self.status.code.to_string(),
+            scim_type: self.scim_type,
+            detail: self.detail,
+        };
+        ScimJson(self.status, body).respond_to(request)
+    }
+}
+
+/// JSON response with the `application/scim+json` content type
+pub struct ScimJson<T>(pub Status, pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for ScimJson<T> {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_string(&self.1).map_err(|err| {
+            tracing::error!(error = %err, "Failed to serialize SCIM response");
+            Status::InternalServerError
+        })?;
+
+        Response::build()
+            .status(self.0)
+            .header(ContentType::new("application", "scim+json"))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+pub type ScimResult<T> = Result<ScimJson<T>, ScimError>;
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ListResponse<T> {
+    schemas: [&'static str; 1],
+    total_results: u64,
+    start_index: u64,
+    items_per_page: u64,
+    #[serde(rename = "Resources")]
+    resources: Vec<T>,
+}
+
+impl<T> ListResponse<T> {
+    pub fn new(resources: Vec<T>, total_results: u64, page: &Page) -> Self {
+        Self {
+            schemas: [LIST_RESPONSE_SCHEMA],
+            total_results,
+            start_index: page.start_index,
+            items_per_page: resources.len() as u64,
+            resources,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: &'static str,
+    pub created: String,
+    pub location: String,
+}
+
+/// Query parameters of the list endpoints
+#[derive(FromForm)]
+pub struct ListQuery<'r> {
+    pub filter: Option<&'r str>,
+    #[field(name = "startIndex")]
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+    #[field(name = "excludedAttributes")]
+    pub excluded_attributes: Option<&'r str>,
+}
+
+/// Query parameters of the endpoints returning a single resource
+#[derive(FromForm)]
+pub struct ResourceQuery<'r> {
+    #[field(name = "excludedAttributes")]
+    pub excluded_attributes: Option<&'r str>,
+}
+
+/// Whether `excludedAttributes` contains the attribute, used to skip expensive attributes
+pub fn is_excluded(excluded_attributes: Option<&str>, attribute: &str) -> bool {
+    excluded_attributes.is_some_and(|excluded| {
+        excluded
+            .split(',')
+            .any(|excluded| excluded.trim().eq_ignore_ascii_case(attribute))
+    })
+}
+
+/// Index based pagination, `startIndex` is 1-based
+pub struct Page {
+    pub start_index: u64,
+    pub count: u64,
+}
+
+impl Page {
+    pub fn new(query: &ListQuery<'_>) -> Self {
+        Self {
+            start_index: query.start_index.unwrap_or(1).max(1) as u64,
+            count: query
+                .count
+                .map(|count| count.clamp(0, MAX_PAGE_SIZE as i64) as u64)
+                .unwrap_or(DEFAULT_PAGE_SIZE),
+        }
+    }
+
+    pub fn skip(&self) -> u64 {
+        self.start_index - 1
+    }
+}
+
+/// A filter of the form `attribute eq "value"`, the only filter the API supports
+pub struct EqFilter {
+    pub attribute: String,
+    pub value: String,
+}
+
+impl EqFilter {
+    pub fn parse(filter: &str) -> Result<Self, ScimError> {
+        let invalid_filter = || {
+            ScimError::bad_request(
+                "invalidFilter",
+                "Only 'attribute eq \"value\"' filters are supported",
+            )
+        };
+
+        let (attribute, rest) = filter
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(invalid_filter)?;
+        let (operator, value) = rest
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .ok_or_else(invalid_filter)?;
+        if !operator.eq_ignore_ascii_case("eq") {
+            return Err(invalid_filter());
+        }
+
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .ok_or_else(invalid_filter)?;
+
+        Ok(Self {
+            // Attribute names are case insensitive
+            attribute: attribute.to_lowercase(),
+            value: value.replace("\\\"", "\""),
+        })
+    }
+}
+
+/// Public url of a SCIM resource
+pub fn get_location(resource: &str, id: &str) -> String {
+    format!(
+        "{}/scim/v2/{}/{}",
+        crate::auth::oidc::get_issuer(),
+        resource,
+        id
+    )
+}
@@ -0,0 +1,268 @@
+use rocket::serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{EqFilter, ScimError};
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PatchOperation {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Replace,
+    Remove,
+}
+
+/// A path of the form `attribute`, `attribute.sub` or `attribute[sub eq "value"].sub`
+struct Path {
+    attribute: String,
+    filter: Option<EqFilter>,
+    sub_attribute: Option<String>,
+}
+
+impl Path {
+    fn parse(path: &str) -> Result<Self, ScimError> {
+        let invalid_path =
+            || ScimError::bad_request("invalidPath", format!("Invalid path {}", path));
+
+        // Attributes of the core schemas can be prefixed with the schema urn
+        let path = match path.strip_prefix("urn:") {
+            Some(_) => match path.find('[') {
+                Some(bracket) => &path[path[..bracket].rfind(':').ok_or_else(invalid_path)? + 1..],
+                None => &path[path.rfind(':').ok_or_else(invalid_path)? + 1..],
+            },
+            None => path,
+        };
+
+        let (attribute, filter, rest) = match path.split_once('[') {
+            Some((attribute, rest)) => {
+                let (filter, rest) = rest.split_once(']').ok_or_else(invalid_path)?;
+                (attribute, Some(EqFilter::parse(filter)?), rest)
+            }
+            None => match path.split_once('.') {
+                Some((attribute, sub_attribute)) => (attribute, None, sub_attribute),
+                None => (path, None, ""),
+            },
+        };
+
+        let sub_attribute = rest.trim_start_matches('.');
+        if attribute.is_empty() || sub_attribute.contains(['.', '[']) {
+            return Err(invalid_path());
+        }
+
+        Ok(Self {
+            attribute: attribute.to_string(),
+            filter,
+            sub_attribute: (!sub_attribute.is_empty()).then(|| sub_attribute.to_string()),
+        })
+    }
+}
+
+impl PatchRequest {
+    /// Applies the operations to the JSON representation of a resource
+    pub fn apply(&self, resource: &mut Value) -> Result<(), ScimError> {
+        let Some(resource) = resource.as_object_mut() else {
+            return Err(ScimError::internal("Resource is not an object"));
+        };
+
+        for operation in &self.operations {
+            operation.apply(resource)?;
+        }
+        Ok(())
+    }
+}
+
+impl PatchOperation {
+    fn apply(&self, resource: &mut Map<String, Value>) -> Result<(), ScimError> {
+        let op = match self.op.to_lowercase().as_str() {
+            "add" => Op::Add,
+            "replace" => Op::Replace,
+            "remove" => Op::Remove,
+            _ => {
+                return Err(ScimError::bad_request(
+                    "invalidSyntax",
+                    format!("Unknown operation {}", self.op),
+                ))
+            }
+        };
+
+        let value = match (op, &self.value) {
+            (_, Some(value)) => value.clone(),
+            (Op::Remove, None) => Value::Null,
+            (_, None) => {
+                return Err(ScimError::bad_request(
+                    "invalidValue",
+                    "Add and replace operations need a value",
+                ))
+            }
+        };
+
+        let Some(path) = &self.path else {
+            // Without a path the value holds the attributes to add or replace
+            let Value::Object(attributes) = value else {
+                return Err(ScimError::bad_request(
+                    "noTarget",
+                    "Operations without a path need an object value",
+                ));
+            };
+            for (attribute, value) in attributes {
+                apply_path(resource, op, &Path::parse(&attribute)?, value)?;
+            }
+            return Ok(());
+        };
+
+        apply_path(resource, op, &Path::parse(path)?, value)
+    }
+}
+
+fn apply_path(
+    resource: &mut Map<String, Value>,
+    op: Op,
+    path: &Path,
+    value: Value,
+) -> Result<(), ScimError> {
+    let key = find_key(resource, &path.attribute);
+
+    let Some(filter) = &path.filter else {
+        return match &path.sub_attribute {
+            Some(sub_attribute) => {
+                let target = resource
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if target.is_null() {
+                    *target = Value::Object(Map::new());
+                }
+                let Some(target) = target.as_object_mut() else {
+                    return Err(ScimError::bad_request(
+                        "invalidPath",
+                        format!("{} is not a complex attribute", path.attribute),
+                    ));
+                };
+                set_value(target, op, sub_attribute, value);
+                Ok(())
+            }
+            None => {
+                set_value(resource, op, &key, value);
+                Ok(())
+            }
+        };
+    };
+
+    let elements = match resource.get_mut(&key) {
+        Some(Value::Array(elements)) => elements,
+        _ if op == Op::Remove => return Ok(()),
+        _ => {
+            resource.insert(key.clone(), Value::Array(Vec::new()));
+            match resource.get_mut(&key) {
+                Some(Value::Array(elements)) => elements,
+                _ => return Err(ScimError::internal("Failed to create attribute")),
+            }
+        }
+    };
+
+    match (op, &path.sub_attribute) {
+        (Op::Remove, None) => elements.retain(|element| !matches_filter(element, filter)),
+        (Op::Remove, Some(sub_attribute)) => {
+            for element in elements.iter_mut().filter(|e| matches_filter(e, filter)) {
+                if let Some(element) = element.as_object_mut() {
+                    let key = find_key(element, sub_attribute);
+                    element.remove(&key);
+                }
+            }
+        }
+        (_, sub_attribute) => {
+            let mut matched = false;
+            for element in elements.iter_mut().filter(|e| matches_filter(e, filter)) {
+                matched = true;
+                match (sub_attribute, element.as_object_mut()) {
+                    (Some(sub_attribute), Some(element)) => {
+                        set_value(element, op, sub_attribute, value.clone())
+                    }
+                    _ => *element = value.clone(),
+                }
+            }
+
+            // Nothing matched, so the filter describes the element to create
+            if !matched {
+                let mut element = Map::new();
+                element.insert(
+                    filter.attribute.clone(),
+                    Value::String(filter.value.clone()),
+                );
+                match (sub_attribute, value) {
+                    (Some(sub_attribute), value) => {
+                        element.insert(sub_attribute.clone(), value);
+                    }
+                    (None, Value::Object(attributes)) => element.extend(attributes),
+                    (None, _) => {
+                        return Err(ScimError::bad_request(
+                            "invalidValue",
+                            "Value must be an object",
+                        ))
+                    }
+                }
+                elements.push(Value::Object(element));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set_value(target: &mut Map<String, Value>, op: Op, attribute: &str, value: Value) {
+    let key = find_key(target, attribute);
+    match (op, target.get_mut(&key), value) {
+        // Removing with a value only removes the given elements of a multi-valued attribute
+        (Op::Remove, Some(Value::Array(existing)), Value::Array(values)) => {
+            existing.retain(|element| !values.iter().any(|value| same_element(element, value)))
+        }
+        (Op::Remove, _, _) => {
+            target.remove(&key);
+        }
+        // Adding to a multi-valued attribute appends instead of replacing
+        (Op::Add, Some(Value::Array(existing)), Value::Array(values)) => existing.extend(values),
+        (Op::Add, Some(Value::Object(existing)), Value::Object(values)) => existing.extend(values),
+        (_, _, value) => {
+            target.insert(key, value);
+        }
+    }
+}
+
+// Attribute names are case insensitive, existing keys keep their spelling
+fn find_key(object: &Map<String, Value>, attribute: &str) -> String {
+    object
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(attribute))
+        .cloned()
+        .unwrap_or_else(|| attribute.to_string())
+}
+
+// Elements of multi-valued attributes are identified by their `value`
+fn same_element(element: &Value, other: &Value) -> bool {
+    match (element.get("value"), other.get("value")) {
+        (Some(value), Some(other)) => value == other,
+        _ => element == other,
+    }
+}
+
+fn matches_filter(element: &Value, filter: &EqFilter) -> bool {
+    let Some(element) = element.as_object() else {
+        return false;
+    };
+    match element.get(&find_key(element, &filter.attribute)) {
+        Some(Value::String(value)) => *value == filter.value,
+        Some(value) => value.to_string() == filter.value,
+        None => false,
+    }
+}
@@ -0,0 +1,184 @@
+use mongodb::bson::Uuid;
+use rocket::serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::{get_location, ScimMeta, USER_SCHEMA};
+use crate::models::user::User;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_name: String,
+    #[serde(default)]
+    pub name: ScimName,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    #[serde(default = "default_active", deserialize_with = "deserialize_bool")]
+    pub active: bool,
+    // Write only, never returned
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    // Group membership is managed through the groups
+    #[serde(default, skip_deserializing)]
+    pub groups: Vec<ScimMember>,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScimEmail {
+    pub value: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub email_type: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_bool")]
+    pub primary: bool,
+}
+
+/// Member of a group or group of a user
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScimMember {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// Some identity providers send booleans as "True" and "False" strings
+fn deserialize_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Bool(value) => Ok(value),
+        Value::String(value) if value.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(value) if value.eq_ignore_ascii_case("false") => Ok(false),
+        Value::Null => Ok(false),
+        value => Err(rocket::serde::de::Error::custom(format!(
+            "Expected a boolean, got {}",
+            value
+        ))),
+    }
+}
+
+impl ScimUser {
+    /// `role_names` maps role ids to names, roles missing from it are left out of `groups`
+    pub fn from_user(user: &User, role_names: &HashMap<Uuid, String>) -> Self {
+        let id = user.id.to_string();
+        Self {
+            schemas: vec![USER_SCHEMA.to_string()],
+            id: Some(id.clone()),
+            user_name: user.email.clone(),
+            name: ScimName {
+                given_name: Some(user.first_name.clone()),
+                family_name: Some(user.last_name.clone()),
+                formatted: Some(
+                    format!("{} {}", user.first_name, user.last_name)
+                        .trim()
+                        .to_string(),
+                ),
+            },
+            display_name: Some(
+                format!("{} {}", user.first_name, user.last_name)
+                    .trim()
+                    .to_string(),
+            ),
+            emails: vec![ScimEmail {
+                value: user.email.clone(),
+                email_type: Some("work".to_string()),
+                primary: true,
+            }],
+            active: !user.disabled,
+            password: None,
+            groups: user
+                .roles
+                .iter()
+                .filter_map(|role_id| {
+                    role_names.get(role_id).map(|name| ScimMember {
+                        value: role_id.to_string(),
+                        display: Some(name.clone()),
+                        reference: Some(get_location("Groups", &role_id.to_string())),
+                    })
+                })
+                .collect(),
+            meta: Some(ScimMeta {
+                resource_type: "User",
+                created: user.created_at.try_to_rfc3339_string().unwrap_or_default(),
+                location: get_location("Users", &id),
+            }),
+        }
+    }
+
+    /// The email of the user, `userName` if it is an email address, otherwise the primary email
+    pub fn email(&self) -> String {
+        let email = if self.user_name.contains('@') {
+            &self.user_name
+        } else {
+            self.emails
+                .iter()
+                .find(|email| email.primary)
+                .or(self.emails.first())
+                .map(|email| &email.value)
+                .unwrap_or(&self.user_name)
+        };
+        email.trim().to_lowercase()
+    }
+
+    pub fn first_name(&self) -> String {
+        match &self.name.given_name {
+            Some(given_name) => given_name.trim().to_string(),
+            None => self
+                .display_name
+                .as_deref()
+                .and_then(|name| name.split_whitespace().next())
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    pub fn last_name(&self) -> String {
+        match &self.name.family_name {
+            Some(family_name) => family_name.trim().to_string(),
+            None => self
+                .display_name
+                .as_deref()
+                .and_then(|name| name.trim().split_once(char::is_whitespace))
+                .map(|(_, last_name)| last_name.trim())
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+}
+
+impl ScimMember {
+    pub fn from_user(user: &User) -> Self {
+        Self {
+            value: user.id.to_string(),
+            display: Some(user.email.clone()),
+            reference: Some(get_location("Users", &user.id.to_string())),
+        }
+    }
+}
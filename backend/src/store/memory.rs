@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use mongodb::bson::Bson;
+use rocket::{async_trait, tokio::sync::Mutex};
+
+use super::{EphemeralStore, StoreResult};
+
+/// Keeps the state in process memory, only suitable for tests and single instance deployments
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<(String, String), (Bson, Instant)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EphemeralStore for MemoryStore {
+    async fn put(&self, namespace: &str, key: &str, value: Bson, ttl: Duration) -> StoreResult<()> {
+        let mut entries = self.entries.lock().await;
+        entries.insert(
+            (namespace.to_string(), key.to_string()),
+            (value, Instant::now() + ttl),
+        );
+
+        Ok(())
+    }
+
+    async fn get(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>> {
+        let entries = self.entries.lock().await;
+
+        match entries.get(&(namespace.to_string(), key.to_string())) {
+            Some((value, expires_at)) if *expires_at > Instant::now() => Ok(Some(value.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    async fn take(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>> {
+        let mut entries = self.entries.lock().await;
+
+        match entries.remove(&(namespace.to_string(), key.to_string())) {
+            Some((value, expires_at)) if expires_at > Instant::now() => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn purge_expired(&self) -> StoreResult<u64> {
+        let mut entries = self.entries.lock().await;
+
+        let now = Instant::now();
+        let count = entries.len();
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+
+        Ok((count - entries.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash_token::hash_token;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn value() -> Bson {
+        Bson::String("value".to_string())
+    }
+
+    #[rocket::async_test]
+    async fn take_is_single_use() {
+        let store = MemoryStore::new();
+        store.put("flows", "key", value(), TTL).await.unwrap();
+
+        assert_eq!(store.take("flows", "key").await.unwrap(), Some(value()));
+        assert_eq!(store.take("flows", "key").await.unwrap(), None);
+        assert_eq!(store.get("flows", "key").await.unwrap(), None);
+    }
+
+    #[rocket::async_test]
+    async fn get_does_not_consume() {
+        let store = MemoryStore::new();
+        store.put("flows", "key", value(), TTL).await.unwrap();
+
+        assert_eq!(store.get("flows", "key").await.unwrap(), Some(value()));
+        assert_eq!(store.get("flows", "key").await.unwrap(), Some(value()));
+        assert_eq!(store.take("flows", "key").await.unwrap(), Some(value()));
+    }
+
+    #[rocket::async_test]
+    async fn expired_values_are_gone() {
+        let store = MemoryStore::new();
+        store
+            .put("flows", "expired", value(), Duration::ZERO)
+            .await
+            .unwrap();
+        store.put("flows", "valid", value(), TTL).await.unwrap();
+
+        assert_eq!(store.get("flows", "expired").await.unwrap(), None);
+        assert_eq!(store.take("flows", "expired").await.unwrap(), None);
+
+        store
+            .put("flows", "expired", value(), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+        assert_eq!(store.get("flows", "valid").await.unwrap(), Some(value()));
+    }
+
+    #[rocket::async_test]
+    async fn put_replaces_the_value() {
+        let store = MemoryStore::new();
+        store.put("flows", "key", value(), TTL).await.unwrap();
+        store
+            .put("flows", "key", Bson::Int32(1), TTL)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.take("flows", "key").await.unwrap(),
+            Some(Bson::Int32(1))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn namespaces_are_separate() {
+        let store = MemoryStore::new();
+        store.put("flows", "key", value(), TTL).await.unwrap();
+
+        assert_eq!(store.take("codes", "key").await.unwrap(), None);
+        assert_eq!(store.take("flows", "key").await.unwrap(), Some(value()));
+    }
+
+    #[rocket::async_test]
+    async fn hashed_keys_are_not_found_by_the_token() {
+        let store = MemoryStore::new();
+        let token = "reset-token";
+        store
+            .put("password-resets", &hash_token(token), value(), TTL)
+            .await
+            .unwrap();
+
+        assert_eq!(hash_token(token), hash_token(token));
+        assert_ne!(hash_token(token), hash_token("other-token"));
+        assert_eq!(store.get("password-resets", token).await.unwrap(), None);
+        assert_eq!(
+            store
+                .take("password-resets", &hash_token(token))
+                .await
+                .unwrap(),
+            Some(value())
+        );
+    }
+}
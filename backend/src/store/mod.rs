@@ -0,0 +1,137 @@
+pub mod memory;
+pub mod mongo;
+
+use std::{env, sync::Arc, time::Duration};
+
+use mongodb::bson::{self, Bson};
+use rocket::{
+    async_trait,
+    serde::{de::DeserializeOwned, Serialize},
+    tokio::{self, sync::RwLock},
+};
+use thiserror::Error;
+
+use crate::{
+    db::{get_main_db_name, AuthRsDatabase},
+    errors::{ApiError, AppError},
+};
+
+use self::{memory::MemoryStore, mongo::MongoStore};
+
+// Short lived state (mfa flows, passkey ceremonies, authorization codes) expires after 5 minutes
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+
+    #[error("Backend error: {0}")]
+    BackendError(String),
+}
+
+impl From<StoreError> for AppError {
+    fn from(error: StoreError) -> Self {
+        AppError::InternalServerError(error.to_string())
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(error: StoreError) -> Self {
+        ApiError::InternalError(error.to_string())
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// Storage for short lived state that has to survive restarts and be shared between replicas
+#[async_trait]
+pub trait EphemeralStore: Send + Sync {
+    /// Stores the value, replacing any existing value with the same key
+    async fn put(&self, namespace: &str, key: &str, value: Bson, ttl: Duration) -> StoreResult<()>;
+
+    /// Returns the value if it exists and has not expired
+    async fn get(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>>;
+
+    /// Atomically returns and removes the value, so it can only be taken once
+    async fn take(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>>;
+
+    /// Removes all expired values and returns how many were removed
+    async fn purge_expired(&self) -> StoreResult<u64>;
+}
+
+lazy_static::lazy_static! {
+    static ref EPHEMERAL_STORE: RwLock<Arc<dyn EphemeralStore>> =
+        RwLock::new(Arc::new(MemoryStore::new()));
+}
+
+/// Selects the backend configured via `EPHEMERAL_STORE` and starts the background cleanup
+pub async fn initialize(db: &AuthRsDatabase) -> StoreResult<()> {
+    let backend = env::var("EPHEMERAL_STORE").unwrap_or_else(|_| "mongodb".to_string());
+
+    let store: Arc<dyn EphemeralStore> = match backend.to_lowercase().as_str() {
+        "memory" => Arc::new(MemoryStore::new()),
+        "mongodb" => Arc::new(MongoStore::initialize(db.database(get_main_db_name())).await?),
+        other => {
+            return Err(StoreError::BackendError(format!(
+                "Unknown ephemeral store backend: {}",
+                other
+            )))
+        }
+    };
+
+    *EPHEMERAL_STORE.write().await = store;
+    tracing::info!(backend = %backend, "Ephemeral store initialized");
+
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+
+            match get_store().await.purge_expired().await {
+                Ok(0) => {}
+                Ok(removed) => tracing::debug!(removed, "Purged expired ephemeral state"),
+                Err(err) => {
+                    tracing::error!(error = %err, "Failed to purge expired ephemeral state")
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+pub async fn get_store() -> Arc<dyn EphemeralStore> {
+    EPHEMERAL_STORE.read().await.clone()
+}
+
+pub async fn put<T: Serialize>(
+    namespace: &str,
+    key: &str,
+    value: &T,
+    ttl: Duration,
+) -> StoreResult<()> {
+    let value =
+        bson::to_bson(value).map_err(|err| StoreError::SerializationError(err.to_string()))?;
+
+    get_store().await.put(namespace, key, value, ttl).await
+}
+
+pub async fn get<T: DeserializeOwned>(namespace: &str, key: &str) -> StoreResult<Option<T>> {
+    match get_store().await.get(namespace, key).await? {
+        Some(value) => from_bson(value).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub async fn take<T: DeserializeOwned>(namespace: &str, key: &str) -> StoreResult<Option<T>> {
+    match get_store().await.take(namespace, key).await? {
+        Some(value) => from_bson(value).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn from_bson<T: DeserializeOwned>(value: Bson) -> StoreResult<T> {
+    bson::from_bson(value).map_err(|err| StoreError::SerializationError(err.to_string()))
+}
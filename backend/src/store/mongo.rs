@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use mongodb::bson::{doc, Bson, DateTime};
+use rocket::{
+    async_trait,
+    serde::{Deserialize, Serialize},
+};
+use rocket_db_pools::mongodb::{
+    options::{IndexOptions, ReplaceOptions},
+    Collection, Database, IndexModel,
+};
+
+use super::{EphemeralStore, StoreError, StoreResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+struct EphemeralEntry {
+    #[serde(rename = "_id")]
+    id: String,
+    namespace: String,
+    key: String,
+    value: Bson,
+    expires_at: DateTime,
+}
+
+/// Keeps the state in MongoDB, so every replica sees the same state
+pub struct MongoStore {
+    collection: Collection<EphemeralEntry>,
+}
+
+impl MongoStore {
+    pub const COLLECTION_NAME: &'static str = "ephemeral-state";
+
+    /// Creates the TTL index, MongoDB removes expired entries on its own as well
+    pub async fn initialize(db: Database) -> StoreResult<Self> {
+        let collection: Collection<EphemeralEntry> = db.collection(Self::COLLECTION_NAME);
+
+        let index = IndexModel::builder()
+            .keys(doc! { "expiresAt": 1 })
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(0))
+                    .build(),
+            )
+            .build();
+
+        collection
+            .create_index(index, None)
+            .await
+            .map_err(|err| StoreError::BackendError(err.to_string()))?;
+
+        Ok(Self { collection })
+    }
+
+    fn entry_id(namespace: &str, key: &str) -> String {
+        format!("{}:{}", namespace, key)
+    }
+}
+
+#[async_trait]
+impl EphemeralStore for MongoStore {
+    async fn put(&self, namespace: &str, key: &str, value: Bson, ttl: Duration) -> StoreResult<()> {
+        let entry = EphemeralEntry {
+            id: Self::entry_id(namespace, key),
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+            value,
+            expires_at: DateTime::from_millis(
+                DateTime::now().timestamp_millis() + ttl.as_millis() as i64,
+            ),
+        };
+
+        let filter = doc! {
+            "_id": &entry.id
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        match self.collection.replace_one(filter, entry, options).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(StoreError::BackendError(err.to_string())),
+        }
+    }
+
+    async fn get(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>> {
+        // The TTL monitor only runs once a minute, so expired entries have to be filtered out
+        let filter = doc! {
+            "_id": Self::entry_id(namespace, key),
+            "expiresAt": { "$gt": DateTime::now() }
+        };
+        match self.collection.find_one(filter, None).await {
+            Ok(entry) => Ok(entry.map(|entry| entry.value)),
+            Err(err) => Err(StoreError::BackendError(err.to_string())),
+        }
+    }
+
+    async fn take(&self, namespace: &str, key: &str) -> StoreResult<Option<Bson>> {
+        let filter = doc! {
+            "_id": Self::entry_id(namespace, key),
+            "expiresAt": { "$gt": DateTime::now() }
+        };
+        match self.collection.find_one_and_delete(filter, None).await {
+            Ok(entry) => Ok(entry.map(|entry| entry.value)),
+            Err(err) => Err(StoreError::BackendError(err.to_string())),
+        }
+    }
+
+    async fn purge_expired(&self) -> StoreResult<u64> {
+        let filter = doc! {
+            "expiresAt": { "$lte": DateTime::now() }
+        };
+        match self.collection.delete_many(filter, None).await {
+            Ok(result) => Ok(result.deleted_count),
+            Err(err) => Err(StoreError::BackendError(err.to_string())),
+        }
+    }
+}
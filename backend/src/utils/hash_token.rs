@@ -0,0 +1,6 @@
+use sha2::{Digest, Sha256};
+
+/// Hashes high entropy tokens before storing them, so a database leak does not leak usable tokens
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -1,2 +1,5 @@
+pub mod hash_token;
+pub mod pagination;
 pub mod parse_uuid;
 pub mod response;
+pub mod url_token;
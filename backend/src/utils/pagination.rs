@@ -0,0 +1,247 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use mongodb::bson::{self, doc, Bson, Document};
+use rocket::{
+    futures::TryStreamExt,
+    serde::{de::DeserializeOwned, Serialize},
+    FromForm,
+};
+use rocket_db_pools::mongodb::{error::Result as MongoResult, options::FindOptions, Collection};
+
+pub const DEFAULT_LIMIT: u64 = 50;
+pub const MAX_LIMIT: u64 = 500;
+// Every skipped item is still read, deeper pages have to use the cursor
+pub const MAX_OFFSET: u64 = 10_000;
+
+/// Query parameters shared by the list routes.
+///
+/// Pages hold `limit` items and continue at `cursor` (the `nextCursor` of the previous page)
+/// or at `offset` (at most `MAX_OFFSET`). `sort` is a field name, prefixed with `-` for descending order. Each route
+/// only applies the filters that exist on its collection.
+#[derive(Debug, Default, FromForm)]
+pub struct PageQuery {
+    pub limit: Option<u64>,
+    pub cursor: Option<String>,
+    pub offset: Option<u64>,
+    pub sort: Option<String>,
+    // Email prefix
+    pub email: Option<String>,
+    // Name prefix
+    pub name: Option<String>,
+    pub disabled: Option<bool>,
+    // Role id
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Number of items matching the filters over all pages
+    pub total: u64,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// Validated paging parameters, sorted by the sort field with `_id` as tie breaker
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    limit: u64,
+    offset: u64,
+    sort_field: String,
+    descending: bool,
+    // Sort value and id of the last item of the previous page
+    after: Option<(Bson, Bson)>,
+}
+
+impl PageQuery {
+    /// `sort_fields` are the fields the route allows sorting by
+    pub fn pagination(
+        &self,
+        sort_fields: &[&str],
+        default_sort: &str,
+    ) -> Result<Pagination, String> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(format!("Limit must be between 1 and {}", MAX_LIMIT));
+        }
+        if self.cursor.is_some() && self.offset.is_some() {
+            return Err("Use either cursor or offset, not both".to_string());
+        }
+        if self.offset.is_some_and(|offset| offset > MAX_OFFSET) {
+            return Err(format!(
+                "Offset must be at most {}, use the cursor for later pages",
+                MAX_OFFSET
+            ));
+        }
+
+        let sort = self.sort.as_deref().unwrap_or(default_sort);
+        let (sort_field, descending) = match sort.strip_prefix('-') {
+            Some(sort_field) => (sort_field, true),
+            None => (sort, false),
+        };
+        if !sort_fields.contains(&sort_field) {
+            return Err(format!("Sort must be one of {}", sort_fields.join(", ")));
+        }
+
+        let mut pagination = Pagination {
+            limit,
+            offset: self.offset.unwrap_or(0),
+            sort_field: sort_field.to_string(),
+            descending,
+            after: None,
+        };
+        if let Some(cursor) = &self.cursor {
+            pagination.after = Some(pagination.decode_cursor(cursor)?);
+        }
+        Ok(pagination)
+    }
+}
+
+impl Pagination {
+    /// Offset based pagination in creation order
+    pub fn with_offset(offset: u64, limit: u64) -> Self {
+        Self {
+            limit: limit.max(1),
+            offset,
+            sort_field: "createdAt".to_string(),
+            descending: false,
+            after: None,
+        }
+    }
+
+    pub fn sort(&self) -> Document {
+        let direction = if self.descending { -1 } else { 1 };
+        doc! {
+            &self.sort_field: direction,
+            "_id": direction
+        }
+    }
+
+    /// Selects the items after the cursor, `None` on the first page
+    pub fn cursor_filter(&self) -> Option<Document> {
+        let (value, id) = self.after.as_ref()?;
+        let operator = if self.descending { "$lt" } else { "$gt" };
+        Some(doc! {
+            "$or": [
+                { &self.sort_field: { operator: value.clone() } },
+                { &self.sort_field: value.clone(), "_id": { operator: id.clone() } }
+            ]
+        })
+    }
+
+    pub fn skip(&self) -> u64 {
+        self.offset
+    }
+
+    /// One more than the page size, the extra item tells whether there is a next page
+    pub fn fetch_limit(&self) -> i64 {
+        (self.limit + 1) as i64
+    }
+
+    /// Builds the page from up to `fetch_limit` items
+    pub fn to_page<T: Serialize>(&self, mut items: Vec<T>, total: u64) -> Page<T> {
+        let has_next = items.len() as u64 > self.limit;
+        items.truncate(self.limit as usize);
+
+        let next_cursor = match items.last() {
+            Some(last) if has_next => self.encode_cursor(last),
+            _ => None,
+        };
+        Page {
+            items,
+            total,
+            next_cursor,
+        }
+    }
+
+    /// Fetches the page of the items matching `filter`
+    pub async fn find<T>(
+        &self,
+        collection: &Collection<T>,
+        filter: Document,
+    ) -> MongoResult<Page<T>>
+    where
+        T: DeserializeOwned + Serialize + Unpin + Send + Sync,
+    {
+        let total = collection.count_documents(filter.clone(), None).await?;
+
+        let filter = match self.cursor_filter() {
+            Some(cursor_filter) => doc! { "$and": [filter, cursor_filter] },
+            None => filter,
+        };
+        let options = FindOptions::builder()
+            .sort(self.sort())
+            .skip(self.skip())
+            .limit(self.fetch_limit())
+            .build();
+        let items = collection
+            .find(filter, options)
+            .await?
+            .try_collect::<Vec<T>>()
+            .await?;
+
+        Ok(self.to_page(items, total))
+    }
+
+    // The cursor is the BSON of the last item's sort value and id, bound to the sort order
+    fn encode_cursor<T: Serialize>(&self, item: &T) -> Option<String> {
+        let item = bson::to_document(item).ok()?;
+        let cursor = doc! {
+            "sort": &self.sort_field,
+            "descending": self.descending,
+            "value": item.get(&self.sort_field).cloned().unwrap_or(Bson::Null),
+            "id": item.get("_id")?.clone()
+        };
+
+        let mut bytes = Vec::new();
+        cursor.to_writer(&mut bytes).ok()?;
+        Some(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode_cursor(&self, cursor: &str) -> Result<(Bson, Bson), String> {
+        let invalid_cursor = || "Invalid cursor".to_string();
+
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| invalid_cursor())?;
+        let cursor = Document::from_reader(&mut bytes.as_slice()).map_err(|_| invalid_cursor())?;
+
+        if cursor.get_str("sort") != Ok(self.sort_field.as_str())
+            || cursor.get_bool("descending") != Ok(self.descending)
+        {
+            return Err("The cursor belongs to another sort order".to_string());
+        }
+
+        match (cursor.get("value"), cursor.get("id")) {
+            (Some(value), Some(id)) => Ok((value.clone(), id.clone())),
+            _ => Err(invalid_cursor()),
+        }
+    }
+}
+
+/// Matches values starting with `prefix`, anchored so the field's index can be used
+pub fn starts_with(field: &str, prefix: &str) -> Document {
+    let mut pattern = String::from("^");
+    for c in prefix.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+
+    doc! {
+        field: {
+            "$regex": pattern
+        }
+    }
+}
@@ -10,6 +10,7 @@ pub fn json_response<T>(response: HttpResponse<T>) -> (Status, Json<HttpResponse
         401 => Status::Unauthorized,
         403 => Status::Forbidden,
         404 => Status::NotFound,
+        429 => Status::TooManyRequests,
         500 => Status::InternalServerError,
         _ => Status::InternalServerError,
     };
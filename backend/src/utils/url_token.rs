@@ -0,0 +1,10 @@
+use base64::{engine::general_purpose, Engine as _};
+use rand::Rng;
+
+/// Generates a random token that can be put into links without escaping
+pub fn generate_url_token() -> String {
+    let mut buffer = [0u8; 32];
+    rand::rng().fill(&mut buffer);
+
+    general_purpose::URL_SAFE_NO_PAD.encode(buffer)
+}
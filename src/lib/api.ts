@@ -10,9 +10,12 @@ import type RegistrationToken from "./models/RegistrationToken";
 import type RegistrationTokenUpdates from "./models/RegistrationTokenUpdates";
 import type Role from "./models/Role";
 import type RoleUpdates from "./models/RoleUpdates";
+import type Session from "./models/Session";
+import type MfaComplianceEntry from "./models/MfaComplianceEntry";
 import type Settings from "./models/Settings";
 import type SettingsUpdates from "./models/SettingsUpdates";
 import type User from "./models/User";
+import type UserImportResult from "./models/UserImportResult";
 import type UserUpdates from "./models/UserUpdates";
 import PasskeyUtils from "./passkeyUtils";
 
@@ -30,6 +33,40 @@ class AuthRsApi {
         this.token = token;
     }
 
+    // List endpoints are paginated, this follows the pages until the last one
+    private async getAllPages<T>(url: string): Promise<T[]> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const items: T[] = [];
+        let cursor: string | null = null;
+        do {
+            const params = new URLSearchParams({ limit: '500' });
+            if (cursor) {
+                params.set('cursor', cursor);
+            }
+
+            const response = await fetch(`${url}?${params}`, {
+                method: 'GET',
+                headers: {
+                    Authorization: `Bearer ${this.token}`,
+                },
+            });
+
+            if (response.ok) {
+                const data = await response.json();
+                items.push(...data.data.items);
+                cursor = data.data.nextCursor;
+            } else {
+                console.error((await response.json()));
+                throw new Error(`(${response.status}): ${response.statusText}`);
+            }
+        } while (cursor);
+
+        return items;
+    }
+
     async checkOnlineState(): Promise<boolean> {
         const response = await fetch(this.baseUrl);
 
@@ -73,6 +110,28 @@ class AuthRsApi {
         }
     }
 
+    async getMfaCompliance(): Promise<MfaComplianceEntry[]> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/admin/mfa-compliance`, {
+            method: 'GET',
+            headers: {
+                'Content-Type': 'application/json',
+                'Authorization': `Bearer ${this.token}`,
+            },
+        });
+
+        if (response.ok) {
+            const data = await response.json();
+            return data.data;
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
     async login(email: string, password: string) {
         const response = await fetch(`${this.baseUrl}/auth/login`, {
             method: 'POST',
@@ -122,6 +181,48 @@ class AuthRsApi {
         }
     }
 
+    async logout(): Promise<null> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/auth/logout`, {
+            method: 'POST',
+            headers: {
+                Authorization: `Bearer ${this.token}`,
+            },
+        });
+
+        if (response.ok) {
+            this.token = null;
+            return null;
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async logoutEverywhere(): Promise<null> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/auth/logout/all`, {
+            method: 'POST',
+            headers: {
+                Authorization: `Bearer ${this.token}`,
+            },
+        });
+
+        if (response.ok) {
+            this.token = null;
+            return null;
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
     async startPasskeyAuth() {
         const startResponse = await fetch(`${this.baseUrl}/auth/passkeys/authenticate/start`);
 
@@ -181,6 +282,75 @@ class AuthRsApi {
         }
     }
 
+    async mfaPasskey() {
+        if (!this.currentMfaFlowId) {
+            throw new Error('No MFA flow ID');
+        }
+
+        const startResponse = await fetch(`${this.baseUrl}/auth/mfa/passkey/start`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ flowId: this.currentMfaFlowId }),
+        });
+
+        if (!startResponse.ok) {
+            console.error((await startResponse.json()));
+            throw new Error(`(${startResponse.status}): ${startResponse.statusText}`);
+        }
+
+        const data = await startResponse.json();
+        const publicKey = data.data.publicKey;
+
+        publicKey.challenge = PasskeyUtils.base64URLStringToBuffer(publicKey.challenge);
+        publicKey.allowCredentials = (publicKey.allowCredentials ?? []).map((allowedCredential: { id: string; type: string; }) => ({
+            ...allowedCredential,
+            id: PasskeyUtils.base64URLStringToBuffer(allowedCredential.id),
+        }));
+
+        const credential = await navigator.credentials.get({ publicKey }) as PublicKeyCredential;
+
+        if (!credential) {
+            throw new Error('No credential created!');
+        }
+
+        const finishResponse = await fetch(`${this.baseUrl}/auth/mfa/passkey/finish`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({
+                flowId: this.currentMfaFlowId,
+                credential: {
+                    id: credential.id,
+                    rawId: PasskeyUtils.bufferToBase64URLString(credential.rawId),
+                    response: {
+                        // @ts-expect-error
+                        authenticatorData: PasskeyUtils.bufferToBase64URLString(credential.response.authenticatorData),
+                        clientDataJSON: PasskeyUtils.bufferToBase64URLString(credential.response.clientDataJSON),
+                        // @ts-expect-error
+                        signature: PasskeyUtils.bufferToBase64URLString(credential.response.signature),
+                        // @ts-expect-error
+                        userHandle: credential.response.userHandle ? PasskeyUtils.bufferToBase64URLString(credential.response.userHandle) : null,
+                    },
+                    extentions: credential.getClientExtensionResults(),
+                    type: credential.type
+                },
+            }),
+        });
+
+        if (finishResponse.ok) {
+            const finishData = await finishResponse.json();
+            new AuthStateManager(this.baseUrl).setToken(finishData.data.token);
+            this.token = finishData.data.token;
+            return finishData.data;
+        } else {
+            console.error((await finishResponse.json()));
+            throw new Error(`(${finishResponse.status}): ${finishResponse.statusText}`);
+        }
+    }
+
     async enableMfa(user: User, password: string) {
         if (!this.token) {
             throw new Error('No token');
@@ -230,6 +400,92 @@ class AuthRsApi {
         }
     }
 
+    async regenerateRecoveryCodes(user: User, password: string): Promise<string[]> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/users/${user._id}/mfa/recovery-codes`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+                Authorization: `Bearer ${this.token}`,
+            },
+            body: JSON.stringify({ password }),
+        });
+
+        if (response.ok) {
+            const data = await response.json();
+            return data.data.recoveryCodes;
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async requestPasswordReset(email: string): Promise<void> {
+        const response = await fetch(`${this.baseUrl}/auth/password-reset`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ email }),
+        });
+
+        if (!response.ok) {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async confirmPasswordReset(token: string, password: string): Promise<void> {
+        const response = await fetch(`${this.baseUrl}/auth/password-reset/confirm`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ token, password }),
+        });
+
+        if (!response.ok) {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async verifyEmail(token: string): Promise<User> {
+        const response = await fetch(`${this.baseUrl}/auth/verify-email`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ token }),
+        });
+
+        if (response.ok) {
+            const data = await response.json();
+            return data.data;
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async resendVerificationEmail(email: string): Promise<void> {
+        const response = await fetch(`${this.baseUrl}/auth/verify-email/resend`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ email }),
+        });
+
+        if (!response.ok) {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
     async createUser(email: string, password: string, firstName: string, lastName: string, registrationCode: string | null): Promise<User> {
         const response = await fetch(`${this.baseUrl}/users`, {
             method: 'POST',
@@ -270,12 +526,12 @@ class AuthRsApi {
         }
     }
 
-    async getAllUsers(): Promise<User[]> {
+    async getCurrentUserPermissions(): Promise<string[]> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/users`, {
+        const response = await fetch(`${this.baseUrl}/users/@me/permissions`, {
             method: 'GET',
             headers: {
                 Authorization: `Bearer ${this.token}`,
@@ -291,6 +547,10 @@ class AuthRsApi {
         }
     }
 
+    async getAllUsers(): Promise<User[]> {
+        return this.getAllPages(`${this.baseUrl}/users`);
+    }
+
     async registerPasskey(type: string = "virtual"): Promise<Passkey> {
         if (!this.token) {
             throw new Error('No token');
@@ -385,24 +645,7 @@ class AuthRsApi {
     }
 
     async getAllPasskeys(): Promise<Passkey[]> {
-        if (!this.token) {
-            throw new Error('No token');
-        }
-
-        const response = await fetch(`${this.baseUrl}/passkeys`, {
-            method: 'GET',
-            headers: {
-                Authorization: `Bearer ${this.token}`,
-            },
-        });
-
-        if (response.ok) {
-            const data = await response.json();
-            return data.data;
-        } else {
-            console.error((await response.json()));
-            throw new Error(`(${response.status}): ${response.statusText}`);
-        }
+        return this.getAllPages(`${this.baseUrl}/passkeys`);
     }
 
     async updatePasskey(passkeyId: string, updates: PasskeyUpdates): Promise<Passkey> {
@@ -494,18 +737,17 @@ class AuthRsApi {
         }
     }
 
-    async createRole(name: string): Promise<Role> {
+    async unlockUser(user: User): Promise<User> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/roles`, {
+        const response = await fetch(`${this.baseUrl}/users/${user._id}/unlock`, {
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json',
-                Authorization: `Bearer ${this.token}`,
+                'Authorization': `Bearer ${this.token}`,
             },
-            body: JSON.stringify({ name }),
         });
 
         if (response.ok) {
@@ -517,16 +759,62 @@ class AuthRsApi {
         }
     }
 
-    async getAllRoles(): Promise<Role[]> {
+    // Invalid rows come back as a result with errors instead of an exception
+    async importUsers(content: string, format: 'json' | 'csv', dryRun: boolean): Promise<UserImportResult> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/roles`, {
+        const response = await fetch(`${this.baseUrl}/users/import?dry_run=${dryRun}`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': format == 'csv' ? 'text/csv' : 'application/json',
+                'Authorization': `Bearer ${this.token}`,
+            },
+            body: content,
+        });
+
+        const data = await response.json();
+        if (response.ok || data.data) {
+            return data.data;
+        } else {
+            console.error(data);
+            throw new Error(`(${response.status}): ${data.message ?? response.statusText}`);
+        }
+    }
+
+    async exportUsers(format: 'json' | 'csv'): Promise<Blob> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/users/export?format=${format}`, {
             method: 'GET',
             headers: {
+                'Authorization': `Bearer ${this.token}`,
+            },
+        });
+
+        if (response.ok) {
+            return await response.blob();
+        } else {
+            console.error((await response.json()));
+            throw new Error(`(${response.status}): ${response.statusText}`);
+        }
+    }
+
+    async createRole(name: string, permissions: string[], parents: string[]): Promise<Role> {
+        if (!this.token) {
+            throw new Error('No token');
+        }
+
+        const response = await fetch(`${this.baseUrl}/roles`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
                 Authorization: `Bearer ${this.token}`,
             },
+            body: JSON.stringify({ name, permissions, parents }),
         });
 
         if (response.ok) {
@@ -538,6 +826,10 @@ class AuthRsApi {
         }
     }
 
+    async getAllRoles(): Promise<Role[]> {
+        return this.getAllPages(`${this.baseUrl}/roles`);
+    }
+
     async getRole(roleId: string): Promise<Role> {
         if (!this.token) {
             throw new Error('No token');
@@ -647,22 +939,20 @@ class AuthRsApi {
         }
     }
 
-    async createOAuthApplication(name: string, description: string | null, redirectUris: string[]): Promise<OAuthApplication> {
+    async getSessions(): Promise<Session[]> {
+        return this.getAllPages(`${this.baseUrl}/users/@me/sessions`);
+    }
+
+    async revokeSession(session: Session): Promise<null> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/oauth-applications`, {
-            method: 'POST',
+        const response = await fetch(`${this.baseUrl}/users/@me/sessions/${session._id}`, {
+            method: 'DELETE',
             headers: {
-                'Content-Type': 'application/json',
                 Authorization: `Bearer ${this.token}`,
             },
-            body: JSON.stringify({
-                name,
-                description,
-                redirectUris
-            }),
         });
 
         if (response.ok) {
@@ -674,17 +964,23 @@ class AuthRsApi {
         }
     }
 
-
-    async getOAuthApplication(clientId: string): Promise<OAuthApplication> {
+    async createOAuthApplication(name: string, description: string | null, redirectUris: string[], isPublic: boolean = false): Promise<OAuthApplication> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/oauth-applications/${clientId}`, {
-            method: 'GET',
+        const response = await fetch(`${this.baseUrl}/oauth-applications`, {
+            method: 'POST',
             headers: {
+                'Content-Type': 'application/json',
                 Authorization: `Bearer ${this.token}`,
             },
+            body: JSON.stringify({
+                name,
+                description,
+                redirectUris,
+                public: isPublic
+            }),
         });
 
         if (response.ok) {
@@ -696,12 +992,13 @@ class AuthRsApi {
         }
     }
 
-    async getOAuthApplications(): Promise<OAuthApplication[]> {
+
+    async getOAuthApplication(clientId: string): Promise<OAuthApplication> {
         if (!this.token) {
             throw new Error('No token');
         }
 
-        const response = await fetch(`${this.baseUrl}/oauth-applications`, {
+        const response = await fetch(`${this.baseUrl}/oauth-applications/${clientId}`, {
             method: 'GET',
             headers: {
                 Authorization: `Bearer ${this.token}`,
@@ -717,7 +1014,11 @@ class AuthRsApi {
         }
     }
 
-    async authorizeOAuthApplication(clientId: string, redirectUri: string, scope: string[]) {
+    async getOAuthApplications(): Promise<OAuthApplication[]> {
+        return this.getAllPages(`${this.baseUrl}/oauth-applications`);
+    }
+
+    async authorizeOAuthApplication(clientId: string, redirectUri: string, scope: string[], nonce: string | null = null, codeChallenge: string | null = null, codeChallengeMethod: string | null = null) {
         if (!this.token) {
             throw new Error('No token');
         }
@@ -732,6 +1033,9 @@ class AuthRsApi {
                 clientId,
                 redirectUri,
                 scope,
+                nonce,
+                codeChallenge,
+                codeChallengeMethod,
             })
         });
 
@@ -790,52 +1094,15 @@ class AuthRsApi {
     }
 
     async getAuditLogs(user: User | null): Promise<AuditLog[]> {
-        if (!this.token) {
-            throw new Error('No token');
-        }
-
-        let url: string;
         if (user) {
-            url = `${this.baseUrl}/users/${user._id}/audit-logs`;
-        } else {
-            url = `${this.baseUrl}/audit-logs`;
-        }
-
-        const response = await fetch(url, {
-            method: 'GET',
-            headers: {
-                Authorization: `Bearer ${this.token}`,
-            },
-        });
-
-        if (response.ok) {
-            const data = await response.json();
-            return data.data;
+            return this.getAllPages(`${this.baseUrl}/users/${user._id}/audit-logs`);
         } else {
-            console.error((await response.json()));
-            throw new Error(`(${response.status}): ${response.statusText}`);
+            return this.getAllPages(`${this.baseUrl}/audit-logs`);
         }
     }
 
     async getUsers(): Promise<User[]> {
-        if (!this.token) {
-            throw new Error('No token');
-        }
-
-        const response = await fetch(`${this.baseUrl}/users`, {
-            method: 'GET',
-            headers: {
-                Authorization: `Bearer ${this.token}`,
-            },
-        });
-
-        if (response.ok) {
-            const data = await response.json();
-            return data.data;
-        } else {
-            console.error((await response.json()));
-            throw new Error(`(${response.status}): ${response.statusText}`);
-        }
+        return this.getAllPages(`${this.baseUrl}/users`);
     }
 
     async createRegistrationToken(maxUses: number, expiresIn: number | null): Promise<RegistrationToken> {
@@ -883,24 +1150,7 @@ class AuthRsApi {
     }
 
     async getAllRegistrationTokens(): Promise<RegistrationToken[]> {
-        if (!this.token) {
-            throw new Error('No token');
-        }
-
-        const response = await fetch(`${this.baseUrl}/registration-tokens`, {
-            method: 'GET',
-            headers: {
-                Authorization: `Bearer ${this.token}`,
-            },
-        });
-
-        if (response.ok) {
-            const data = await response.json();
-            return data.data;
-        } else {
-            console.error((await response.json()));
-            throw new Error(`(${response.status}): ${response.statusText}`);
-        }
+        return this.getAllPages(`${this.baseUrl}/registration-tokens`);
     }
 
     async updateRegistrationToken(token: RegistrationToken, updates: RegistrationTokenUpdates): Promise<RegistrationToken> {
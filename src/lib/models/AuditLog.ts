@@ -24,19 +24,25 @@ class AuditLog {
     static auditLogChangeLogKeys: Record<string, string> = {
         // User
         "email": "Email",
+        "email_verified": "Email Verified",
         "first_name": "First Name",
         "last_name": "Last Name",
         "password": "Password",
         "totp_secret": "TOTP Secret",
+        "recovery_codes": "Recovery Codes",
         "disabled": "Disabled",
 
         // Role
         "name": "Name",
+        "permissions": "Permissions",
+        "parents": "Parent Roles",
 
         // OAuthApplication
         // "name": "Name", -> Duplicate key
         "description": "Description",
         "redirect_uris": "Redirect URIs",
+        "public": "Public Client",
+        "disclosed_roles": "Disclosed Roles",
 
         // RegistrationToken
         "max_uses": "Max Uses",
@@ -46,6 +52,11 @@ class AuditLog {
         // Settings
         "open_registration": "Open Registrations",
         "allow_oauth_apps_for_users": "Allow OAuth Apps for Users",
+        "mfa_required_roles": "Roles requiring MFA",
+        "mfa_grace_period_days": "MFA Grace Period (days)",
+        "require_email_verification": "Require Verified Emails",
+        "password_policy": "Password Policy",
+        "password_hashing": "Password Hashing",
     }
 
     static getCreatedAt(auditLog: AuditLog): Date {
@@ -56,8 +67,14 @@ class AuditLog {
 
 enum AuditLogAction {
     Create = 'Create',
+    Import = 'Import',
     Update = 'Update',
     Login = 'Login',
+    Logout = 'Logout',
+    LogoutEverywhere = 'LogoutEverywhere',
+    UseRecoveryCode = 'UseRecoveryCode',
+    Lockout = 'Lockout',
+    Unlock = 'Unlock',
     Delete = 'Delete',
 }
 
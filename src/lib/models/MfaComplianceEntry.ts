@@ -0,0 +1,18 @@
+import type User from './User';
+
+export default class MfaComplianceEntry {
+    user: User;
+    deadline: any | null;
+    overdue: boolean;
+
+    constructor(user: User, deadline: any | null, overdue: boolean) {
+        this.user = user;
+        this.deadline = deadline;
+        this.overdue = overdue;
+    }
+
+    static getDeadline(entry: MfaComplianceEntry): Date | null {
+        // @ts-ignore
+        return entry.deadline ? new Date(parseInt(entry.deadline.$date.$numberLong) ?? 0) : null;
+    }
+}
@@ -3,15 +3,19 @@ export default class OAuthApplication {
     name: string;
     description: string | null;
     redirectUris: string[];
+    public: boolean;
+    disclosedRoles: string[];
     owner: string;
     secret: string | null;
     createdAt: any;
 
-    constructor(_id: string, name: string, description: string | null, redirectUris: string[], owner: string, secret: string | null, createdAt: any) {
+    constructor(_id: string, name: string, description: string | null, redirectUris: string[], isPublic: boolean, disclosedRoles: string[], owner: string, secret: string | null, createdAt: any) {
         this._id = _id;
         this.name = name;
         this.description = description;
         this.redirectUris = redirectUris;
+        this.public = isPublic;
+        this.disclosedRoles = disclosedRoles;
         this.owner = owner;
         this.secret = secret;
         this.createdAt = createdAt;
@@ -2,10 +2,12 @@ export default class OAuthApplicationUpdates {
     public name: string | null;
     public description: string | null;
     public redirectUris: string[] | null;
+    public disclosedRoles: string[] | null;
 
-    constructor({ name, description, redirectUris }: { name: string | null, description: string | null, redirectUris: string[] | null }) {
+    constructor({ name, description, redirectUris, disclosedRoles = null }: { name: string | null, description: string | null, redirectUris: string[] | null, disclosedRoles?: string[] | null }) {
         this.name = name;
         this.description = description;
         this.redirectUris = redirectUris;
+        this.disclosedRoles = disclosedRoles;
     }
 }
\ No newline at end of file
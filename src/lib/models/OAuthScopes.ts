@@ -18,6 +18,9 @@ const INVALID_SCOPES = [
 ]
 
 const SCOPES: Record<string, { icon: string; description: string }> = {
+    'openid': { icon: 'fingerprint', description: 'Sign you in with your account' },
+    'profile': { icon: 'user', description: 'Read your name' },
+    'email': { icon: 'mail', description: 'Read your email address' },
     'user:read': { icon: 'user', description: 'Read your profile data' },
     'user:update': { icon: 'user-pen', description: 'Change your profile data' },
     'user:*': { icon: 'user-cog', description: 'Read and modify your profile' },
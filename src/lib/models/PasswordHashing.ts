@@ -0,0 +1,11 @@
+export default class PasswordHashing {
+    memoryKib: number;
+    iterations: number;
+    parallelism: number;
+
+    constructor(memoryKib: number, iterations: number, parallelism: number) {
+        this.memoryKib = memoryKib;
+        this.iterations = iterations;
+        this.parallelism = parallelism;
+    }
+}
@@ -0,0 +1,30 @@
+export default class PasswordPolicy {
+    minLength: number;
+    requireUppercase: boolean;
+    requireLowercase: boolean;
+    requireDigit: boolean;
+    requireSymbol: boolean;
+    denyPersonalInfo: boolean;
+    denyBreached: boolean;
+
+    constructor(minLength: number, requireUppercase: boolean, requireLowercase: boolean, requireDigit: boolean, requireSymbol: boolean, denyPersonalInfo: boolean, denyBreached: boolean) {
+        this.minLength = minLength;
+        this.requireUppercase = requireUppercase;
+        this.requireLowercase = requireLowercase;
+        this.requireDigit = requireDigit;
+        this.requireSymbol = requireSymbol;
+        this.denyPersonalInfo = denyPersonalInfo;
+        this.denyBreached = denyBreached;
+    }
+
+    // Only covers the rules the browser can check, the rest is validated by the backend
+    static meetsBasicRules(policy: PasswordPolicy | null | undefined, password: string): boolean {
+        if (!policy) return password.length >= 8;
+
+        return [...password].length >= policy.minLength
+            && (!policy.requireUppercase || /\p{Lu}/u.test(password))
+            && (!policy.requireLowercase || /\p{Ll}/u.test(password))
+            && (!policy.requireDigit || /[0-9]/.test(password))
+            && (!policy.requireSymbol || /[^\p{L}\p{N}]/u.test(password));
+    }
+}
@@ -0,0 +1,29 @@
+const PERMISSIONS: Record<string, { description: string }> = {
+    'users:read': { description: 'Read all users' },
+    'users:create': { description: 'Create users while registration is closed' },
+    'users:update': { description: 'Update other users' },
+    'users:disable': { description: 'Disable and enable users' },
+    'users:delete': { description: 'Delete users' },
+    'users:mfa': { description: 'Manage MFA of other users' },
+    'roles:create': { description: 'Create roles' },
+    'roles:update': { description: 'Update roles' },
+    'roles:delete': { description: 'Delete roles' },
+    'roles:assign': { description: 'Assign roles to users' },
+    'oauth_applications:create': { description: 'Create OAuth applications' },
+    'oauth_applications:read': { description: 'Read all OAuth applications' },
+    'oauth_applications:update': { description: 'Update all OAuth applications' },
+    'oauth_applications:delete': { description: 'Delete all OAuth applications' },
+    'connections:read': { description: 'Read connections of other users' },
+    'connections:delete': { description: 'Remove connections of other users' },
+    'registration_tokens:read': { description: 'Read registration codes' },
+    'registration_tokens:create': { description: 'Create registration codes' },
+    'registration_tokens:update': { description: 'Update registration codes' },
+    'registration_tokens:delete': { description: 'Delete registration codes' },
+    'passkeys:read': { description: 'Read all passkeys' },
+    'audit_logs:read': { description: 'Read all audit logs' },
+    'settings:update': { description: 'Change the system settings' },
+    'signing_keys:read': { description: 'Read the signing keys' },
+    'signing_keys:rotate': { description: 'Rotate the signing keys' }
+}
+
+export { PERMISSIONS }
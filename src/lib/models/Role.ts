@@ -2,12 +2,16 @@ export default class Role {
     _id: string;
     name: string;
     system: boolean;
+    permissions: string[];
+    parents: string[];
     createdAt: any;
 
-    constructor(_id: string, name: string, system: boolean, createdAt: any) {
+    constructor(_id: string, name: string, system: boolean, permissions: string[], parents: string[], createdAt: any) {
         this._id = _id;
         this.name = name;
         this.system = system;
+        this.permissions = permissions;
+        this.parents = parents;
         this.createdAt = createdAt;
     }
 
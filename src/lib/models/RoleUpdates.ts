@@ -1,7 +1,11 @@
 export default class RoleUpdates {
     public name: string | null;
+    public permissions: string[] | null;
+    public parents: string[] | null;
 
-    constructor({ name }: { name: string | null }) {
+    constructor({ name, permissions, parents }: { name: string | null, permissions: string[] | null, parents: string[] | null }) {
         this.name = name;
+        this.permissions = permissions;
+        this.parents = parents;
     }
 }
\ No newline at end of file
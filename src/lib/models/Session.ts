@@ -0,0 +1,29 @@
+export default class Session {
+    _id: string;
+    ipAddress: string | null;
+    userAgent: string | null;
+    lastSeenAt: any;
+    expiresAt: any;
+    createdAt: any;
+    current: boolean;
+
+    constructor(_id: string, ipAddress: string | null, userAgent: string | null, lastSeenAt: any, expiresAt: any, createdAt: any, current: boolean) {
+        this._id = _id;
+        this.ipAddress = ipAddress;
+        this.userAgent = userAgent;
+        this.lastSeenAt = lastSeenAt;
+        this.expiresAt = expiresAt;
+        this.createdAt = createdAt;
+        this.current = current;
+    }
+
+    static getLastSeenAt(session: Session): Date {
+        // @ts-ignore
+        return new Date(parseInt(session.lastSeenAt.$date.$numberLong) ?? 0);
+    }
+
+    static getCreatedAt(session: Session): Date {
+        // @ts-ignore
+        return new Date(parseInt(session.createdAt.$date.$numberLong) ?? 0);
+    }
+}
@@ -1,11 +1,24 @@
+import type PasswordHashing from './PasswordHashing';
+import type PasswordPolicy from './PasswordPolicy';
+
 export default class Settings {
     _id: string;
     openRegistration: boolean;
     allowOauthAppsForUsers: boolean;
+    mfaRequiredRoles: string[];
+    mfaGracePeriodDays: number;
+    requireEmailVerification: boolean;
+    passwordPolicy: PasswordPolicy;
+    passwordHashing: PasswordHashing;
 
-    constructor(_id: string, openRegistration: boolean, allowOauthAppsForUsers: boolean) {
+    constructor(_id: string, openRegistration: boolean, allowOauthAppsForUsers: boolean, mfaRequiredRoles: string[], mfaGracePeriodDays: number, requireEmailVerification: boolean, passwordPolicy: PasswordPolicy, passwordHashing: PasswordHashing) {
         this._id = _id;
         this.openRegistration = openRegistration;
         this.allowOauthAppsForUsers = allowOauthAppsForUsers;
+        this.mfaRequiredRoles = mfaRequiredRoles;
+        this.mfaGracePeriodDays = mfaGracePeriodDays;
+        this.requireEmailVerification = requireEmailVerification;
+        this.passwordPolicy = passwordPolicy;
+        this.passwordHashing = passwordHashing;
     }
 }
\ No newline at end of file
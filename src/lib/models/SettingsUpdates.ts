@@ -1,9 +1,22 @@
+import type PasswordHashing from './PasswordHashing';
+import type PasswordPolicy from './PasswordPolicy';
+
 export default class SettingsUpdates {
     public openRegistration: boolean | null;
     public allowOauthAppsForUsers: boolean | null;
+    public mfaRequiredRoles: string[] | null;
+    public mfaGracePeriodDays: number | null;
+    public requireEmailVerification: boolean | null;
+    public passwordPolicy: PasswordPolicy | null;
+    public passwordHashing: PasswordHashing | null;
 
-    constructor({ openRegistration, allowOauthAppsForUsers }: { openRegistration: boolean | null, allowOauthAppsForUsers: boolean | null }) {
+    constructor({ openRegistration, allowOauthAppsForUsers, mfaRequiredRoles = null, mfaGracePeriodDays = null, requireEmailVerification = null, passwordPolicy = null, passwordHashing = null }: { openRegistration: boolean | null, allowOauthAppsForUsers: boolean | null, mfaRequiredRoles?: string[] | null, mfaGracePeriodDays?: number | null, requireEmailVerification?: boolean | null, passwordPolicy?: PasswordPolicy | null, passwordHashing?: PasswordHashing | null }) {
         this.openRegistration = openRegistration;
         this.allowOauthAppsForUsers = allowOauthAppsForUsers;
+        this.mfaRequiredRoles = mfaRequiredRoles;
+        this.mfaGracePeriodDays = mfaGracePeriodDays;
+        this.requireEmailVerification = requireEmailVerification;
+        this.passwordPolicy = passwordPolicy;
+        this.passwordHashing = passwordHashing;
     }
 }
\ No newline at end of file
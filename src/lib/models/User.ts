@@ -1,20 +1,26 @@
 export default class User {
     _id: string;
     email: string;
+    emailVerified: boolean;
     firstName: string;
     lastName: string;
     roles: string[];
     mfa: boolean;
+    recoveryCodesRemaining: number;
+    lockedUntil: any | null;
     disabled: boolean;
     createdAt: any;
 
-    constructor(_id: string, email: string, firstName: string, lastName: string, roles: string[], mfa: boolean, disabled: boolean, createdAt: any) {
+    constructor(_id: string, email: string, emailVerified: boolean, firstName: string, lastName: string, roles: string[], mfa: boolean, recoveryCodesRemaining: number, lockedUntil: any | null, disabled: boolean, createdAt: any) {
         this._id = _id;
         this.email = email;
+        this.emailVerified = emailVerified;
         this.firstName = firstName;
         this.lastName = lastName;
         this.roles = roles;
         this.mfa = mfa;
+        this.recoveryCodesRemaining = recoveryCodesRemaining;
+        this.lockedUntil = lockedUntil;
         this.disabled = disabled;
         this.createdAt = createdAt;
     }
@@ -24,8 +30,14 @@ export default class User {
         return new Date(parseInt(user.createdAt.$date.$numberLong) ?? 0);
     }
 
-    static isAdmin(user: User): boolean {
-        return user._id == this.DEFAULT_USER_ID || user.roles.includes(this.ADMIN_ROLE_ID);
+    static getLockedUntil(user: User): Date | null {
+        // @ts-ignore
+        return user.lockedUntil ? new Date(parseInt(user.lockedUntil.$date.$numberLong) ?? 0) : null;
+    }
+
+    static isLocked(user: User): boolean {
+        const lockedUntil = this.getLockedUntil(user);
+        return lockedUntil != null && lockedUntil > new Date();
     }
 
     static isSystemAdmin(user: User): boolean {
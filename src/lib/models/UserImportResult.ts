@@ -0,0 +1,25 @@
+import type User from './User';
+
+export type UserImportRowError = {
+    row: number;
+    email: string;
+    message: string;
+};
+
+export default class UserImportResult {
+    dryRun: boolean;
+    total: number;
+    imported: number;
+    errors: UserImportRowError[];
+    users: User[];
+    // Entity id of the audit log entry, only set when users were imported
+    importId?: string;
+
+    constructor(dryRun: boolean, total: number, imported: number, errors: UserImportRowError[], users: User[]) {
+        this.dryRun = dryRun;
+        this.total = total;
+        this.imported = imported;
+        this.errors = errors;
+        this.users = users;
+    }
+}